@@ -75,6 +75,51 @@ export async function GetDashboardStats(req: any) {
   return await invoke('get_dashboard_stats', { req });
 }
 
+// 抓包相关
+export async function StartCapture(req: any) {
+  return await invoke('start_capture', { req });
+}
+
+export async function StopCapture(id: string) {
+  return await invoke('stop_capture', { id });
+}
+
+export async function GetCaptureSessions() {
+  return await invoke('get_capture_sessions');
+}
+
+export async function QueryCaptures(sessionId: string | null, page: number, pageSize: number) {
+  return await invoke('query_captures', { sessionId, page, pageSize });
+}
+
+export async function ClearCaptures(sessionId: string | null) {
+  return await invoke('clear_captures', { sessionId });
+}
+
+export async function ExportCaptureHar(sessionId: string | null) {
+  return await invoke('export_capture_har', { sessionId });
+}
+
+export async function ExplainRoute(
+  listenAddr: string,
+  host: string,
+  uri: string,
+  method: string,
+  headers?: Record<string, string>,
+  clientIp?: string,
+  body?: string,
+) {
+  return await invoke('explain_route', { listenAddr, host, uri, method, headers, body, clientIp });
+}
+
+export async function TestRouteRequest(req: any) {
+  return await invoke('test_route_request', { req });
+}
+
+export async function ReplayTraffic(req: any) {
+  return await invoke('replay_traffic', { req });
+}
+
 // 黑名单相关
 export async function AddBlacklistEntry(ip: string, reason: string, durationSeconds: number) {
   return await invoke('add_blacklist_entry', { ip, reason, durationSeconds });
@@ -92,6 +137,22 @@ export async function RefreshBlacklistCache() {
   return await invoke('refresh_blacklist_cache');
 }
 
+export async function CreateApiKey(req: any) {
+  return await invoke('create_api_key', { req });
+}
+
+export async function UpdateApiKey(req: any) {
+  return await invoke('update_api_key', { req });
+}
+
+export async function DeleteApiKey(name: string) {
+  return await invoke('delete_api_key', { name });
+}
+
+export async function ListApiKeys() {
+  return await invoke('list_api_keys');
+}
+
 // 数据库相关
 export async function GetMetricsDBStatus() {
   return await invoke('get_metrics_db_status');
@@ -0,0 +1,320 @@
+// 规则配置在后端 snake_case 结构与界面编辑用结构之间的转换
+
+export interface Upstream {
+  URL: string
+  Weight: number
+}
+
+export interface HeaderKV {
+  Key: string
+  Value: string
+}
+
+export interface Route {
+  ID?: string
+  Enabled?: boolean
+  Host: string
+  Path: string
+
+  ProxyPassPath?: string
+  FollowRedirects?: boolean
+  SetHeaders?: Record<string, string>
+  SetHeadersList?: HeaderKV[]
+
+  StaticDir?: string
+  ExcludeBasicAuth?: boolean
+  UrlRewriteRules?: UrlRewriteRule[]
+  RequestBodyReplace?: BodyReplaceRule[]
+  ResponseBodyReplace?: BodyReplaceRule[]
+  RemoveHeaders?: string[]
+
+  // 路由匹配增强（兼容 Nginx 风格）
+  Methods?: string[]
+  MatchHeadersList?: HeaderKV[]
+
+  // 由代理直接应答（界面暂不编辑，原样保留）
+  Return?: Record<string, any>
+
+  Upstreams: Upstream[]
+
+  // 后端返回的原始路由：保存时保留界面未编辑的字段
+  Raw?: Record<string, any>
+}
+
+export interface UrlRewriteRule {
+  Pattern: string
+  Replacement: string
+  Enabled?: boolean
+}
+
+export interface BodyReplaceRule {
+  Find: string
+  Replace: string
+  UseRegex?: boolean
+  Enabled?: boolean
+  ContentTypes?: string[]
+}
+
+export interface ListenRule {
+  ID?: string
+  Enabled?: boolean
+  ListenAddr: string         // 兼容旧字段（单个）
+  ListenAddrs?: string[]     // 新字段：多个监听地址
+  SSLEnable: boolean
+  CertFile: string
+  KeyFile: string
+  BasicAuthEnable?: boolean
+  BasicAuthUsername?: string
+  BasicAuthPassword?: string
+  BasicAuthForwardHeader?: boolean
+  RateLimitEnabled?: boolean
+  RateLimitRequestsPerSecond?: number
+  RateLimitBurstSize?: number
+  RateLimitBanSeconds?: number
+  Routes: Route[]
+
+  // 后端返回的原始规则：保存时保留界面未编辑的字段
+  Raw?: Record<string, any>
+}
+
+export const normalizePath = (p: string) => {
+  const v = (p || '').trim()
+  if (!v) return '/'
+  return v.startsWith('/') ? v : '/' + v
+}
+
+// 后端配置 -> 界面结构
+export function rulesFromConfig(rules: any[]): ListenRule[] {
+  return rules.map((rule: any) => {
+    const routes = (rule.routes || []).map((rt: any) => ({
+      ID: rt.id || '',
+      Enabled: rt.enabled !== undefined ? !!rt.enabled : true,
+      Host: rt.host || '',
+      Path: rt.path || '/',
+      ProxyPassPath: rt.proxy_pass_path || '',
+      FollowRedirects: !!rt.follow_redirects,
+      SetHeaders: rt.set_headers || {},
+      SetHeadersList: Object.entries(rt.set_headers || {}).map(([Key, Value]) => ({
+        Key,
+        Value: String(Value ?? ''),
+      })),
+      StaticDir: rt.static_dir || '',
+      ExcludeBasicAuth: !!rt.exclude_basic_auth,
+      UrlRewriteRules: (rt.url_rewrite_rules || []).map((r: any) => ({
+        Pattern: r.pattern || '',
+        Replacement: r.replacement || '',
+        Enabled: r.enabled !== undefined ? !!r.enabled : true,
+      })),
+      RequestBodyReplace: (rt.request_body_replace || []).map((r: any) => ({
+        Find: r.find || '',
+        Replace: r.replace || '',
+        UseRegex: !!r.use_regex,
+        Enabled: r.enabled !== undefined ? !!r.enabled : true,
+        ContentTypes: r.content_types ? r.content_types.split(',').map((s: string) => s.trim()).filter((s: string) => s) : [],
+      })),
+      ResponseBodyReplace: (rt.response_body_replace || []).map((r: any) => ({
+        Find: r.find || '',
+        Replace: r.replace || '',
+        UseRegex: !!r.use_regex,
+        Enabled: r.enabled !== undefined ? !!r.enabled : true,
+        ContentTypes: r.content_types ? r.content_types.split(',').map((s: string) => s.trim()).filter((s: string) => s) : [],
+      })),
+      RemoveHeaders: rt.remove_headers || [],
+      Methods: rt.methods || [],
+      MatchHeadersList: (rt.headers && Object.entries(rt.headers).map(([Key, Value]) => ({
+        Key,
+        Value: String(Value ?? ''),
+      }))) || [],
+      Return: rt.return || undefined,
+      Upstreams: (rt.upstreams || []).map((u: any) => ({
+        URL: u.url || '',
+        Weight: u.weight || 1,
+      })),
+      Raw: rt,
+    }));
+
+    return {
+      ID: rule.id || '',
+      Enabled: rule.enabled !== undefined ? !!rule.enabled : true,
+      // 后端向下兼容：如果有 listen_addrs 就用数组，否则从 listen_addr 构造
+      ListenAddr: rule.listen_addr || '0.0.0.0:8888',
+      ListenAddrs: Array.isArray(rule.listen_addrs) && rule.listen_addrs.length > 0
+        ? rule.listen_addrs
+        : [(rule.listen_addr || '0.0.0.0:8888')],
+      SSLEnable: !!rule.ssl_enable,
+      CertFile: rule.cert_file || '',
+      KeyFile: rule.key_file || '',
+      BasicAuthEnable: !!rule.basic_auth_enable,
+      BasicAuthUsername: rule.basic_auth_username || '',
+      BasicAuthPassword: rule.basic_auth_password || '',
+      BasicAuthForwardHeader: !!rule.basic_auth_forward_header,
+      RateLimitEnabled: rule.rate_limit_enabled !== undefined ? !!rule.rate_limit_enabled : undefined,
+      RateLimitRequestsPerSecond: rule.rate_limit_requests_per_second !== undefined ? Number(rule.rate_limit_requests_per_second) : undefined,
+      RateLimitBurstSize: rule.rate_limit_burst_size !== undefined ? Number(rule.rate_limit_burst_size) : undefined,
+      RateLimitBanSeconds: rule.rate_limit_ban_seconds !== undefined ? Number(rule.rate_limit_ban_seconds) : undefined,
+      Routes: routes.length > 0 ? routes : [{
+        Host: '',
+        Path: '/',
+        ProxyPassPath: '',
+        SetHeaders: {} as Record<string, string>,
+        SetHeadersList: [],
+        StaticDir: '',
+        ExcludeBasicAuth: false,
+        Upstreams: [{ URL: '', Weight: 1 }],
+      }],
+      Raw: rule,
+    } as ListenRule
+  })
+}
+
+// 去除首尾空白与空条目，供校验与输出使用
+export function cleanRules(rules: ListenRule[]): ListenRule[] {
+  return rules.map((rule) => ({
+    ID: (rule.ID || '').trim(),
+    Enabled: rule.Enabled !== undefined ? !!rule.Enabled : true,
+    ListenAddr: rule.ListenAddr.trim(),
+    ListenAddrs: (rule.ListenAddrs && rule.ListenAddrs.length > 0
+      ? rule.ListenAddrs
+      : [rule.ListenAddr]
+    ).map((s) => s.trim()).filter((s) => s !== ''),
+    SSLEnable: !!rule.SSLEnable,
+    CertFile: rule.CertFile || '',
+    KeyFile: rule.KeyFile || '',
+    BasicAuthEnable: !!rule.BasicAuthEnable,
+    BasicAuthUsername: (rule.BasicAuthUsername || '').trim(),
+    BasicAuthPassword: (rule.BasicAuthPassword || '').trim(),
+    BasicAuthForwardHeader: !!rule.BasicAuthForwardHeader,
+    RateLimitEnabled: rule.RateLimitEnabled !== undefined ? !!rule.RateLimitEnabled : undefined,
+    RateLimitRequestsPerSecond: rule.RateLimitRequestsPerSecond !== undefined ? Number(rule.RateLimitRequestsPerSecond) : undefined,
+    RateLimitBurstSize: rule.RateLimitBurstSize !== undefined ? Number(rule.RateLimitBurstSize) : undefined,
+    RateLimitBanSeconds: rule.RateLimitBanSeconds !== undefined ? Number(rule.RateLimitBanSeconds) : undefined,
+    Routes: rule.Routes.map((rt) => {
+      const list = Array.isArray(rt.SetHeadersList) ? rt.SetHeadersList : []
+      const setHeaders: Record<string, string> = {}
+      for (const kv of list) {
+        const k = (kv.Key || '').trim()
+        if (!k) continue
+        setHeaders[k] = (kv.Value || '').trim()
+      }
+      return {
+        ID: (rt.ID || '').trim(),
+        Enabled: rt.Enabled !== undefined ? !!rt.Enabled : true,
+        Host: (rt.Host || '').trim(),
+        Path: normalizePath(rt.Path),
+        ProxyPassPath: rt.ProxyPassPath ? normalizePath(rt.ProxyPassPath) : '',
+        FollowRedirects: !!rt.FollowRedirects,
+        SetHeaders: setHeaders,
+        StaticDir: (rt.StaticDir || '').trim(),
+        ExcludeBasicAuth: !!rt.ExcludeBasicAuth,
+        // 新增字段
+        Methods: Array.isArray(rt.Methods) ? rt.Methods : [],
+        MatchHeadersList: Array.isArray(rt.MatchHeadersList) ? rt.MatchHeadersList : [],
+        UrlRewriteRules: (rt.UrlRewriteRules || []).filter((r) => r.Pattern.trim() !== '').map((r) => ({
+          Pattern: r.Pattern.trim(),
+          Replacement: r.Replacement.trim(),
+          Enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+        })),
+        RequestBodyReplace: (rt.RequestBodyReplace || []).filter((r) => r.Find.trim() !== '').map((r) => ({
+          Find: r.Find.trim(),
+          Replace: r.Replace.trim(),
+          UseRegex: !!r.UseRegex,
+          Enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+          ContentTypes: Array.isArray(r.ContentTypes) ? r.ContentTypes : [],
+        })),
+        ResponseBodyReplace: (rt.ResponseBodyReplace || []).filter((r) => r.Find.trim() !== '').map((r) => ({
+          Find: r.Find.trim(),
+          Replace: r.Replace.trim(),
+          UseRegex: !!r.UseRegex,
+          Enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+          ContentTypes: Array.isArray(r.ContentTypes) ? r.ContentTypes : [],
+        })),
+        RemoveHeaders: (rt.RemoveHeaders || []).filter((h) => h.trim() !== '').map((h) => h.trim()),
+        Return: rt.Return,
+        Upstreams: rt.Upstreams.filter((u) => u.URL.trim() !== '').map((u) => ({
+          URL: u.URL.trim(),
+          Weight: u.Weight > 0 ? u.Weight : 1,
+        })),
+        Raw: rt.Raw,
+      }
+    }),
+    Raw: rule.Raw,
+  }))
+}
+
+// 界面结构 -> 后端配置：输出为 Rust 后端需要的 snake_case 结构
+export function rulesToConfig(rules: ListenRule[]): any[] {
+  return rules.map((r: any) => ({
+    // 先展开原始规则，界面编辑的字段再覆盖，未在界面中编辑的字段（如 error_pages、https_redirect_*）原样保留
+    ...r.Raw,
+    id: r.ID || undefined,
+    enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+    // 向后端输出新的 listen_addrs 数组，同时保留第一个为 listen_addr 兼容旧字段
+    listen_addr: r.ListenAddrs[0] || r.ListenAddr,
+    listen_addrs: r.ListenAddrs,
+    ssl_enable: !!r.SSLEnable,
+    cert_file: r.CertFile,
+    key_file: r.KeyFile,
+    basic_auth_enable: !!r.BasicAuthEnable,
+    basic_auth_username: r.BasicAuthUsername || '',
+    basic_auth_password: r.BasicAuthPassword || '',
+    basic_auth_forward_header: !!r.BasicAuthForwardHeader,
+    rate_limit_enabled: r.RateLimitEnabled !== undefined ? !!r.RateLimitEnabled : undefined,
+    rate_limit_requests_per_second: r.RateLimitRequestsPerSecond !== undefined ? Number(r.RateLimitRequestsPerSecond) : undefined,
+    rate_limit_burst_size: r.RateLimitBurstSize !== undefined ? Number(r.RateLimitBurstSize) : undefined,
+    rate_limit_window_seconds: r.RateLimitWindowSeconds !== undefined ? Number(r.RateLimitWindowSeconds) : 1,
+    rate_limit_ban_seconds: r.RateLimitBanSeconds !== undefined ? Number(r.RateLimitBanSeconds) : undefined,
+    routes: (r.Routes || []).map((rt: any) => {
+      // 处理 MatchHeadersList -> headers 对象
+      const headersObj: Record<string, string> = {}
+      if (Array.isArray(rt.MatchHeadersList)) {
+        for (const kv of rt.MatchHeadersList) {
+          const key = (kv.Key || '').trim()
+          if (key) {
+            headersObj[key] = (kv.Value || '').trim()
+          }
+        }
+      }
+
+      return {
+        ...rt.Raw,
+        id: rt.ID || undefined,
+        enabled: rt.Enabled !== undefined ? !!rt.Enabled : true,
+        host: rt.Host || undefined,
+        path: rt.Path,
+        proxy_pass_path: rt.ProxyPassPath || undefined,
+        follow_redirects: !!rt.FollowRedirects,
+        set_headers: rt.SetHeaders || {},
+        static_dir: rt.StaticDir || undefined,
+        exclude_basic_auth: !!rt.ExcludeBasicAuth,
+        // 新增字段映射
+        methods: Array.isArray(rt.Methods) && rt.Methods.length > 0 ? rt.Methods.map((m: string) => m.trim().toUpperCase()).filter((m: string) => m) : undefined,
+        headers: Object.keys(headersObj).length > 0 ? headersObj : undefined,
+        url_rewrite_rules: (rt.UrlRewriteRules || []).filter((r: any) => r.Pattern.trim() !== '').map((r: any) => ({
+          pattern: r.Pattern.trim(),
+          replacement: r.Replacement.trim(),
+          enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+        })),
+        request_body_replace: (rt.RequestBodyReplace || []).filter((r: any) => r.Find.trim() !== '').map((r: any) => ({
+          find: r.Find.trim(),
+          replace: r.Replace.trim(),
+          use_regex: !!r.UseRegex,
+          enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+          content_types: Array.isArray(r.ContentTypes) && r.ContentTypes.length > 0 ? r.ContentTypes.map((s: string) => (s || '').trim()).filter((s: string) => s).join(',') : undefined,
+        })),
+        response_body_replace: (rt.ResponseBodyReplace || []).filter((r: any) => r.Find.trim() !== '').map((r: any) => ({
+          find: r.Find.trim(),
+          replace: r.Replace.trim(),
+          use_regex: !!r.UseRegex,
+          enabled: r.Enabled !== undefined ? !!r.Enabled : true,
+          content_types: Array.isArray(r.ContentTypes) && r.ContentTypes.length > 0 ? r.ContentTypes.map((s: string) => (s || '').trim()).filter((s: string) => s).join(',') : undefined,
+        })),
+        remove_headers: (rt.RemoveHeaders || []).filter((h: any) => h.trim() !== '').map((h: any) => h.trim()),
+        return: rt.Return || undefined,
+        upstreams: (rt.Upstreams || []).map((u: any) => ({
+          url: u.URL,
+          weight: u.Weight,
+        })),
+      }
+    }),
+  }))
+}
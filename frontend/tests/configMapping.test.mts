@@ -0,0 +1,90 @@
+// 规则配置读取 -> 保存往返测试：界面未编辑的字段必须原样写回后端
+import assert from 'node:assert/strict'
+import { test } from 'node:test'
+import { cleanRules, rulesFromConfig, rulesToConfig } from '../src/configMapping.ts'
+
+const roundTrip = (rules: any[]) => JSON.parse(JSON.stringify(rulesToConfig(cleanRules(rulesFromConfig(rules)))))
+
+test('保存时保留界面未编辑的规则与路由字段', () => {
+  const route = {
+    id: 'api',
+    enabled: true,
+    path: '/api/',
+    match_type: 'prefix',
+    priority: 10,
+    methods: ['GET', 'POST'],
+    headers: { 'x-env': 'prod' },
+    upstreams: [{ url: 'http://127.0.0.1:9000', weight: 1 }],
+    set_headers: { 'X-Real-IP': '$remote_addr' },
+    set_response_headers: { 'cache-control': 'no-store' },
+    cors: { enabled: true, allow_origins: ['https://a.com'] },
+    error_pages: [{ status: [404], file: '/srv/404.html' }],
+    proxy_intercept_errors: true,
+    cache: { enabled: true, storage: 'memory', default_ttl_secs: 60 },
+    coalesce: { enabled: true },
+    mirrors: [{ url: 'http://127.0.0.1:9100', percent: 10 }],
+    splits: [{ name: 'canary', percent: 5, upstreams: [{ url: 'http://127.0.0.1:9200', weight: 1 }] }],
+    connect_timeout_ms: 1000,
+    read_timeout_ms: 5000,
+    total_timeout_ms: 30000,
+    max_body_size: 1048576,
+    max_response_body_size: 8388608,
+    circuit_breaker: { enabled: true, consecutive_failures: 5 },
+    fault: { enabled: true, delay_ms: 100, delay_percent: 50 },
+    mock: { enabled: true, file: '/srv/mock.toml' },
+    jwt_auth: { enabled: true, secret: 's', algorithms: ['HS256'] },
+    forward_auth: { enabled: true, url: 'http://127.0.0.1:9300/auth' },
+    oidc: { issuer: 'https://id.example.com', client_id: 'c', client_secret: 's', cookie_secret: '0123456789abcdef' },
+    api_key: { enabled: true, header: 'X-Api-Key' },
+    basic_auth_enable: true,
+    basic_auth_users: [{ username: 'alice', password: '$2y$10$abc' }],
+    basic_auth_htpasswd_file: '/srv/htpasswd',
+    basic_auth_realm: 'Admin',
+  }
+  const redirect = { id: 'to-https', path: '/', upstreams: [], return: { status: 301, location: 'https://$host_name$request_uri' } }
+  const rule = {
+    id: 'main',
+    enabled: true,
+    listen_addr: '0.0.0.0:8443',
+    listen_addrs: ['0.0.0.0:8443'],
+    ssl_enable: true,
+    cert_file: '/srv/cert.pem',
+    key_file: '/srv/key.pem',
+    basic_auth_enable: false,
+    basic_auth_username: '',
+    basic_auth_password: '',
+    basic_auth_forward_header: false,
+    basic_auth_users: [{ username: 'ops', password: 'p' }],
+    basic_auth_htpasswd_file: '/srv/rule-htpasswd',
+    basic_auth_realm: 'Corp',
+    security_headers_enabled: true,
+    https_redirect_listen_addr: '0.0.0.0:80',
+    https_redirect_status: 308,
+    acme_challenge_dir: '/srv/acme',
+    error_pages: [{ status: [502, 503], html: '<h1>$status</h1>' }],
+    proxy_intercept_errors: true,
+    circuit_breaker: { enabled: true, open_secs: 30 },
+    routes: [route, redirect],
+  }
+
+  const saved = roundTrip([rule])
+  for (const [key, value] of Object.entries(rule)) {
+    if (key !== 'routes') {
+      assert.deepEqual(saved[0][key], value, `规则字段 ${key}`)
+    }
+  }
+  for (const [key, value] of Object.entries(route)) {
+    assert.deepEqual(saved[0].routes[0][key], value, `路由字段 ${key}`)
+  }
+  assert.deepEqual(saved[0].routes[1].return, redirect.return)
+})
+
+test('界面修改的字段覆盖原始值', () => {
+  const rules = rulesFromConfig([{ listen_addr: '0.0.0.0:8080', routes: [{ path: '/', host: 'a.com', upstreams: [], cache: { enabled: true } }] }])
+  rules[0].Routes[0].Host = ''
+  rules[0].Routes[0].Path = 'app'
+  const saved = JSON.parse(JSON.stringify(rulesToConfig(cleanRules(rules))))
+  assert.equal(saved[0].routes[0].host, undefined)
+  assert.equal(saved[0].routes[0].path, '/app')
+  assert.deepEqual(saved[0].routes[0].cache, { enabled: true })
+})
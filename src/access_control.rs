@@ -60,6 +60,36 @@ pub(crate) fn is_lan_ip(ip: &IpAddr) -> bool {
 }
 
 
+/// 解析 CIDR（如 `10.0.0.0/8`、`fd00::/8`）；不带前缀长度时视为单个地址
+pub(crate) fn parse_cidr(s: &str) -> Option<(IpAddr, u8)> {
+    let s = s.trim();
+    let (ip, len) = match s.split_once('/') {
+        Some((ip, len)) => (parse_ip(ip)?, len.trim().parse::<u8>().ok()?),
+        None => {
+            let ip = parse_ip(s)?;
+            (ip, if ip.is_ipv4() { 32 } else { 128 })
+        }
+    };
+    let ip = to_ipv4_mapped(&ip);
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    (len <= max).then_some((ip, len))
+}
+
+pub(crate) fn ip_in_cidr(ip: &IpAddr, cidr: &(IpAddr, u8)) -> bool {
+    let (net, len) = cidr;
+    match (to_ipv4_mapped(ip), net) {
+        (IpAddr::V4(a), IpAddr::V4(b)) => {
+            let mask = u32::MAX.checked_shl(32 - *len as u32).unwrap_or(0);
+            u32::from(a) & mask == u32::from(*b) & mask
+        }
+        (IpAddr::V6(a), IpAddr::V6(b)) => {
+            let mask = u128::MAX.checked_shl(128 - *len as u32).unwrap_or(0);
+            u128::from(a) & mask == u128::from(*b) & mask
+        }
+        _ => false,
+    }
+}
+
 pub fn client_ip_from_headers(remote: &SocketAddr, headers: &HeaderMap) -> String {
     if let Some(h) = headers
         .get("x-forwarded-for")
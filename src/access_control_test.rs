@@ -70,7 +70,7 @@ mod access_control_tests {
         let headers = HeaderMap::new();
         let whitelist = vec![];
         
-        let allowed = access_control::is_allowed_fast(&remote, &headers, false, &whitelist);
+        let allowed = access_control::is_allowed_fast(&remote, &headers, false, false, &whitelist);
         assert!(allowed, "IPv6 loopback should be allowed even without allow_all_lan");
         println!("✓ IPv6 loopback (::1) is allowed");
     }
@@ -83,7 +83,7 @@ mod access_control_tests {
         let headers = HeaderMap::new();
         let whitelist = vec![];
         
-        let allowed = access_control::is_allowed_fast(&remote, &headers, true, &whitelist);
+        let allowed = access_control::is_allowed_fast(&remote, &headers, true, false, &whitelist);
         assert!(allowed, "IPv4-mapped IPv6 LAN address should be allowed with allow_all_lan=true");
         println!("✓ IPv4-mapped IPv6 LAN address (::ffff:192.168.1.128) is allowed with allow_all_lan=true");
     }
@@ -96,8 +96,27 @@ mod access_control_tests {
         let headers = HeaderMap::new();
         let whitelist = vec![];
         
-        let allowed = access_control::is_allowed_fast(&remote, &headers, true, &whitelist);
+        let allowed = access_control::is_allowed_fast(&remote, &headers, true, false, &whitelist);
         assert!(allowed, "IPv6 unique local address should be allowed with allow_all_lan=true");
         println!("✓ IPv6 unique local address (fc00::1) is allowed with allow_all_lan=true");
     }
+
+    #[test]
+    fn test_cidr_match() {
+        // 测试 CIDR 解析与匹配（IPv4-mapped 地址按 IPv4 处理）
+        let net = access_control::parse_cidr("10.1.0.0/16").unwrap();
+        let inside = "::ffff:10.1.2.3".parse::<IpAddr>().unwrap();
+        let outside = "10.2.0.1".parse::<IpAddr>().unwrap();
+        assert!(access_control::ip_in_cidr(&inside, &net));
+        assert!(!access_control::ip_in_cidr(&outside, &net));
+
+        let v6 = access_control::parse_cidr("fd00::/8").unwrap();
+        assert!(access_control::ip_in_cidr(&"fd12::1".parse().unwrap(), &v6));
+        assert!(!access_control::ip_in_cidr(&outside, &v6));
+
+        let single = access_control::parse_cidr("192.168.1.5").unwrap();
+        assert_eq!(single.1, 32);
+        assert!(access_control::parse_cidr("10.0.0.0/33").is_none());
+        println!("✓ CIDR matching works for IPv4 / IPv6 / single address");
+    }
 }
@@ -0,0 +1,504 @@
+use anyhow::{anyhow, Result};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri};
+use base64::Engine;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{config, metrics, rate_limit::TokenBucket};
+
+const DEFAULT_HEADER: &str = "x-api-key";
+const DEFAULT_FORWARD_HEADER: &str = "x-api-key-name";
+/// 生成的 Key 前缀，便于在日志或代码仓库中识别
+const KEY_PREFIX: &str = "spm_";
+
+/// 已启用的 Key：哈希 -> Key 信息
+static KEYS: Lazy<RwLock<HashMap<String, Arc<KeyEntry>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+/// 每个 Key 的令牌桶（按 Key 名称）
+static BUCKETS: Lazy<DashMap<String, Mutex<TokenBucket>>> = Lazy::new(DashMap::new);
+/// 每个 Key 当天的用量（按 Key 名称），定期写回数据库
+static USAGE: Lazy<DashMap<String, Usage>> = Lazy::new(DashMap::new);
+/// 跨天时尚未写回的前一天用量
+static ROLLOVER: Lazy<Mutex<Vec<(String, String, i64)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+struct KeyEntry {
+    name: String,
+    allowed_routes: Vec<String>,
+    expires_at: i64,
+    rate_limit_per_second: u32,
+    rate_limit_burst: u32,
+    daily_quota: i64,
+}
+
+struct Usage {
+    day: String,
+    used: i64,
+    dirty: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ApiKeyRow {
+    id: i64,
+    name: String,
+    key_hash: String,
+    key_prefix: String,
+    allowed_routes: String,
+    expires_at: i64,
+    rate_limit_per_second: i64,
+    rate_limit_burst: i64,
+    daily_quota: i64,
+    enabled: bool,
+    created_at: i64,
+}
+
+/// Key 信息（不含 Key 本身）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyInfo {
+    pub id: i64,
+    pub name: String,
+    /// Key 的前几位，用于辨认
+    pub key_prefix: String,
+    /// 允许访问的路由 ID，为空表示所有启用了 API Key 认证的路由
+    pub allowed_routes: Vec<String>,
+    /// 过期时间（Unix 秒），0 表示永不过期
+    pub expires_at: i64,
+    /// 每秒请求数，0 表示不限
+    pub rate_limit_per_second: i64,
+    /// 突发请求数（令牌桶容量），0 表示与每秒请求数相同
+    pub rate_limit_burst: i64,
+    /// 每日请求限额（UTC 自然日），0 表示不限
+    pub daily_quota: i64,
+    pub enabled: bool,
+    pub created_at: i64,
+    /// 当天已用请求数
+    pub used_today: i64,
+}
+
+/// 创建 / 修改 Key 的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub allowed_routes: Vec<String>,
+    #[serde(default)]
+    pub expires_at: i64,
+    #[serde(default)]
+    pub rate_limit_per_second: i64,
+    #[serde(default)]
+    pub rate_limit_burst: i64,
+    #[serde(default)]
+    pub daily_quota: i64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 新建的 Key：明文只在创建时返回一次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedApiKey {
+    pub key: String,
+    pub info: ApiKeyInfo,
+}
+
+#[inline]
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[inline]
+fn today() -> String {
+    chrono::Utc::now().format("%Y-%m-%d").to_string()
+}
+
+fn used_today(name: &str) -> i64 {
+    let day = today();
+    USAGE
+        .get(name)
+        .filter(|u| u.day == day)
+        .map_or(0, |u| u.used)
+}
+
+impl ApiKeyRow {
+    fn into_info(self) -> ApiKeyInfo {
+        let used_today = used_today(&self.name);
+        ApiKeyInfo {
+            id: self.id,
+            allowed_routes: split_routes(&self.allowed_routes),
+            name: self.name,
+            key_prefix: self.key_prefix,
+            expires_at: self.expires_at,
+            rate_limit_per_second: self.rate_limit_per_second,
+            rate_limit_burst: self.rate_limit_burst,
+            daily_quota: self.daily_quota,
+            enabled: self.enabled,
+            created_at: self.created_at,
+            used_today,
+        }
+    }
+}
+
+fn split_routes(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn validate(req: &ApiKeyRequest) -> Result<String> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("API Key 名称不能为空"));
+    }
+    if req.rate_limit_per_second < 0 || req.rate_limit_burst < 0 || req.daily_quota < 0 || req.expires_at < 0 {
+        return Err(anyhow!("API Key 的限流、限额与过期时间不能为负数"));
+    }
+    if req.allowed_routes.iter().any(|r| r.contains(',')) {
+        return Err(anyhow!("路由 ID 不能包含逗号"));
+    }
+    Ok(name.to_string())
+}
+
+const SELECT_COLUMNS: &str = "id, name, key_hash, key_prefix, allowed_routes, expires_at, rate_limit_per_second, rate_limit_burst, daily_quota, enabled, created_at";
+
+pub async fn create(req: ApiKeyRequest) -> Result<CreatedApiKey> {
+    let pool = metrics::pool().ok_or_else(|| anyhow!("metrics 数据库未初始化"))?;
+    let name = validate(&req)?;
+
+    let mut raw = [0u8; 24];
+    aws_lc_rs::rand::fill(&mut raw).map_err(|_| anyhow!("生成 API Key 失败"))?;
+    let key = format!("{KEY_PREFIX}{}", base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw));
+
+    let row = sqlx::query_as::<_, ApiKeyRow>(&format!(
+        "INSERT INTO api_keys(name, key_hash, key_prefix, allowed_routes, expires_at, rate_limit_per_second, rate_limit_burst, daily_quota, enabled, created_at) \
+         VALUES(?,?,?,?,?,?,?,?,?,?) RETURNING {SELECT_COLUMNS}"
+    ))
+    .bind(&name)
+    .bind(hash_key(&key))
+    .bind(&key[..KEY_PREFIX.len() + 6])
+    .bind(req.allowed_routes.join(","))
+    .bind(req.expires_at)
+    .bind(req.rate_limit_per_second)
+    .bind(req.rate_limit_burst)
+    .bind(req.daily_quota)
+    .bind(req.enabled)
+    .bind(chrono::Utc::now().timestamp())
+    .fetch_one(&*pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(d) if d.is_unique_violation() => anyhow!("API Key 名称已存在: {name}"),
+        e => e.into(),
+    })?;
+
+    reload_keys(&pool).await?;
+    Ok(CreatedApiKey { key, info: row.into_info() })
+}
+
+/// 修改 Key 的权限、限流与限额（Key 本身不变）
+pub async fn update(req: ApiKeyRequest) -> Result<ApiKeyInfo> {
+    let pool = metrics::pool().ok_or_else(|| anyhow!("metrics 数据库未初始化"))?;
+    let name = validate(&req)?;
+
+    let row = sqlx::query_as::<_, ApiKeyRow>(&format!(
+        "UPDATE api_keys SET allowed_routes=?, expires_at=?, rate_limit_per_second=?, rate_limit_burst=?, daily_quota=?, enabled=? \
+         WHERE name=? RETURNING {SELECT_COLUMNS}"
+    ))
+    .bind(req.allowed_routes.join(","))
+    .bind(req.expires_at)
+    .bind(req.rate_limit_per_second)
+    .bind(req.rate_limit_burst)
+    .bind(req.daily_quota)
+    .bind(req.enabled)
+    .bind(&name)
+    .fetch_optional(&*pool)
+    .await?
+    .ok_or_else(|| anyhow!("未找到 API Key: {name}"))?;
+
+    BUCKETS.remove(&name);
+    reload_keys(&pool).await?;
+    Ok(row.into_info())
+}
+
+pub async fn delete(name: String) -> Result<()> {
+    let Some(pool) = metrics::pool() else { return Ok(()) };
+
+    sqlx::query("DELETE FROM api_keys WHERE name=?")
+        .bind(&name)
+        .execute(&*pool)
+        .await?;
+    sqlx::query("DELETE FROM api_key_usage WHERE name=?")
+        .bind(&name)
+        .execute(&*pool)
+        .await?;
+
+    BUCKETS.remove(&name);
+    USAGE.remove(&name);
+    reload_keys(&pool).await
+}
+
+pub async fn list() -> Result<Vec<ApiKeyInfo>> {
+    let Some(pool) = metrics::pool() else { return Ok(vec![]) };
+
+    let rows = sqlx::query_as::<_, ApiKeyRow>(&format!(
+        "SELECT {SELECT_COLUMNS} FROM api_keys ORDER BY created_at DESC"
+    ))
+    .fetch_all(&*pool)
+    .await?;
+    Ok(rows.into_iter().map(ApiKeyRow::into_info).collect())
+}
+
+/// 从数据库加载 Key 与当天用量（数据库初始化时调用）
+pub(crate) async fn reload(pool: &SqlitePool) -> Result<()> {
+    reload_keys(pool).await?;
+
+    let day = today();
+    let rows = sqlx::query_as::<_, (String, i64)>("SELECT name, count FROM api_key_usage WHERE day=?")
+        .bind(&day)
+        .fetch_all(pool)
+        .await?;
+    for (name, count) in rows {
+        let mut usage = USAGE.entry(name).or_insert_with(|| Usage {
+            day: day.clone(),
+            used: 0,
+            dirty: false,
+        });
+        if usage.day == day {
+            usage.used = usage.used.max(count);
+        }
+    }
+    Ok(())
+}
+
+async fn reload_keys(pool: &SqlitePool) -> Result<()> {
+    let rows = sqlx::query_as::<_, ApiKeyRow>(&format!(
+        "SELECT {SELECT_COLUMNS} FROM api_keys WHERE enabled=1"
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    let keys = rows
+        .into_iter()
+        .map(|r| {
+            let entry = KeyEntry {
+                allowed_routes: split_routes(&r.allowed_routes),
+                name: r.name,
+                expires_at: r.expires_at,
+                rate_limit_per_second: r.rate_limit_per_second.clamp(0, u32::MAX as i64) as u32,
+                rate_limit_burst: r.rate_limit_burst.clamp(0, u32::MAX as i64) as u32,
+                daily_quota: r.daily_quota,
+            };
+            (r.key_hash, Arc::new(entry))
+        })
+        .collect();
+    *KEYS.write() = keys;
+    Ok(())
+}
+
+/// 把当天用量写回数据库（写入的是当天累计值，重复写入不会重复计数）
+pub(crate) async fn flush_usage(pool: &SqlitePool) {
+    let mut pending = std::mem::take(&mut *ROLLOVER.lock());
+    for mut u in USAGE.iter_mut() {
+        if u.dirty {
+            u.dirty = false;
+            pending.push((u.key().clone(), u.day.clone(), u.used));
+        }
+    }
+    for (name, day, used) in pending {
+        let result = sqlx::query(
+            "INSERT INTO api_key_usage(name, day, count) VALUES(?,?,?) \
+             ON CONFLICT(name, day) DO UPDATE SET count=MAX(count, excluded.count)",
+        )
+        .bind(&name)
+        .bind(&day)
+        .bind(used)
+        .execute(pool)
+        .await;
+        if let Err(e) = result {
+            eprintln!("写入 API Key 用量失败: {}", e);
+        }
+    }
+}
+
+/// API Key 认证失败原因
+pub enum ApiKeyReject {
+    Missing,
+    Invalid,
+    Expired(String),
+    RouteNotAllowed(String),
+    RateLimited(String),
+    QuotaExceeded(String),
+}
+
+impl ApiKeyReject {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiKeyReject::Missing | ApiKeyReject::Invalid | ApiKeyReject::Expired(_) => StatusCode::UNAUTHORIZED,
+            ApiKeyReject::RouteNotAllowed(_) => StatusCode::FORBIDDEN,
+            ApiKeyReject::RateLimited(_) | ApiKeyReject::QuotaExceeded(_) => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ApiKeyReject::Missing => "Missing API key",
+            ApiKeyReject::Invalid => "Invalid API key",
+            ApiKeyReject::Expired(_) => "API key expired",
+            ApiKeyReject::RouteNotAllowed(_) => "Route not allowed for this API key",
+            ApiKeyReject::RateLimited(_) => "Rate limit exceeded",
+            ApiKeyReject::QuotaExceeded(_) => "Daily quota exceeded",
+        }
+    }
+
+    /// 已识别的 Key 名称（Key 无效或缺失时为空）
+    pub fn key_name(&self) -> &str {
+        match self {
+            ApiKeyReject::Missing | ApiKeyReject::Invalid => "",
+            ApiKeyReject::Expired(n)
+            | ApiKeyReject::RouteNotAllowed(n)
+            | ApiKeyReject::RateLimited(n)
+            | ApiKeyReject::QuotaExceeded(n) => n,
+        }
+    }
+
+    /// 429 响应的 Retry-After：限流为 1 秒，超出限额则等到下一个 UTC 日
+    pub fn retry_after(&self) -> Option<HeaderValue> {
+        let secs = match self {
+            ApiKeyReject::RateLimited(_) => 1,
+            ApiKeyReject::QuotaExceeded(_) => 86_400 - chrono::Utc::now().timestamp().rem_euclid(86_400),
+            _ => return None,
+        };
+        Some(HeaderValue::from(secs))
+    }
+}
+
+/// 预编译的路由级 API Key 认证配置
+pub struct CompiledApiKeyAuth {
+    header: HeaderName,
+    query_param: Option<String>,
+    forward_header: HeaderName,
+}
+
+impl CompiledApiKeyAuth {
+    pub fn compile(cfg: &config::ApiKeyAuthConfig, label: &str) -> Result<Self> {
+        let header_name = |v: Option<&str>, default: &'static str, field: &str| -> Result<HeaderName> {
+            match v.map(str::trim).filter(|s| !s.is_empty()) {
+                Some(h) => HeaderName::from_bytes(h.as_bytes())
+                    .map_err(|_| anyhow!("路由 {label} 的 api_key.{field} 请求头名无效: {h}")),
+                None => Ok(HeaderName::from_static(default)),
+            }
+        };
+        Ok(Self {
+            header: header_name(cfg.header.as_deref(), DEFAULT_HEADER, "header")?,
+            query_param: cfg
+                .query_param
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+            forward_header: header_name(cfg.forward_header.as_deref(), DEFAULT_FORWARD_HEADER, "forward_header")?,
+        })
+    }
+
+    /// 认证通过时写入 Key 名称的请求头
+    pub fn identity_headers(&self) -> &[HeaderName] {
+        std::slice::from_ref(&self.forward_header)
+    }
+
+    /// 校验请求中的 Key 并计入限流与当天用量。
+    /// Key 会从请求头和查询串中移除（不转发给上游），通过时写入 forward_header，返回 Key 名称
+    pub fn authenticate(&self, route_id: &str, headers: &mut HeaderMap, uri: &mut Uri) -> Result<String, ApiKeyReject> {
+        let from_header = headers
+            .remove(&self.header)
+            .and_then(|v| v.to_str().ok().map(|s| s.trim().to_string()));
+        let from_query = self.query_param.as_deref().and_then(|p| take_query_param(uri, p));
+        headers.remove(&self.forward_header);
+
+        let key = from_header
+            .filter(|s| !s.is_empty())
+            .or(from_query)
+            .ok_or(ApiKeyReject::Missing)?;
+        let entry = KEYS.read().get(&hash_key(&key)).cloned().ok_or(ApiKeyReject::Invalid)?;
+        let name = entry.name.clone();
+
+        if entry.expires_at > 0 && entry.expires_at <= chrono::Utc::now().timestamp() {
+            return Err(ApiKeyReject::Expired(name));
+        }
+        if !entry.allowed_routes.is_empty() && !entry.allowed_routes.iter().any(|r| r == route_id) {
+            return Err(ApiKeyReject::RouteNotAllowed(name));
+        }
+        if entry.rate_limit_per_second > 0 {
+            let rps = entry.rate_limit_per_second;
+            let burst = if entry.rate_limit_burst > 0 { entry.rate_limit_burst } else { rps };
+            let bucket = BUCKETS
+                .entry(name.clone())
+                .or_insert_with(|| Mutex::new(TokenBucket::new(burst as f64, rps as f64)));
+            if !bucket.lock().try_consume() {
+                return Err(ApiKeyReject::RateLimited(name));
+            }
+        }
+
+        let day = today();
+        {
+            let mut usage = USAGE.entry(name.clone()).or_insert_with(|| Usage {
+                day: day.clone(),
+                used: 0,
+                dirty: false,
+            });
+            if usage.day != day {
+                if usage.dirty {
+                    ROLLOVER.lock().push((name.clone(), usage.day.clone(), usage.used));
+                }
+                *usage = Usage { day, used: 0, dirty: false };
+            }
+            if entry.daily_quota > 0 && usage.used >= entry.daily_quota {
+                return Err(ApiKeyReject::QuotaExceeded(name));
+            }
+            usage.used += 1;
+            usage.dirty = true;
+        }
+
+        if let Ok(v) = HeaderValue::from_str(&name) {
+            headers.insert(self.forward_header.clone(), v);
+        }
+        Ok(name)
+    }
+}
+
+/// 从 URI 查询串中取出并移除指定参数，其余参数保持原样
+fn take_query_param(uri: &mut Uri, name: &str) -> Option<String> {
+    let query = uri.query()?;
+    let mut found = None;
+    let mut kept = Vec::new();
+    for pair in query.split('&') {
+        let mut parsed = url::form_urlencoded::parse(pair.as_bytes());
+        match parsed.next() {
+            Some((k, v)) if k == name => {
+                if found.is_none() {
+                    found = Some(v.into_owned());
+                }
+            }
+            _ => kept.push(pair),
+        }
+    }
+    found.as_ref()?;
+
+    let pq = if kept.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), kept.join("&"))
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = pq.parse().ok();
+    if let Ok(new_uri) = Uri::from_parts(parts) {
+        *uri = new_uri;
+    }
+    found.filter(|s| !s.is_empty())
+}
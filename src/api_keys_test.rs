@@ -0,0 +1,113 @@
+// API Key 认证（api_keys）模块的单元测试
+
+#[cfg(test)]
+mod api_keys_tests {
+    use crate::api_keys::{self, ApiKeyRequest, CompiledApiKeyAuth};
+    use crate::config;
+    use axum::http::{HeaderMap, StatusCode, Uri};
+
+    #[tokio::test]
+    async fn test_api_key_quota() {
+        let dir = std::env::temp_dir().join(format!("api-key-test-{}", uuid::Uuid::new_v4()));
+        crate::metrics::init_db(dir.join("metrics.db").to_string_lossy().to_string())
+            .await
+            .unwrap();
+        let pool = crate::metrics::pool().unwrap();
+
+        let key_req = |name: &str| ApiKeyRequest {
+            name: name.into(),
+            allowed_routes: vec!["partner".into()],
+            expires_at: 0,
+            rate_limit_per_second: 0,
+            rate_limit_burst: 0,
+            daily_quota: 3,
+            enabled: true,
+        };
+        let created = api_keys::create(key_req("acme")).await.unwrap();
+        assert!(created.key.starts_with("spm_"));
+        assert!(created.key.starts_with(&created.info.key_prefix));
+        assert!(api_keys::create(key_req("acme")).await.is_err());
+        // 只保存哈希
+        let (hash,): (String,) = sqlx::query_as("SELECT key_hash FROM api_keys WHERE name='acme'")
+            .fetch_one(&*pool)
+            .await
+            .unwrap();
+        assert_ne!(hash, created.key);
+
+        let compile = |toml_src: &str| {
+            let cfg: config::ApiKeyAuthConfig = toml::from_str(toml_src).unwrap();
+            CompiledApiKeyAuth::compile(&cfg, "api").unwrap()
+        };
+        let partner = compile(r#"query_param = "api_key""#);
+        let internal = compile(r#"header = "X-Token""#);
+        let auth = |path: &str, header: Option<(&'static str, &str)>, uri: &str| {
+            let (route_id, compiled) =
+                if path.starts_with("/api/") { ("partner", &partner) } else { ("internal", &internal) };
+            let mut headers = HeaderMap::new();
+            headers.insert("x-api-key-name", "spoofed".parse().unwrap());
+            if let Some((k, v)) = header {
+                headers.insert(k, v.parse().unwrap());
+            }
+            let mut uri: Uri = uri.parse().unwrap();
+            let result = compiled.authenticate(route_id, &mut headers, &mut uri);
+            (result.map_err(|r| (r.status(), r.key_name().to_string())), headers, uri)
+        };
+
+        let (r, headers, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert_eq!(r.unwrap(), "acme");
+        assert_eq!(headers["x-api-key-name"], "acme");
+        assert!(!headers.contains_key("x-api-key"));
+
+        // 查询参数中的 Key 会从转发的 URI 中移除，其余参数保持原样
+        let (r, _, uri) = auth("/api/x", None, &format!("/api/x?a=%20b&api_key={}&c=1", created.key));
+        assert_eq!(r.unwrap(), "acme");
+        assert_eq!(uri.to_string(), "/api/x?a=%20b&c=1");
+
+        let (r, headers, _) = auth("/api/x", None, "/api/x");
+        assert_eq!(r.unwrap_err(), (StatusCode::UNAUTHORIZED, String::new()));
+        assert!(!headers.contains_key("x-api-key-name"));
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", "spm_wrong")), "/api/x");
+        assert_eq!(r.unwrap_err().0, StatusCode::UNAUTHORIZED);
+        let (r, _, _) = auth("/internal/x", Some(("x-token", &created.key)), "/internal/x");
+        assert_eq!(r.unwrap_err(), (StatusCode::FORBIDDEN, "acme".to_string()));
+
+        // 每日限额 3 次：第 4 次返回 429
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert!(r.is_ok());
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert_eq!(r.unwrap_err(), (StatusCode::TOO_MANY_REQUESTS, "acme".to_string()));
+
+        // 用量写回数据库，重新加载后仍然有效
+        api_keys::flush_usage(&pool).await;
+        let (count,): (i64,) = sqlx::query_as("SELECT count FROM api_key_usage WHERE name='acme'")
+            .fetch_one(&*pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+        let listed = api_keys::list().await.unwrap();
+        assert_eq!(listed.iter().find(|k| k.name == "acme").unwrap().used_today, 3);
+
+        // 调整限额与限流：每秒 1 次、突发 1 次
+        let mut update = key_req("acme");
+        update.daily_quota = 100;
+        update.rate_limit_per_second = 1;
+        update.rate_limit_burst = 1;
+        api_keys::update(update).await.unwrap();
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert!(r.is_ok());
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert_eq!(r.unwrap_err(), (StatusCode::TOO_MANY_REQUESTS, "acme".to_string()));
+
+        let mut expired = key_req("acme");
+        expired.expires_at = 1;
+        api_keys::update(expired).await.unwrap();
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert_eq!(r.unwrap_err(), (StatusCode::UNAUTHORIZED, "acme".to_string()));
+
+        api_keys::delete("acme".into()).await.unwrap();
+        let (r, _, _) = auth("/api/x", Some(("x-api-key", &created.key)), "/api/x");
+        assert_eq!(r.unwrap_err(), (StatusCode::UNAUTHORIZED, String::new()));
+        assert!(api_keys::list().await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
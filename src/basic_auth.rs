@@ -0,0 +1,399 @@
+use anyhow::{anyhow, Result};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::http::{header, HeaderMap, HeaderValue};
+use base64::Engine;
+use dashmap::DashMap;
+use md5::{Digest, Md5};
+use parking_lot::RwLock;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use subtle::ConstantTimeEq;
+
+use crate::{config, proxy};
+
+const RELOAD_CHECK_INTERVAL_MS: i64 = 1000;
+const DEFAULT_REALM: &str = "SSLProxyManager";
+/// 认证成功结果的缓存时间：bcrypt / argon2 校验开销较大，避免每个请求都重新计算
+const CACHE_TTL: Duration = Duration::from_secs(60);
+/// 缓存条目超过该数量时清理过期项
+const CACHE_SWEEP_THRESHOLD: usize = 10_000;
+
+const CRYPT_HASH64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const MD5_TRANSPOSE: &[u8] = b"\x0c\x06\x00\x0d\x07\x01\x0e\x08\x02\x0f\x09\x03\x05\x0a\x04\x0b";
+
+/// 校验密码：按存储值的前缀识别哈希格式，无法识别时按明文比较
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    if let Some(rest) = stored.strip_prefix("$apr1$") {
+        let salt = rest.split('$').next().unwrap_or_default();
+        return ct_eq(apr1_crypt(password.as_bytes(), salt).as_bytes(), stored.as_bytes());
+    }
+    if stored.starts_with("$argon2") {
+        return PasswordHash::new(stored)
+            .map(|h| Argon2::default().verify_password(password.as_bytes(), &h).is_ok())
+            .unwrap_or(false);
+    }
+    if stored.starts_with('$') {
+        // bcrypt（$2a$/$2b$/$2y$）、MD5-crypt（$1$）、SHA-crypt（$5$/$6$）
+        return pwhash::unix::verify(password, stored);
+    }
+    if let Some(b64) = stored.strip_prefix("{SHA}") {
+        let digest = aws_lc_rs::digest::digest(&aws_lc_rs::digest::SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes());
+        let encoded = base64::engine::general_purpose::STANDARD.encode(digest.as_ref());
+        return ct_eq(encoded.as_bytes(), b64.as_bytes());
+    }
+    ct_eq(password.as_bytes(), stored.as_bytes())
+}
+
+#[inline]
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Apache 的 MD5 变体（htpasswd -m），与 MD5-crypt 仅魔数不同
+fn apr1_crypt(pass: &[u8], salt: &str) -> String {
+    const MAGIC: &str = "$apr1$";
+    let salt = &salt[..salt.len().min(8)];
+
+    let mut alt = Md5::new();
+    alt.update(pass);
+    alt.update(salt.as_bytes());
+    alt.update(pass);
+    let alt = alt.finalize();
+
+    let mut ctx = Md5::new();
+    ctx.update(pass);
+    ctx.update(MAGIC.as_bytes());
+    ctx.update(salt.as_bytes());
+    let mut left = pass.len();
+    while left > 0 {
+        let n = left.min(16);
+        ctx.update(&alt[..n]);
+        left -= n;
+    }
+    let mut len = pass.len();
+    while len > 0 {
+        if len & 1 == 1 {
+            ctx.update([0u8]);
+        } else {
+            ctx.update(&pass[..1]);
+        }
+        len >>= 1;
+    }
+    let mut hash = ctx.finalize();
+
+    for round in 0..1000 {
+        let mut ctx = Md5::new();
+        if round % 2 == 1 {
+            ctx.update(pass);
+        } else {
+            ctx.update(hash);
+        }
+        if round % 3 != 0 {
+            ctx.update(salt.as_bytes());
+        }
+        if round % 7 != 0 {
+            ctx.update(pass);
+        }
+        if round % 2 == 1 {
+            ctx.update(hash);
+        } else {
+            ctx.update(pass);
+        }
+        hash = ctx.finalize();
+    }
+
+    let bytes: Vec<u8> = MD5_TRANSPOSE.iter().map(|&i| hash[i as usize]).collect();
+    let mut out = String::with_capacity(MAGIC.len() + salt.len() + 23);
+    out.push_str(MAGIC);
+    out.push_str(salt);
+    out.push('$');
+    for group in bytes.chunks(3) {
+        let mut v = 0u32;
+        for (i, b) in group.iter().enumerate() {
+            v |= (*b as u32) << (8 * i);
+        }
+        for _ in 0..=group.len() {
+            out.push(CRYPT_HASH64[(v & 0x3f) as usize] as char);
+            v >>= 6;
+        }
+    }
+    out
+}
+
+fn fingerprint(path: &PathBuf) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// 解析 htpasswd：每行 `用户名:密码哈希`，忽略空行与 `#` 注释
+fn load_htpasswd(path: &PathBuf, label: &str) -> Result<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("{label} 读取 htpasswd 文件失败 {}: {e}", path.display()))?;
+    let mut users = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((user, hash)) = line.split_once(':') else {
+            return Err(anyhow!("{label} 的 htpasswd 文件第 {} 行格式无效", i + 1));
+        };
+        users.insert(user.trim().to_string(), hash.trim().to_string());
+    }
+    Ok(users)
+}
+
+struct Loaded {
+    fingerprint: Option<(SystemTime, u64)>,
+    users: Arc<HashMap<String, String>>,
+}
+
+/// 外部 htpasswd 文件：文件变化后由请求触发在后台重新加载
+struct Htpasswd {
+    file: PathBuf,
+    label: String,
+    loaded: RwLock<Loaded>,
+    last_check: AtomicI64,
+}
+
+impl Htpasswd {
+    fn load(path: &str, label: &str) -> Result<Self> {
+        let file = PathBuf::from(path);
+        let fp = fingerprint(&file);
+        let users = load_htpasswd(&file, label)?;
+        Ok(Self {
+            file,
+            label: label.to_string(),
+            loaded: RwLock::new(Loaded {
+                fingerprint: fp,
+                users: Arc::new(users),
+            }),
+            last_check: AtomicI64::new(chrono::Utc::now().timestamp_millis()),
+        })
+    }
+
+    /// 文件变化时重新加载；解析失败保留旧用户并写日志。返回是否已替换用户表
+    fn refresh(&self) -> bool {
+        let fp = fingerprint(&self.file);
+        if fp == self.loaded.read().fingerprint {
+            return false;
+        }
+        let result = load_htpasswd(&self.file, &self.label);
+        let mut loaded = self.loaded.write();
+        loaded.fingerprint = fp;
+        match result {
+            Ok(users) => {
+                proxy::send_log(format!("{} 的 htpasswd 文件已重新加载: {} 个用户", self.label, users.len()));
+                loaded.users = Arc::new(users);
+                true
+            }
+            Err(e) => {
+                proxy::send_log(format!("{e}，继续使用旧用户"));
+                false
+            }
+        }
+    }
+
+    /// 距上次检查超过间隔时返回 true（同一时刻只有一个请求负责检查）
+    fn due(&self) -> bool {
+        let now = chrono::Utc::now().timestamp_millis();
+        let last = self.last_check.load(Ordering::Relaxed);
+        now - last >= RELOAD_CHECK_INTERVAL_MS
+            && self
+                .last_check
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    fn users(&self) -> Arc<HashMap<String, String>> {
+        self.loaded.read().users.clone()
+    }
+}
+
+/// 请求适用的 Basic Auth 配置来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// 不需要认证
+    None,
+    /// 监听规则的 Basic Auth
+    Rule,
+    /// 路由自己的 Basic Auth
+    Route,
+}
+
+/// 路由 basic_auth_enable = true 时使用路由自己的配置；
+/// 否则沿用监听规则的配置，exclude_basic_auth = true 的路由跳过
+pub fn scope(rule: &config::ListenRule, route: Option<&config::Route>) -> Scope {
+    if let Some(r) = route {
+        if r.basic_auth_enable == Some(true) {
+            return Scope::Route;
+        }
+        if r.exclude_basic_auth.unwrap_or(false) {
+            return Scope::None;
+        }
+    }
+    if rule.basic_auth_enable {
+        Scope::Rule
+    } else {
+        Scope::None
+    }
+}
+
+/// 转发给上游前是否移除 Authorization：仅在本次请求确实做了 Basic Auth 且未开启转发时移除
+pub fn strips_authorization(rule: &config::ListenRule, route: Option<&config::Route>) -> bool {
+    match scope(rule, route) {
+        Scope::None => false,
+        Scope::Rule => !rule.basic_auth_forward_header,
+        Scope::Route => !route.and_then(|r| r.basic_auth_forward_header).unwrap_or(false),
+    }
+}
+
+/// 401 响应的 WWW-Authenticate 头
+pub fn www_authenticate(realm: Option<&str>) -> HeaderValue {
+    let realm = realm.map(str::trim).filter(|r| !r.is_empty()).unwrap_or(DEFAULT_REALM);
+    let escaped = realm.replace('\\', "\\\\").replace('"', "\\\"");
+    HeaderValue::from_str(&format!("Basic realm=\"{escaped}\""))
+        .unwrap_or_else(|_| HeaderValue::from_static("Basic realm=\"SSLProxyManager\""))
+}
+
+/// 预编译的 Basic Auth 用户集合
+pub struct BasicAuth {
+    users: HashMap<String, String>,
+    htpasswd: Option<Arc<Htpasswd>>,
+    // Authorization 头摘要 -> (认证时间, 用户名)，仅缓存认证成功的结果
+    cache: Arc<DashMap<[u8; 32], (Instant, String)>>,
+}
+
+impl BasicAuth {
+    pub fn compile(
+        users: &[config::BasicAuthUser],
+        legacy: Option<(&str, &str)>,
+        htpasswd_file: Option<&str>,
+        label: &str,
+    ) -> Result<Self> {
+        let mut map = HashMap::new();
+        if let Some((user, pass)) = legacy.filter(|(u, _)| !u.is_empty()) {
+            map.insert(user.to_string(), pass.to_string());
+        }
+        for (i, u) in users.iter().enumerate() {
+            let name = u.username.trim();
+            if name.is_empty() || name.contains(':') {
+                return Err(anyhow!("{label} 的 basic_auth_users[{i}] 用户名无效: {}", u.username));
+            }
+            map.insert(name.to_string(), u.password.clone());
+        }
+        let htpasswd = htpasswd_file
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| Htpasswd::load(p, label).map(Arc::new))
+            .transpose()?;
+        if map.is_empty() && htpasswd.is_none() {
+            return Err(anyhow!("{label} 启用了 Basic Auth 但未配置任何用户"));
+        }
+        Ok(Self {
+            users: map,
+            htpasswd,
+            cache: Arc::new(DashMap::new()),
+        })
+    }
+
+    /// 监听规则级 Basic Auth；未启用时返回 None
+    pub fn for_rule(rule: &config::ListenRule) -> Result<Option<Self>> {
+        if !rule.basic_auth_enable {
+            return Ok(None);
+        }
+        let label = format!("监听规则 {}", rule.listen_addr);
+        Self::compile(
+            &rule.basic_auth_users,
+            Some((rule.basic_auth_username.as_str(), rule.basic_auth_password.as_str())),
+            rule.basic_auth_htpasswd_file.as_deref(),
+            &label,
+        )
+        .map(Some)
+    }
+
+    /// 路由级 Basic Auth；basic_auth_enable 不为 true 时返回 None
+    pub fn for_route(route: &config::Route, label: &str) -> Result<Option<Self>> {
+        if route.basic_auth_enable != Some(true) {
+            return Ok(None);
+        }
+        let label = format!("路由 {label}");
+        Self::compile(
+            &route.basic_auth_users,
+            route
+                .basic_auth_username
+                .as_deref()
+                .map(|u| (u, route.basic_auth_password.as_deref().unwrap_or(""))),
+            route.basic_auth_htpasswd_file.as_deref(),
+            &label,
+        )
+        .map(Some)
+    }
+
+    /// 立即检查 htpasswd 文件是否变化；重新加载后清空认证缓存
+    pub(crate) fn refresh(&self) {
+        if self.htpasswd.as_ref().is_some_and(|h| h.refresh()) {
+            self.cache.clear();
+        }
+    }
+
+    /// 校验 Authorization 头，成功时返回用户名
+    pub async fn check(&self, headers: &HeaderMap) -> Option<String> {
+        let raw = headers.get(header::AUTHORIZATION)?;
+        // 检查与重新加载文件放到阻塞线程池，不占用处理请求的线程；本次请求仍使用当前用户表
+        if let Some(h) = self.htpasswd.as_ref().filter(|h| h.due()) {
+            match tokio::runtime::Handle::try_current() {
+                Ok(rt) => {
+                    let (h, cache) = (h.clone(), self.cache.clone());
+                    rt.spawn_blocking(move || {
+                        if h.refresh() {
+                            cache.clear();
+                        }
+                    });
+                }
+                Err(_) => self.refresh(),
+            }
+        }
+
+        let key: [u8; 32] = Sha256::digest(raw.as_bytes()).into();
+        if let Some(entry) = self.cache.get(&key) {
+            if entry.0.elapsed() < CACHE_TTL {
+                return Some(entry.1.clone());
+            }
+        }
+
+        let b64 = raw.to_str().ok()?.strip_prefix("Basic ")?;
+        let decoded = base64::engine::general_purpose::STANDARD.decode(b64.trim()).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (user, pass) = decoded.split_once(':')?;
+
+        // 配置中的用户优先于 htpasswd 文件中的同名用户
+        let htpasswd = self.htpasswd.as_ref().map(|h| h.users());
+        let stored = match self.users.get(user) {
+            Some(s) => s.clone(),
+            None => htpasswd.as_ref()?.get(user)?.clone(),
+        };
+        // bcrypt / argon2 / SHA-crypt 单次校验需要数十毫秒，且失败结果不缓存，放到阻塞线程池执行
+        let ok = if stored.starts_with('$') {
+            let pass = pass.to_string();
+            tokio::task::spawn_blocking(move || verify_password(&pass, &stored))
+                .await
+                .unwrap_or(false)
+        } else {
+            verify_password(pass, &stored)
+        };
+        if !ok {
+            return None;
+        }
+
+        if self.cache.len() >= CACHE_SWEEP_THRESHOLD {
+            self.cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+        }
+        self.cache.insert(key, (Instant::now(), user.to_string()));
+        Some(user.to_string())
+    }
+}
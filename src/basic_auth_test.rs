@@ -0,0 +1,173 @@
+// Basic Auth（basic_auth）模块的单元测试
+
+#[cfg(test)]
+mod basic_auth_tests {
+    use crate::basic_auth::{self, verify_password, BasicAuth, Scope};
+    use crate::config;
+    use crate::route_table::RouteTable;
+    use axum::http::{HeaderMap, Method};
+    use base64::Engine;
+
+    #[tokio::test]
+    async fn test_basic_auth_multi_user_hashes() {
+        use argon2::password_hash::{PasswordHasher, SaltString};
+
+        // htpasswd / openssl passwd 生成的哈希
+        assert!(verify_password("myPassword", "$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/"));
+        assert!(!verify_password("mypassword", "$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/"));
+        let sha512 = "$6$saltsalt$As4wrv0kZlfch1du9WeH7qhskyLriQWySXrZzynnvi46nFnNxjdpl6ksRegrrKexvhIa/Iny8S8uF3fVWTMuC1";
+        assert!(verify_password("s3cret", sha512));
+        assert!(verify_password("s3cret", "$5$saltsalt$i1q2ZQzc.tl/BQ6CHiENAcVDvEY6nJ1OWlWXKh94b1."));
+        assert!(verify_password("s3cret", "{SHA}/vNB+F2HQ559kaLUZbmHHvZrXpg="));
+        assert!(!verify_password("s3cret!", "{SHA}/vNB+F2HQ559kaLUZbmHHvZrXpg="));
+        assert!(verify_password("plain", "plain"));
+        assert!(!verify_password("plain", "plain2"));
+
+        let bcrypt = pwhash::bcrypt::hash_with(
+            pwhash::bcrypt::BcryptSetup { cost: Some(4), variant: Some(pwhash::bcrypt::BcryptVariant::V2y), ..Default::default() },
+            "b-pass",
+        )
+        .unwrap();
+        assert!(bcrypt.starts_with("$2y$04$"));
+        assert!(verify_password("b-pass", &bcrypt));
+        assert!(!verify_password("b-pas", &bcrypt));
+        let argon = argon2::Argon2::default()
+            .hash_password(b"a-pass", &SaltString::from_b64("c29tZXNhbHRzYWx0").unwrap())
+            .unwrap()
+            .to_string();
+        assert!(verify_password("a-pass", &argon));
+        assert!(!verify_password("a-pas", &argon));
+
+        let file = std::env::temp_dir().join(format!("htpasswd-test-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&file, format!("# users\nbob:{bcrypt}\ncarol:$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/\n")).unwrap();
+        let rule: config::ListenRule = toml::from_str(&format!(
+            r#"listen_addr = ":18081"
+               ssl_enable = false
+               cert_file = ""
+               key_file = ""
+               basic_auth_enable = true
+               basic_auth_username = "admin"
+               basic_auth_password = "legacy"
+               basic_auth_forward_header = false
+               basic_auth_htpasswd_file = "{}"
+               basic_auth_users = [{{ username = "alice", password = "{argon}" }}]
+               routes = []"#,
+            file.display()
+        ))
+        .unwrap();
+        let auth = BasicAuth::for_rule(&rule).unwrap().expect("enabled");
+        let auth = &auth;
+        let check = |user: &str, pass: &str| {
+            let mut headers = HeaderMap::new();
+            let token = base64::engine::general_purpose::STANDARD.encode(format!("{user}:{pass}"));
+            headers.insert("authorization", format!("Basic {token}").parse().unwrap());
+            async move { auth.check(&headers).await }
+        };
+
+        assert_eq!(check("admin", "legacy").await.as_deref(), Some("admin"));
+        assert_eq!(check("alice", "a-pass").await.as_deref(), Some("alice"));
+        assert_eq!(check("bob", "b-pass").await.as_deref(), Some("bob"));
+        assert_eq!(check("carol", "myPassword").await.as_deref(), Some("carol"));
+        assert_eq!(check("alice", "wrong").await, None);
+        assert_eq!(check("nobody", "legacy").await, None);
+        assert_eq!(auth.check(&HeaderMap::new()).await, None);
+
+        // htpasswd 文件变化后重新加载，已缓存的认证结果随之失效
+        std::fs::write(&file, "carol:{SHA}/vNB+F2HQ559kaLUZbmHHvZrXpg=\n").unwrap();
+        auth.refresh();
+        assert_eq!(check("bob", "b-pass").await, None);
+        assert_eq!(check("carol", "myPassword").await, None);
+        assert_eq!(check("carol", "s3cret").await.as_deref(), Some("carol"));
+        // 解析失败时继续使用旧用户
+        std::fs::write(&file, "broken line without colon\n").unwrap();
+        auth.refresh();
+        assert_eq!(check("carol", "s3cret").await.as_deref(), Some("carol"));
+        let _ = std::fs::remove_file(&file);
+
+        let mut empty = rule.clone();
+        empty.basic_auth_username = String::new();
+        empty.basic_auth_users.clear();
+        empty.basic_auth_htpasswd_file = None;
+        assert!(BasicAuth::for_rule(&empty).is_err());
+        empty.basic_auth_enable = false;
+        assert!(BasicAuth::for_rule(&empty).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_route_basic_auth_override() {
+        let rule: config::ListenRule = toml::from_str(
+            r#"listen_addr = ":18082"
+               ssl_enable = false
+               cert_file = ""
+               key_file = ""
+               basic_auth_enable = true
+               basic_auth_username = "ops"
+               basic_auth_password = "ops-pass"
+               basic_auth_forward_header = false
+               basic_auth_realm = "Corp"
+
+               [[routes]]
+               id = "public"
+               path = "/public/"
+               exclude_basic_auth = true
+               upstreams = [{ url = "http://127.0.0.1:9", weight = 1 }]
+
+               [[routes]]
+               id = "admin"
+               path = "/admin/"
+               basic_auth_enable = true
+               basic_auth_username = "root"
+               basic_auth_password = "toor"
+               basic_auth_users = [{ username = "auditor", password = "{SHA}/vNB+F2HQ559kaLUZbmHHvZrXpg=" }]
+               basic_auth_forward_header = true
+               basic_auth_realm = "Admin \"area\""
+               exclude_basic_auth = true
+               upstreams = [{ url = "http://127.0.0.1:9", weight = 1 }]
+
+               [[routes]]
+               id = "app"
+               path = "/"
+               basic_auth_enable = false
+               upstreams = [{ url = "http://127.0.0.1:9", weight = 1 }]"#,
+        )
+        .unwrap();
+        let t = RouteTable::compile(&rule).unwrap();
+        let compiled = |path: &str| t.match_route("a.com", path, &Method::GET, &HeaderMap::new()).0.unwrap().route;
+
+        // 路由启用了自己的 Basic Auth 时优先于 exclude_basic_auth；显式关闭不会绕过监听规则
+        assert_eq!(basic_auth::scope(&rule, Some(&compiled("/public/x").route)), Scope::None);
+        assert_eq!(basic_auth::scope(&rule, Some(&compiled("/admin/x").route)), Scope::Route);
+        assert_eq!(basic_auth::scope(&rule, Some(&compiled("/x").route)), Scope::Rule);
+        assert_eq!(basic_auth::scope(&rule, None), Scope::Rule);
+
+        let basic = |user: &str, pass: &str| {
+            let mut headers = HeaderMap::new();
+            let token = base64::engine::general_purpose::STANDARD.encode(format!("{user}:{pass}"));
+            headers.insert("authorization", format!("Basic {token}").parse().unwrap());
+            headers
+        };
+        let admin = compiled("/admin/x").basic_auth.as_ref().expect("route basic auth");
+        assert_eq!(admin.check(&basic("root", "toor")).await.as_deref(), Some("root"));
+        assert_eq!(admin.check(&basic("auditor", "s3cret")).await.as_deref(), Some("auditor"));
+        assert_eq!(admin.check(&basic("ops", "ops-pass")).await, None);
+        assert!(compiled("/x").basic_auth.is_none());
+
+        assert_eq!(basic_auth::www_authenticate(Some("Admin \"area\"")), r#"Basic realm="Admin \"area\"""#);
+        assert_eq!(basic_auth::www_authenticate(Some("Corp")), r#"Basic realm="Corp""#);
+        assert_eq!(basic_auth::www_authenticate(None), r#"Basic realm="SSLProxyManager""#);
+
+        // Authorization 只在本次请求做了 Basic Auth 且未开启转发时移除
+        let outbound_auth = |path: &str| {
+            let headers = std::collections::HashMap::from([("Authorization".to_string(), "Basic xyz".to_string())]);
+            let out = crate::proxy::explain_request(&rule, ":18082", "a.com", path, "GET", &headers, None, None).unwrap();
+            out.outbound_headers.iter().any(|(k, _)| k == "authorization")
+        };
+        assert!(!outbound_auth("/x"));
+        assert!(outbound_auth("/admin/x"));
+        assert!(outbound_auth("/public/x"));
+
+        let no_users: config::Route = toml::from_str(r#"path = "/"
+               basic_auth_enable = true"#).unwrap();
+        assert!(BasicAuth::for_route(&no_users, "/").is_err());
+    }
+}
@@ -0,0 +1,531 @@
+use anyhow::{anyhow, Result};
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use crate::config;
+
+pub const DEFAULT_KEY_TEMPLATE: &str = "$scheme://$host$request_uri";
+
+/// 可被缓存的状态码（RFC 9111 中默认可缓存的常见状态）
+const CACHEABLE_STATUS: [u16; 6] = [200, 203, 204, 301, 404, 410];
+
+/// 所有路由缓存的弱引用注册表，供清除命令遍历
+static CACHES: Lazy<RwLock<Vec<Weak<RouteCache>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+#[inline]
+fn now_ts() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// 缓存条目元数据；磁盘存储时同时落盘为 `<hash>.meta`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EntryMeta {
+    key: String,
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    /// 存储时请求中 Vary 头对应的值
+    vary: Vec<(String, Option<Vec<u8>>)>,
+    stored_at: i64,
+    fresh_until: i64,
+    stale_while_revalidate: i64,
+    stale_if_error: i64,
+    size: u64,
+}
+
+struct Entry {
+    meta: EntryMeta,
+    /// 内存存储时持有响应体；磁盘存储时为 None
+    body: Option<Bytes>,
+    last_access: AtomicI64,
+}
+
+/// 命中的缓存内容
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    age: i64,
+    stale_if_error_until: i64,
+    stale_while_revalidate_until: i64,
+}
+
+impl CachedResponse {
+    /// 是否仍处于 stale-while-revalidate 窗口内
+    pub fn can_revalidate(&self) -> bool {
+        now_ts() <= self.stale_while_revalidate_until
+    }
+
+    /// 上游出错时是否仍可返回
+    pub fn usable_on_error(&self) -> bool {
+        now_ts() <= self.stale_if_error_until
+    }
+
+    /// 生成响应头（附带 Age）
+    pub fn response_headers(&self) -> HeaderMap {
+        let mut h = self.headers.clone();
+        h.insert(header::AGE, HeaderValue::from(self.age.max(0) as u64));
+        h
+    }
+}
+
+pub enum Lookup {
+    Fresh(CachedResponse),
+    /// 已过期，但仍可用于 stale-while-revalidate 或 stale-if-error
+    Stale(CachedResponse),
+    Miss,
+}
+
+/// 单个路由的缓存实例
+pub struct RouteCache {
+    pub route_id: String,
+    cfg: config::CacheConfig,
+    disk_dir: Option<PathBuf>,
+    entries: DashMap<String, Arc<Entry>>,
+    total_size: AtomicU64,
+    locks: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+}
+
+/// 并发未命中合并锁；释放时清理无人等待的锁
+pub struct CacheLockGuard {
+    cache: Arc<RouteCache>,
+    key: String,
+    _guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl Drop for CacheLockGuard {
+    fn drop(&mut self) {
+        // 注册表与当前 guard 各持有一份；更多引用说明仍有等待者
+        self.cache
+            .locks
+            .remove_if(&self.key, |_, m| Arc::strong_count(m) <= 2);
+    }
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// 解析 Cache-Control 中的 `name=秒数` 指令
+fn directive_secs(cc: &str, name: &str) -> Option<i64> {
+    cc.split(',').find_map(|d| {
+        let (k, v) = d.trim().split_once('=')?;
+        if k.trim().eq_ignore_ascii_case(name) {
+            v.trim().trim_matches('"').parse::<i64>().ok()
+        } else {
+            None
+        }
+    })
+}
+
+fn has_directive(cc: &str, name: &str) -> bool {
+    cc.split(',')
+        .any(|d| d.trim().split('=').next().unwrap_or("").trim().eq_ignore_ascii_case(name))
+}
+
+fn cache_control(headers: &HeaderMap) -> String {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 响应带 Set-Cookie 或 Cache-Control: private / no-store 时只属于当前用户，不能给其他请求复用
+pub fn is_private(headers: &HeaderMap) -> bool {
+    if headers.contains_key(header::SET_COOKIE) {
+        return true;
+    }
+    let cc = cache_control(headers);
+    has_directive(&cc, "no-store") || has_directive(&cc, "private")
+}
+
+/// 请求声明 no-store 时完全绕过缓存
+pub fn request_no_store(headers: &HeaderMap) -> bool {
+    has_directive(&cache_control(headers), "no-store")
+}
+
+/// 请求声明 no-cache（或 Pragma: no-cache）时跳过读取，但仍写入新结果
+pub fn request_no_cache(headers: &HeaderMap) -> bool {
+    has_directive(&cache_control(headers), "no-cache")
+        || headers
+            .get(header::PRAGMA)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.to_ascii_lowercase().contains("no-cache"))
+}
+
+impl RouteCache {
+    pub fn new(route_id: String, cfg: &config::CacheConfig, label: &str) -> Result<Arc<Self>> {
+        let disk_dir = match cfg.storage {
+            config::CacheStorage::Memory => None,
+            config::CacheStorage::Disk => {
+                let dir = cfg
+                    .disk_path
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .ok_or_else(|| anyhow!("路由 {label} 的 cache.storage 为 disk，但未配置 disk_path"))?;
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| anyhow!("路由 {label} 的缓存目录创建失败 {dir}: {e}"))?;
+                Some(PathBuf::from(dir))
+            }
+        };
+
+        let cache = Arc::new(Self {
+            route_id,
+            cfg: cfg.clone(),
+            disk_dir,
+            entries: DashMap::new(),
+            total_size: AtomicU64::new(0),
+            locks: DashMap::new(),
+        });
+
+        let mut caches = CACHES.write();
+        caches.retain(|w| w.strong_count() > 0);
+        caches.push(Arc::downgrade(&cache));
+        Ok(cache)
+    }
+
+    pub fn key_template(&self) -> &str {
+        self.cfg
+            .key
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .unwrap_or(DEFAULT_KEY_TEMPLATE)
+    }
+
+    pub fn lock_timeout(&self) -> Duration {
+        Duration::from_millis(self.cfg.lock_timeout_ms)
+    }
+
+    fn max_entry_size(&self) -> u64 {
+        self.cfg.max_entry_kb.saturating_mul(1024)
+    }
+
+    fn max_total_size(&self) -> u64 {
+        self.cfg.max_size_mb.saturating_mul(1024 * 1024)
+    }
+
+    fn paths(&self, key: &str) -> Option<(PathBuf, PathBuf)> {
+        let dir = self.disk_dir.as_ref()?;
+        let h = hash_key(key);
+        Some((dir.join(format!("{h}.meta")), dir.join(format!("{h}.body"))))
+    }
+
+    /// 查找缓存；磁盘存储在内存索引未命中时尝试从文件恢复（进程重启后仍可命中）
+    pub async fn lookup(&self, key: &str, req_headers: &HeaderMap) -> Lookup {
+        let entry = match self.entries.get(key).map(|e| e.clone()) {
+            Some(e) => e,
+            None => match self.load_from_disk(key).await {
+                Some(e) => e,
+                None => return Lookup::Miss,
+            },
+        };
+
+        let meta = &entry.meta;
+        let vary_ok = meta.vary.iter().all(|(name, stored)| {
+            let actual = req_headers.get(name.as_str()).map(|v| v.as_bytes().to_vec());
+            actual == *stored
+        });
+        if !vary_ok {
+            return Lookup::Miss;
+        }
+
+        let now = now_ts();
+        let swr_until = meta.fresh_until + meta.stale_while_revalidate;
+        let sie_until = meta.fresh_until + meta.stale_if_error;
+        if now > swr_until && now > sie_until {
+            self.remove(key).await;
+            return Lookup::Miss;
+        }
+
+        let body = match entry.body.as_ref() {
+            Some(b) => b.clone(),
+            None => match self.paths(key) {
+                Some((_, body_path)) => match tokio::fs::read(&body_path).await {
+                    Ok(b) => Bytes::from(b),
+                    Err(_) => {
+                        self.remove(key).await;
+                        return Lookup::Miss;
+                    }
+                },
+                None => return Lookup::Miss,
+            },
+        };
+        entry.last_access.store(now, Ordering::Relaxed);
+
+        let mut headers = HeaderMap::new();
+        for (k, v) in &meta.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(k.as_bytes()), HeaderValue::from_bytes(v)) {
+                headers.append(name, value);
+            }
+        }
+
+        let hit = CachedResponse {
+            status: StatusCode::from_u16(meta.status).unwrap_or(StatusCode::OK),
+            headers,
+            body,
+            age: now - meta.stored_at,
+            stale_if_error_until: sie_until,
+            stale_while_revalidate_until: swr_until,
+        };
+        if now <= meta.fresh_until {
+            Lookup::Fresh(hit)
+        } else {
+            Lookup::Stale(hit)
+        }
+    }
+
+    async fn load_from_disk(&self, key: &str) -> Option<Arc<Entry>> {
+        let (meta_path, _) = self.paths(key)?;
+        let raw = tokio::fs::read(&meta_path).await.ok()?;
+        let meta: EntryMeta = serde_json::from_slice(&raw).ok()?;
+        if meta.key != key {
+            return None;
+        }
+        let entry = Arc::new(Entry {
+            last_access: AtomicI64::new(now_ts()),
+            meta,
+            body: None,
+        });
+        self.total_size.fetch_add(entry.meta.size, Ordering::Relaxed);
+        if let Some(old) = self.entries.insert(key.to_string(), entry.clone()) {
+            self.total_size.fetch_sub(old.meta.size, Ordering::Relaxed);
+        }
+        Some(entry)
+    }
+
+    /// 获取回源锁：同一 key 的并发未命中只放行一个请求，超时后返回 None（各自回源）
+    pub async fn lock(self: &Arc<Self>, key: &str) -> Option<CacheLockGuard> {
+        let m = self
+            .locks
+            .entry(key.to_string())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+            .clone();
+        let guard = tokio::time::timeout(self.lock_timeout(), m.lock_owned()).await.ok()?;
+        Some(CacheLockGuard {
+            cache: self.clone(),
+            key: key.to_string(),
+            _guard: guard,
+        })
+    }
+
+    /// 根据上游响应头计算缓存策略：(ttl, stale_while_revalidate, stale_if_error)
+    fn policy(&self, status: StatusCode, headers: &HeaderMap) -> Option<(i64, i64, i64)> {
+        if !CACHEABLE_STATUS.contains(&status.as_u16()) || is_private(headers) {
+            return None;
+        }
+        let cc = cache_control(headers);
+        if has_directive(&cc, "no-cache") {
+            return None;
+        }
+        let vary = headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.split(',').any(|x| x.trim() == "*"));
+        if vary {
+            return None;
+        }
+
+        let ttl = directive_secs(&cc, "s-maxage")
+            .or_else(|| directive_secs(&cc, "max-age"))
+            .or_else(|| {
+                let expires = headers.get(header::EXPIRES)?.to_str().ok()?;
+                let t = chrono::DateTime::parse_from_rfc2822(expires).ok()?;
+                Some(t.timestamp() - now_ts())
+            })
+            .unwrap_or(self.cfg.default_ttl_secs as i64);
+        let swr = directive_secs(&cc, "stale-while-revalidate")
+            .unwrap_or(self.cfg.stale_while_revalidate_secs as i64);
+        let sie = directive_secs(&cc, "stale-if-error").unwrap_or(self.cfg.stale_if_error_secs as i64);
+
+        if ttl <= 0 && swr <= 0 && sie <= 0 {
+            return None;
+        }
+        Some((ttl.max(0), swr.max(0), sie.max(0)))
+    }
+
+    /// 上游响应是否可以写入缓存（不含条目大小检查）
+    pub fn cacheable(&self, status: StatusCode, headers: &HeaderMap) -> bool {
+        self.policy(status, headers).is_some()
+    }
+
+    /// 按上游响应决定是否写入缓存，返回是否已写入
+    pub async fn store(
+        &self,
+        key: &str,
+        req_headers: &HeaderMap,
+        status: StatusCode,
+        resp_headers: &HeaderMap,
+        body: Bytes,
+    ) -> bool {
+        let Some((ttl, swr, sie)) = self.policy(status, resp_headers) else {
+            return false;
+        };
+        let size = body.len() as u64;
+        if size > self.max_entry_size() {
+            return false;
+        }
+
+        let vary = resp_headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|n| n.trim().to_ascii_lowercase())
+            .filter(|n| !n.is_empty())
+            .map(|n| {
+                let v = req_headers.get(n.as_str()).map(|v| v.as_bytes().to_vec());
+                (n, v)
+            })
+            .collect();
+
+        // Content-Length 由响应体决定；Age 在返回时重新计算
+        let headers = resp_headers
+            .iter()
+            .filter(|(k, _)| *k != header::CONTENT_LENGTH && *k != header::AGE)
+            .map(|(k, v)| (k.as_str().to_string(), v.as_bytes().to_vec()))
+            .collect();
+
+        let now = now_ts();
+        let meta = EntryMeta {
+            key: key.to_string(),
+            status: status.as_u16(),
+            headers,
+            vary,
+            stored_at: now,
+            fresh_until: now + ttl,
+            stale_while_revalidate: swr,
+            stale_if_error: sie,
+            size,
+        };
+
+        let body = match self.paths(key) {
+            Some((meta_path, body_path)) => {
+                let Ok(raw) = serde_json::to_vec(&meta) else {
+                    return false;
+                };
+                if tokio::fs::write(&body_path, &body).await.is_err()
+                    || tokio::fs::write(&meta_path, raw).await.is_err()
+                {
+                    return false;
+                }
+                None
+            }
+            None => Some(body),
+        };
+
+        let entry = Arc::new(Entry {
+            meta,
+            body,
+            last_access: AtomicI64::new(now),
+        });
+        self.total_size.fetch_add(size, Ordering::Relaxed);
+        if let Some(old) = self.entries.insert(key.to_string(), entry) {
+            self.total_size.fetch_sub(old.meta.size, Ordering::Relaxed);
+        }
+        self.evict().await;
+        true
+    }
+
+    /// 超出总大小时淘汰最久未访问的条目
+    async fn evict(&self) {
+        let max = self.max_total_size();
+        if self.total_size.load(Ordering::Relaxed) <= max {
+            return;
+        }
+        let mut by_access: Vec<(i64, String)> = self
+            .entries
+            .iter()
+            .map(|e| (e.last_access.load(Ordering::Relaxed), e.key().clone()))
+            .collect();
+        by_access.sort_unstable();
+        for (_, key) in by_access {
+            if self.total_size.load(Ordering::Relaxed) <= max {
+                break;
+            }
+            self.remove(&key).await;
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        if let Some((_, old)) = self.entries.remove(key) {
+            self.total_size.fetch_sub(old.meta.size, Ordering::Relaxed);
+        }
+        if let Some((meta_path, body_path)) = self.paths(key) {
+            let _ = tokio::fs::remove_file(meta_path).await;
+            let _ = tokio::fs::remove_file(body_path).await;
+        }
+    }
+
+    /// 清除缓存：key_prefix 为空时清除全部，返回清除的条目数
+    async fn purge(&self, key_prefix: Option<&str>) -> usize {
+        let keys: Vec<String> = self
+            .entries
+            .iter()
+            .map(|e| e.key().clone())
+            .filter(|k| key_prefix.is_none_or(|p| k.starts_with(p)))
+            .collect();
+        for k in &keys {
+            self.remove(k).await;
+        }
+        let mut count = keys.len();
+        // 磁盘存储：再清除尚未加载到内存索引的条目（例如重启前写入的文件）
+        if let Some(dir) = self.disk_dir.as_deref() {
+            count += purge_disk(dir, key_prefix).await;
+        }
+        count
+    }
+}
+
+/// 扫描缓存目录，按元数据中的缓存键清除匹配的 `<hash>.meta` / `<hash>.body`
+async fn purge_disk(dir: &Path, key_prefix: Option<&str>) -> usize {
+    let Ok(mut rd) = tokio::fs::read_dir(dir).await else {
+        return 0;
+    };
+    let mut count = 0;
+    while let Ok(Some(ent)) = rd.next_entry().await {
+        let meta_path = ent.path();
+        if meta_path.extension().is_none_or(|e| e != "meta") {
+            continue;
+        }
+        let Ok(raw) = tokio::fs::read(&meta_path).await else {
+            continue;
+        };
+        let Ok(meta) = serde_json::from_slice::<EntryMeta>(&raw) else {
+            continue;
+        };
+        if key_prefix.is_some_and(|p| !meta.key.starts_with(p)) {
+            continue;
+        }
+        let _ = tokio::fs::remove_file(meta_path.with_extension("body")).await;
+        if tokio::fs::remove_file(&meta_path).await.is_ok() {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// 清除缓存：可按路由 ID 与缓存键前缀过滤，返回清除的条目数
+pub async fn purge(route_id: Option<&str>, key_prefix: Option<&str>) -> usize {
+    let caches: Vec<Arc<RouteCache>> = CACHES.read().iter().filter_map(|w| w.upgrade()).collect();
+    let mut total = 0;
+    for c in caches {
+        if route_id.is_some_and(|id| id != c.route_id) {
+            continue;
+        }
+        total += c.purge(key_prefix).await;
+    }
+    total
+}
@@ -0,0 +1,83 @@
+// 响应缓存（cache）模块的单元测试
+
+#[cfg(test)]
+mod cache_tests {
+    use crate::cache::{self, Lookup, RouteCache};
+    use crate::config;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, HeaderValue, StatusCode};
+
+    fn cache_cfg(toml_src: &str) -> config::CacheConfig {
+        toml::from_str(toml_src).expect("invalid cache toml")
+    }
+
+    #[tokio::test]
+    async fn test_cache_store_policy() {
+        let c = RouteCache::new("cached".into(), &cache_cfg("default_ttl_secs = 0"), "cached").unwrap();
+        assert_eq!(c.key_template(), cache::DEFAULT_KEY_TEMPLATE);
+
+        let mut req = HeaderMap::new();
+        req.insert("accept-language", HeaderValue::from_static("zh"));
+        let resp_headers = |cc: &'static str| {
+            let mut h = HeaderMap::new();
+            h.insert("cache-control", HeaderValue::from_static(cc));
+            h.insert("vary", HeaderValue::from_static("Accept-Language"));
+            h
+        };
+
+        // (Cache-Control, 状态码, 是否写入)
+        let cases = [
+            ("max-age=60", StatusCode::OK, true),
+            ("no-store", StatusCode::OK, false),
+            ("private, max-age=60", StatusCode::OK, false),
+            ("max-age=60", StatusCode::INTERNAL_SERVER_ERROR, false),
+            ("max-age=0", StatusCode::OK, false),
+        ];
+        for (i, (cc, status, want)) in cases.into_iter().enumerate() {
+            let key = format!("k{i}");
+            let stored = c.store(&key, &req, status, &resp_headers(cc), Bytes::from_static(b"hi")).await;
+            assert_eq!(stored, want, "cache-control={cc} status={status}");
+        }
+
+        assert!(matches!(c.lookup("k0", &req).await, Lookup::Fresh(_)));
+        // Vary 指定的请求头不同则视为未命中
+        let mut other = HeaderMap::new();
+        other.insert("accept-language", HeaderValue::from_static("en"));
+        assert!(matches!(c.lookup("k0", &other).await, Lookup::Miss));
+
+        assert_eq!(cache::purge(Some("cached"), Some("k")).await, 1);
+        assert!(matches!(c.lookup("k0", &req).await, Lookup::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_disk_purge_unloaded_entries() {
+        let dir = std::env::temp_dir().join(format!("cache-purge-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cfg = cache_cfg(&format!(
+            r#"storage = "disk"
+               disk_path = "{}""#,
+            dir.display().to_string().replace('\\', "/")
+        ));
+        let mut resp = HeaderMap::new();
+        resp.insert("cache-control", HeaderValue::from_static("max-age=60"));
+        let req = HeaderMap::new();
+
+        let before = RouteCache::new("disk-purge".into(), &cfg, "disk-purge").unwrap();
+        for key in ["user/1", "user/2", "other/1"] {
+            assert!(before.store(key, &req, StatusCode::OK, &resp, Bytes::from_static(b"hi")).await);
+        }
+        assert!(before.cacheable(StatusCode::OK, &resp));
+        drop(before);
+
+        // 模拟重启：新实例的内存索引为空，清除仍需覆盖磁盘上的条目
+        let after = RouteCache::new("disk-purge".into(), &cfg, "disk-purge").unwrap();
+        assert_eq!(cache::purge(Some("disk-purge"), Some("user/")).await, 2);
+        assert!(matches!(after.lookup("user/1", &req).await, Lookup::Miss));
+        assert!(matches!(after.lookup("other/1", &req).await, Lookup::Fresh(_)));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        assert_eq!(cache::purge(Some("disk-purge"), None).await, 1);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,468 @@
+use anyhow::{anyhow, Context, Result};
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, StatusCode};
+use base64::Engine;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+use crate::{metrics, proxy};
+
+/// 抓包会话：按条件记录完整的请求 / 响应，写入 SQLite 的 captures 表
+static SESSIONS: Lazy<RwLock<Vec<Arc<Session>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+/// 是否存在会话；没有会话时请求热路径只读取这一个原子变量
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// 写入前替换取值的敏感头
+const REDACTED_HEADERS: [header::HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::PROXY_AUTHORIZATION,
+    header::COOKIE,
+    header::SET_COOKIE,
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartCaptureRequest {
+    pub route_id: Option<String>,
+    pub client_ip: Option<String>,
+    /// 请求路径前缀
+    pub path_prefix: Option<String>,
+    /// 状态码过滤，逗号分隔，支持 `404`、`5xx`
+    pub status: Option<String>,
+    /// 会话有效期（秒），默认 300
+    pub ttl_secs: Option<u64>,
+    /// 最多记录的条数，默认 100
+    pub max_count: Option<u32>,
+    /// 单个请求 / 响应体最多保存的大小（KB），默认 256
+    pub max_body_kb: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureSessionInfo {
+    pub id: String,
+    pub route_id: Option<String>,
+    pub client_ip: Option<String>,
+    pub path_prefix: Option<String>,
+    pub status: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub max_count: u32,
+    pub max_body_kb: usize,
+    pub captured: u32,
+}
+
+pub struct Session {
+    info: CaptureSessionInfo,
+    statuses: Vec<(u16, u16)>,
+    captured: AtomicU32,
+}
+
+/// 一次完整的请求 / 响应（请求体、响应体为客户端实际收发的内容）
+pub struct CaptureEntry<'a> {
+    pub route_id: &'a str,
+    pub client_ip: &'a str,
+    pub method: &'a str,
+    pub url: String,
+    pub http_version: &'a str,
+    pub request_headers: &'a HeaderMap,
+    pub request_body: Bytes,
+    pub status: u16,
+    pub upstream: &'a str,
+    pub latency_ms: f64,
+    pub response_headers: &'a HeaderMap,
+    pub response_body: Bytes,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct CaptureRow {
+    id: i64,
+    session_id: String,
+    timestamp_ms: i64,
+    route_id: String,
+    client_ip: String,
+    method: String,
+    url: String,
+    http_version: String,
+    status_code: i64,
+    upstream: String,
+    latency_ms: f64,
+    request_headers: String,
+    request_body: Vec<u8>,
+    request_body_size: i64,
+    response_headers: String,
+    response_body: Vec<u8>,
+    response_body_size: i64,
+}
+
+/// 展示用的抓包记录；非 UTF-8 的内容以 base64 返回
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedExchange {
+    pub id: i64,
+    pub session_id: String,
+    pub timestamp_ms: i64,
+    pub route_id: String,
+    pub client_ip: String,
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub status_code: i64,
+    pub upstream: String,
+    pub latency_ms: f64,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: String,
+    pub request_body_encoding: Option<String>,
+    pub request_body_size: i64,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: String,
+    pub response_body_encoding: Option<String>,
+    pub response_body_size: i64,
+}
+
+fn parse_statuses(raw: &str) -> Result<Vec<(u16, u16)>> {
+    let mut out = Vec::new();
+    for part in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let range = match part.strip_suffix("xx").or_else(|| part.strip_suffix("XX")) {
+            Some(d) => d.parse::<u16>().ok().filter(|d| (1..=5).contains(d)).map(|d| (d * 100, d * 100 + 99)),
+            None => part.parse::<u16>().ok().filter(|c| (100..=599).contains(c)).map(|c| (c, c)),
+        };
+        out.push(range.ok_or_else(|| anyhow!("状态码过滤无效: {part}"))?);
+    }
+    Ok(out)
+}
+
+fn non_empty(v: Option<String>) -> Option<String> {
+    v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+pub fn start(req: StartCaptureRequest) -> Result<CaptureSessionInfo> {
+    let status = non_empty(req.status);
+    let statuses = parse_statuses(status.as_deref().unwrap_or(""))?;
+    let now = chrono::Utc::now().timestamp();
+    let info = CaptureSessionInfo {
+        id: uuid::Uuid::new_v4().to_string(),
+        route_id: non_empty(req.route_id),
+        client_ip: non_empty(req.client_ip),
+        path_prefix: non_empty(req.path_prefix),
+        status,
+        created_at: now,
+        expires_at: now + req.ttl_secs.unwrap_or(300).max(1) as i64,
+        max_count: req.max_count.unwrap_or(100).max(1),
+        max_body_kb: req.max_body_kb.unwrap_or(256),
+        captured: 0,
+    };
+
+    let mut sessions = SESSIONS.write();
+    sessions.push(Arc::new(Session {
+        info: info.clone(),
+        statuses,
+        captured: AtomicU32::new(0),
+    }));
+    ACTIVE.store(true, Ordering::Relaxed);
+    proxy::send_log(format!("抓包已开始: {}", info.id));
+    Ok(info)
+}
+
+pub fn stop(id: &str) -> bool {
+    let mut sessions = SESSIONS.write();
+    let before = sessions.len();
+    sessions.retain(|s| s.info.id != id);
+    ACTIVE.store(!sessions.is_empty(), Ordering::Relaxed);
+    before != sessions.len()
+}
+
+/// 当前会话（已过期或已记满的会话在此清理）
+pub fn sessions() -> Vec<CaptureSessionInfo> {
+    prune();
+    SESSIONS
+        .read()
+        .iter()
+        .map(|s| CaptureSessionInfo {
+            captured: s.captured.load(Ordering::Relaxed),
+            ..s.info.clone()
+        })
+        .collect()
+}
+
+fn prune() {
+    let now = chrono::Utc::now().timestamp();
+    let mut sessions = SESSIONS.write();
+    sessions.retain(|s| s.info.expires_at > now && s.captured.load(Ordering::Relaxed) < s.info.max_count);
+    ACTIVE.store(!sessions.is_empty(), Ordering::Relaxed);
+}
+
+/// 查找与请求匹配的会话；命中时调用方需缓冲请求体与响应体
+pub fn matching(route_id: &str, client_ip: &str, path: &str) -> Option<Arc<Session>> {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+    let now = chrono::Utc::now().timestamp();
+    let (found, stale) = {
+        let sessions = SESSIONS.read();
+        let found = sessions.iter().find(|s| s.accepts_request(now, route_id, client_ip, path)).cloned();
+        let stale = sessions.iter().any(|s| s.info.expires_at <= now);
+        (found, stale)
+    };
+    if stale {
+        prune();
+    }
+    found
+}
+
+fn headers_json(headers: &HeaderMap) -> String {
+    let pairs: Vec<(&str, String)> = headers
+        .iter()
+        .map(|(k, v)| {
+            let value = if REDACTED_HEADERS.contains(k) {
+                "[REDACTED]".to_string()
+            } else {
+                String::from_utf8_lossy(v.as_bytes()).into_owned()
+            };
+            (k.as_str(), value)
+        })
+        .collect();
+    serde_json::to_string(&pairs).unwrap_or_else(|_| "[]".to_string())
+}
+
+impl Session {
+    fn accepts_request(&self, now: i64, route_id: &str, client_ip: &str, path: &str) -> bool {
+        let i = &self.info;
+        i.expires_at > now
+            && self.captured.load(Ordering::Relaxed) < i.max_count
+            && i.route_id.as_deref().is_none_or(|r| r == route_id)
+            && i.client_ip.as_deref().is_none_or(|c| c == client_ip)
+            && i.path_prefix.as_deref().is_none_or(|p| path.starts_with(p))
+    }
+
+    pub fn accepts_status(&self, status: u16) -> bool {
+        self.statuses.is_empty() || self.statuses.iter().any(|(lo, hi)| (*lo..=*hi).contains(&status))
+    }
+
+    /// 占用一个名额并异步写库；名额用完时忽略
+    pub fn record(&self, entry: CaptureEntry<'_>) {
+        let n = self.captured.fetch_add(1, Ordering::Relaxed);
+        if n >= self.info.max_count {
+            self.captured.fetch_sub(1, Ordering::Relaxed);
+            return;
+        }
+
+        let Some(pool) = metrics::pool() else {
+            return;
+        };
+        let cap = self.info.max_body_kb.saturating_mul(1024);
+        let truncate = |b: &Bytes| b.slice(..b.len().min(cap));
+        let session_id = self.info.id.clone();
+        let row = (
+            entry.route_id.to_string(),
+            entry.client_ip.to_string(),
+            entry.method.to_string(),
+            entry.url,
+            entry.http_version.to_string(),
+            entry.status as i64,
+            entry.upstream.to_string(),
+            entry.latency_ms,
+            headers_json(entry.request_headers),
+            truncate(&entry.request_body),
+            entry.request_body.len() as i64,
+            headers_json(entry.response_headers),
+            truncate(&entry.response_body),
+            entry.response_body.len() as i64,
+        );
+
+        tokio::spawn(async move {
+            let result = sqlx::query(
+                "INSERT INTO captures (session_id, timestamp_ms, route_id, client_ip, method, url, http_version, status_code, upstream, latency_ms, request_headers, request_body, request_body_size, response_headers, response_body, response_body_size) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(session_id)
+            .bind(chrono::Utc::now().timestamp_millis())
+            .bind(row.0)
+            .bind(row.1)
+            .bind(row.2)
+            .bind(row.3)
+            .bind(row.4)
+            .bind(row.5)
+            .bind(row.6)
+            .bind(row.7)
+            .bind(row.8)
+            .bind(row.9.to_vec())
+            .bind(row.10)
+            .bind(row.11)
+            .bind(row.12.to_vec())
+            .bind(row.13)
+            .execute(&*pool)
+            .await;
+            if let Err(e) = result {
+                proxy::send_log(format!("写入抓包记录失败: {e}"));
+            }
+        });
+    }
+}
+
+/// 文本内容原样返回，二进制内容转为 base64
+fn body_text(body: &[u8]) -> (String, Option<String>) {
+    match std::str::from_utf8(body) {
+        Ok(s) => (s.to_string(), None),
+        Err(_) => (
+            base64::engine::general_purpose::STANDARD.encode(body),
+            Some("base64".to_string()),
+        ),
+    }
+}
+
+impl From<CaptureRow> for CapturedExchange {
+    fn from(r: CaptureRow) -> Self {
+        let (request_body, request_body_encoding) = body_text(&r.request_body);
+        let (response_body, response_body_encoding) = body_text(&r.response_body);
+        Self {
+            id: r.id,
+            session_id: r.session_id,
+            timestamp_ms: r.timestamp_ms,
+            route_id: r.route_id,
+            client_ip: r.client_ip,
+            method: r.method,
+            url: r.url,
+            http_version: r.http_version,
+            status_code: r.status_code,
+            upstream: r.upstream,
+            latency_ms: r.latency_ms,
+            request_headers: serde_json::from_str(&r.request_headers).unwrap_or_default(),
+            request_body,
+            request_body_encoding,
+            request_body_size: r.request_body_size,
+            response_headers: serde_json::from_str(&r.response_headers).unwrap_or_default(),
+            response_body,
+            response_body_encoding,
+            response_body_size: r.response_body_size,
+        }
+    }
+}
+
+pub async fn query(session_id: Option<String>, limit: i64, offset: i64) -> Result<Vec<CapturedExchange>> {
+    let pool = metrics::pool().ok_or_else(|| anyhow!("metrics 数据库未初始化"))?;
+    let rows: Vec<CaptureRow> = match non_empty(session_id) {
+        Some(id) => sqlx::query_as("SELECT * FROM captures WHERE session_id = ? ORDER BY id LIMIT ? OFFSET ?")
+            .bind(id)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&*pool)
+            .await,
+        None => sqlx::query_as("SELECT * FROM captures ORDER BY id DESC LIMIT ? OFFSET ?")
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&*pool)
+            .await,
+    }
+    .context("查询抓包记录失败")?;
+    Ok(rows.into_iter().map(CapturedExchange::from).collect())
+}
+
+pub async fn clear(session_id: Option<String>) -> Result<u64> {
+    let pool = metrics::pool().ok_or_else(|| anyhow!("metrics 数据库未初始化"))?;
+    let result = match non_empty(session_id) {
+        Some(id) => sqlx::query("DELETE FROM captures WHERE session_id = ?").bind(id).execute(&*pool).await,
+        None => sqlx::query("DELETE FROM captures").execute(&*pool).await,
+    }
+    .context("删除抓包记录失败")?;
+    Ok(result.rows_affected())
+}
+
+fn har_headers(headers: &[(String, String)]) -> Value {
+    Value::Array(
+        headers
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect(),
+    )
+}
+
+fn header_value<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn har_entry(e: &CapturedExchange) -> Value {
+    let query: Vec<Value> = e
+        .url
+        .split_once('?')
+        .map(|(_, q)| q)
+        .unwrap_or("")
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (name, value) = kv.split_once('=').unwrap_or((kv, ""));
+            json!({ "name": name, "value": value })
+        })
+        .collect();
+
+    let started = chrono::DateTime::from_timestamp_millis(e.timestamp_ms - e.latency_ms as i64)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+
+    let mut request = json!({
+        "method": e.method,
+        "url": e.url,
+        "httpVersion": e.http_version,
+        "cookies": [],
+        "headers": har_headers(&e.request_headers),
+        "queryString": query,
+        "headersSize": -1,
+        "bodySize": e.request_body_size,
+    });
+    if e.request_body_size > 0 {
+        request["postData"] = json!({
+            "mimeType": header_value(&e.request_headers, "content-type").unwrap_or(""),
+            "text": e.request_body,
+        });
+    }
+
+    let mut content = json!({
+        "size": e.response_body_size,
+        "mimeType": header_value(&e.response_headers, "content-type").unwrap_or(""),
+        "text": e.response_body,
+    });
+    if let Some(enc) = e.response_body_encoding.as_deref() {
+        content["encoding"] = json!(enc);
+    }
+
+    let status = StatusCode::from_u16(e.status_code as u16).ok();
+    json!({
+        "startedDateTime": started,
+        "time": e.latency_ms,
+        "request": request,
+        "response": {
+            "status": e.status_code,
+            "statusText": status.and_then(|s| s.canonical_reason()).unwrap_or(""),
+            "httpVersion": e.http_version,
+            "cookies": [],
+            "headers": har_headers(&e.response_headers),
+            "content": content,
+            "redirectURL": header_value(&e.response_headers, "location").unwrap_or(""),
+            "headersSize": -1,
+            "bodySize": e.response_body_size,
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": e.latency_ms, "receive": 0 },
+        "comment": format!("route={} upstream={} client={}", e.route_id, e.upstream, e.client_ip),
+    })
+}
+
+/// 导出为 HAR 1.2
+pub fn to_har(entries: &[CapturedExchange]) -> Value {
+    json!({
+        "log": {
+            "version": "1.2",
+            "creator": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+            "entries": entries.iter().map(har_entry).collect::<Vec<_>>(),
+        }
+    })
+}
+
+pub async fn export_har(session_id: Option<String>) -> Result<String> {
+    let mut entries = query(session_id, i64::MAX, 0).await?;
+    entries.sort_by_key(|e| e.id);
+    Ok(serde_json::to_string_pretty(&to_har(&entries))?)
+}
@@ -0,0 +1,72 @@
+// 请求抓包（capture）模块的单元测试
+
+#[cfg(test)]
+mod capture_tests {
+    use crate::capture;
+
+    #[test]
+    fn test_capture_filters_and_har() {
+        let bad = capture::StartCaptureRequest {
+            route_id: None,
+            client_ip: None,
+            path_prefix: None,
+            status: Some("6xx".into()),
+            ttl_secs: None,
+            max_count: None,
+            max_body_kb: None,
+        };
+        assert!(capture::start(bad.clone()).is_err());
+
+        let info = capture::start(capture::StartCaptureRequest {
+            route_id: Some("cap-route".into()),
+            client_ip: Some("10.0.0.9".into()),
+            path_prefix: Some("/api/".into()),
+            status: Some("404, 5xx".into()),
+            max_count: Some(1),
+            ..bad
+        })
+        .unwrap();
+
+        assert!(capture::matching("other", "10.0.0.9", "/api/x").is_none());
+        assert!(capture::matching("cap-route", "10.0.0.1", "/api/x").is_none());
+        assert!(capture::matching("cap-route", "10.0.0.9", "/static/x").is_none());
+        let session = capture::matching("cap-route", "10.0.0.9", "/api/x").expect("session matched");
+        assert!(session.accepts_status(404) && session.accepts_status(503));
+        assert!(!session.accepts_status(200));
+        assert!(capture::stop(&info.id));
+        assert!(capture::matching("cap-route", "10.0.0.9", "/api/x").is_none());
+
+        let entry = capture::CapturedExchange {
+            id: 1,
+            session_id: info.id,
+            timestamp_ms: 1_700_000_000_500,
+            route_id: "cap-route".into(),
+            client_ip: "10.0.0.9".into(),
+            method: "POST".into(),
+            url: "https://example.com/api/x?a=1&b".into(),
+            http_version: "HTTP/1.1".into(),
+            status_code: 502,
+            upstream: "http://127.0.0.1:9000/api/x".into(),
+            latency_ms: 500.0,
+            request_headers: vec![
+                ("content-type".into(), "application/json".into()),
+                ("authorization".into(), "[REDACTED]".into()),
+            ],
+            request_body: "{}".into(),
+            request_body_encoding: None,
+            request_body_size: 2,
+            response_headers: vec![],
+            response_body: "AAE=".into(),
+            response_body_encoding: Some("base64".into()),
+            response_body_size: 2,
+        };
+        let har = capture::to_har(&[entry]);
+        let e = &har["log"]["entries"][0];
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(e["startedDateTime"], "2023-11-14T22:13:20.000Z");
+        assert_eq!(e["request"]["queryString"][1]["name"], "b");
+        assert_eq!(e["request"]["postData"]["mimeType"], "application/json");
+        assert_eq!(e["response"]["statusText"], "Bad Gateway");
+        assert_eq!(e["response"]["content"]["encoding"], "base64");
+    }
+}
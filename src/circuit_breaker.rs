@@ -0,0 +1,231 @@
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tauri::Emitter;
+
+use crate::{config, proxy};
+
+/// 按上游地址共享的熔断器：指向同一上游的所有路由共用一个状态
+static BREAKERS: Lazy<DashMap<String, Arc<Breaker>>> = Lazy::new(DashMap::new);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateChangePayload {
+    pub upstream: String,
+    pub state: State,
+    pub reason: String,
+}
+
+#[inline]
+fn now_ts() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+struct Inner {
+    state: State,
+    /// 每秒一个桶：(秒, 总数, 失败数)
+    window: VecDeque<(i64, u32, u32)>,
+    consecutive_failures: u32,
+    opened_at: i64,
+    probes_inflight: u32,
+}
+
+struct Breaker {
+    upstream: String,
+    inner: Mutex<Inner>,
+}
+
+/// 放行凭证：请求结束后通过 `success` / `failure` 上报结果；未上报即丢弃时只释放半开探测名额
+pub struct Permit {
+    breaker: Arc<Breaker>,
+    probe: bool,
+    reported: bool,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.probe && !self.reported {
+            let mut inner = self.breaker.inner.lock();
+            inner.probes_inflight = inner.probes_inflight.saturating_sub(1);
+        }
+    }
+}
+
+fn emit_change(app: &tauri::AppHandle, upstream: &str, state: State, reason: String) {
+    let label = match state {
+        State::Closed => "关闭（恢复）",
+        State::Open => "打开（熔断）",
+        State::HalfOpen => "半开（探测）",
+    };
+    proxy::send_log_with_app(app, format!("熔断器状态变更: {upstream} -> {label}，原因: {reason}"));
+    let _ = app.emit(
+        "circuit-breaker-state",
+        StateChangePayload {
+            upstream: upstream.to_string(),
+            state,
+            reason,
+        },
+    );
+}
+
+/// 状态变更：(上游, 新状态, 原因)
+pub(crate) type Change = (String, State, String);
+
+/// 申请放行；熔断打开时返回 None，调用方应直接返回 503
+pub fn acquire(app: &tauri::AppHandle, upstream: &str, cfg: &config::CircuitBreakerConfig) -> Option<Permit> {
+    let (permit, change) = try_acquire(upstream, cfg);
+    if let Some((up, state, reason)) = change {
+        emit_change(app, &up, state, reason);
+    }
+    permit
+}
+
+pub(crate) fn try_acquire(upstream: &str, cfg: &config::CircuitBreakerConfig) -> (Option<Permit>, Option<Change>) {
+    let breaker = BREAKERS
+        .entry(upstream.to_string())
+        .or_insert_with(|| {
+            Arc::new(Breaker {
+                upstream: upstream.to_string(),
+                inner: Mutex::new(Inner {
+                    state: State::Closed,
+                    window: VecDeque::new(),
+                    consecutive_failures: 0,
+                    opened_at: 0,
+                    probes_inflight: 0,
+                }),
+            })
+        })
+        .clone();
+
+    let mut inner = breaker.inner.lock();
+    let mut change = None;
+    let probe = match inner.state {
+        State::Closed => false,
+        State::Open => {
+            if now_ts() - inner.opened_at < cfg.open_secs as i64 {
+                return (None, None);
+            }
+            inner.state = State::HalfOpen;
+            inner.probes_inflight = 0;
+            change = Some((upstream.to_string(), State::HalfOpen, format!("熔断已持续 {} 秒", cfg.open_secs)));
+            true
+        }
+        State::HalfOpen => true,
+    };
+    if probe {
+        if inner.probes_inflight >= cfg.half_open_probes.max(1) {
+            return (None, change);
+        }
+        inner.probes_inflight += 1;
+    }
+    drop(inner);
+
+    let permit = Permit {
+        breaker,
+        probe,
+        reported: false,
+    };
+    (Some(permit), change)
+}
+
+impl Permit {
+    pub fn success(self, app: &tauri::AppHandle) {
+        if let Some((up, state, reason)) = self.finish(None) {
+            emit_change(app, &up, state, reason);
+        }
+    }
+
+    pub fn failure(self, app: &tauri::AppHandle, cfg: &config::CircuitBreakerConfig, reason: &str) {
+        if let Some((up, state, reason)) = self.finish(Some((cfg, reason))) {
+            emit_change(app, &up, state, reason);
+        }
+    }
+
+    /// 上报结果；failure 为 None 表示成功
+    pub(crate) fn finish(mut self, failure: Option<(&config::CircuitBreakerConfig, &str)>) -> Option<Change> {
+        self.reported = true;
+        let upstream = self.breaker.upstream.clone();
+        let mut inner = self.breaker.inner.lock();
+
+        let Some((cfg, reason)) = failure else {
+            inner.consecutive_failures = 0;
+            if self.probe {
+                inner.probes_inflight = inner.probes_inflight.saturating_sub(1);
+                if inner.state == State::HalfOpen {
+                    inner.state = State::Closed;
+                    inner.window.clear();
+                    return Some((upstream, State::Closed, "探测请求成功".to_string()));
+                }
+            }
+            record(&mut inner, false);
+            return None;
+        };
+
+        inner.consecutive_failures += 1;
+        if self.probe {
+            inner.probes_inflight = inner.probes_inflight.saturating_sub(1);
+            if inner.state == State::HalfOpen {
+                inner.state = State::Open;
+                inner.opened_at = now_ts();
+                return Some((upstream, State::Open, format!("探测请求失败: {reason}")));
+            }
+        }
+        if inner.state != State::Closed {
+            return None;
+        }
+
+        record(&mut inner, true);
+        let cutoff = now_ts() - cfg.window_secs.max(1) as i64;
+        while inner.window.front().is_some_and(|(ts, _, _)| *ts <= cutoff) {
+            inner.window.pop_front();
+        }
+        let (total, failed) = inner
+            .window
+            .iter()
+            .fold((0u32, 0u32), |(t, f), (_, bt, bf)| (t + bt, f + bf));
+
+        let trip = if cfg.consecutive_failures > 0 && inner.consecutive_failures >= cfg.consecutive_failures {
+            Some(format!("连续失败 {} 次，最近一次: {reason}", inner.consecutive_failures))
+        } else if total >= cfg.min_requests.max(1)
+            && failed as f64 * 100.0 >= cfg.error_rate_percent * total as f64
+        {
+            Some(format!(
+                "{} 秒内错误率 {:.1}%（{failed}/{total}），最近一次: {reason}",
+                cfg.window_secs,
+                failed as f64 * 100.0 / total as f64
+            ))
+        } else {
+            None
+        };
+
+        let why = trip?;
+        inner.state = State::Open;
+        inner.opened_at = now_ts();
+        Some((upstream, State::Open, why))
+    }
+}
+
+fn record(inner: &mut Inner, failed: bool) {
+    let now = now_ts();
+    match inner.window.back_mut() {
+        Some((ts, total, fails)) if *ts == now => {
+            *total += 1;
+            *fails += failed as u32;
+        }
+        _ => inner.window.push_back((now, 1, failed as u32)),
+    }
+    // 窗口只在失败时按配置精确裁剪，这里限制上限避免长期只有成功请求时无限增长
+    if inner.window.len() > 3600 {
+        inner.window.pop_front();
+    }
+}
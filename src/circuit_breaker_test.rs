@@ -0,0 +1,37 @@
+// 熔断器（circuit_breaker）模块的单元测试
+
+#[cfg(test)]
+mod circuit_breaker_tests {
+    use crate::circuit_breaker::{try_acquire, State};
+    use crate::config;
+
+    #[test]
+    fn test_circuit_breaker_transitions() {
+        let cfg: config::CircuitBreakerConfig = toml::from_str(
+            r#"consecutive_failures = 3
+               min_requests = 100
+               open_secs = 0"#,
+        )
+        .unwrap();
+        let up = "http://cb-test-upstream";
+
+        // 连续失败达到阈值后打开
+        for i in 0..3 {
+            let (p, _) = try_acquire(up, &cfg);
+            let change = p.expect("closed breaker admits").finish(Some((&cfg, "timeout")));
+            assert_eq!(change.map(|c| c.1), (i == 2).then_some(State::Open), "failure #{i}");
+        }
+
+        // open_secs 已过：首个请求作为探测放行，其余请求在探测结束前被拒绝
+        let (probe, change) = try_acquire(up, &cfg);
+        assert_eq!(change.map(|c| c.1), Some(State::HalfOpen));
+        let probe = probe.expect("probe admitted");
+        assert!(try_acquire(up, &cfg).0.is_none());
+
+        // 探测失败重新打开，再次探测成功后关闭
+        assert_eq!(probe.finish(Some((&cfg, "502"))).map(|c| c.1), Some(State::Open));
+        let (probe, _) = try_acquire(up, &cfg);
+        assert_eq!(probe.unwrap().finish(None).map(|c| c.1), Some(State::Closed));
+        assert!(try_acquire(up, &cfg).0.is_some());
+    }
+}
@@ -0,0 +1,121 @@
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderName, StatusCode};
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::{cache, config, vars};
+
+pub const DEFAULT_KEY_TEMPLATE: &str = "$request_method $scheme://$host$request_uri";
+
+/// 首个请求拿到的上游原始响应，分发给所有等待者
+pub struct SharedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+    pub upstream_addr: String,
+}
+
+/// 单个路由的请求合并器：同一 key 同时只有一个请求回源
+pub struct Coalescer {
+    key: Option<String>,
+    // key 模板引用的变量名，用于判断 Cookie / 身份请求头是否参与了分组
+    key_vars: Vec<String>,
+    timeout: Duration,
+    inflight: Arc<DashMap<String, broadcast::Sender<Arc<SharedResponse>>>>,
+}
+
+pub enum Role {
+    /// 负责回源；完成后调用 `complete` 分发响应，中途放弃（drop）时等待者各自回源
+    Leader(LeaderGuard),
+    Follower(broadcast::Receiver<Arc<SharedResponse>>),
+}
+
+pub struct LeaderGuard {
+    key: String,
+    done: bool,
+    inflight: Arc<DashMap<String, broadcast::Sender<Arc<SharedResponse>>>>,
+}
+
+impl LeaderGuard {
+    /// 分发响应；带 Set-Cookie 或 Cache-Control: private / no-store 的响应只属于首个请求，
+    /// 不分发给等待者（等同放弃），返回是否已分发
+    pub fn complete(mut self, resp: SharedResponse) -> bool {
+        if cache::is_private(&resp.headers) {
+            return false;
+        }
+        // 在分片锁内移除后再发送：订阅都发生在移除之前，不会漏收
+        self.done = true;
+        if let Some((_, tx)) = self.inflight.remove(&self.key) {
+            let _ = tx.send(Arc::new(resp));
+        }
+        true
+    }
+}
+
+impl Drop for LeaderGuard {
+    fn drop(&mut self) {
+        // 未 complete 时移除发送端，等待者收到 Closed 后各自回源
+        if !self.done {
+            self.inflight.remove(&self.key);
+        }
+    }
+}
+
+impl Coalescer {
+    pub fn new(cfg: &config::CoalesceConfig) -> Self {
+        let key = cfg
+            .key
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        Self {
+            key_vars: vars::names(key.as_deref().unwrap_or(DEFAULT_KEY_TEMPLATE)),
+            key,
+            timeout: Duration::from_millis(cfg.timeout_ms),
+            inflight: Arc::new(DashMap::new()),
+        }
+    }
+
+    pub fn key_template(&self) -> &str {
+        self.key.as_deref().unwrap_or(DEFAULT_KEY_TEMPLATE)
+    }
+
+    /// 请求携带 Cookie 或认证模块注入的身份请求头时，上游响应可能因用户而异：
+    /// 只有 key 模板引用了这些请求头（`$cookie_*` / `$http_*`）时才允许合并
+    pub fn shareable<'a>(&self, headers: &HeaderMap, identity: impl IntoIterator<Item = &'a HeaderName>) -> bool {
+        let referenced = |var: &str| self.key_vars.iter().any(|v| v == var);
+        if headers.contains_key(header::COOKIE)
+            && !referenced("http_cookie")
+            && !self.key_vars.iter().any(|v| v.starts_with("cookie_"))
+        {
+            return false;
+        }
+        identity.into_iter().all(|name| {
+            !headers.contains_key(name) || referenced(&format!("http_{}", name.as_str().replace('-', "_")))
+        })
+    }
+
+    pub fn join(&self, key: String) -> Role {
+        match self.inflight.entry(key) {
+            dashmap::mapref::entry::Entry::Occupied(e) => Role::Follower(e.get().subscribe()),
+            dashmap::mapref::entry::Entry::Vacant(e) => {
+                let key = e.key().clone();
+                let (tx, _) = broadcast::channel(1);
+                e.insert(tx);
+                Role::Leader(LeaderGuard {
+                    key,
+                    done: false,
+                    inflight: self.inflight.clone(),
+                })
+            }
+        }
+    }
+
+    /// 等待首个请求的响应；超时或首个请求失败时返回 None，由调用方自行回源
+    pub async fn wait(&self, mut rx: broadcast::Receiver<Arc<SharedResponse>>) -> Option<Arc<SharedResponse>> {
+        tokio::time::timeout(self.timeout, rx.recv()).await.ok()?.ok()
+    }
+}
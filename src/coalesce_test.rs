@@ -0,0 +1,91 @@
+// 请求合并（coalesce）模块的单元测试
+
+#[cfg(test)]
+mod coalesce_tests {
+    use crate::coalesce::{Coalescer, Role, SharedResponse};
+    use crate::config;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+
+    fn coalescer(toml_src: &str) -> Coalescer {
+        let cfg: config::CoalesceConfig = toml::from_str(toml_src).expect("invalid coalesce toml");
+        Coalescer::new(&cfg)
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_fan_out() {
+        let c = coalescer("timeout_ms = 200");
+
+        let Role::Leader(leader) = c.join("k".to_string()) else { panic!("first request leads") };
+        let Role::Follower(rx) = c.join("k".to_string()) else { panic!("second request waits") };
+        leader.complete(SharedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"shared"),
+            upstream_addr: "u".to_string(),
+        });
+        let shared = c.wait(rx).await.expect("follower receives leader response");
+        assert_eq!(&shared.body[..], b"shared");
+
+        // 首个请求放弃时，等待者立即得到 None 并自行回源；之后的请求重新成为 leader
+        let Role::Leader(leader) = c.join("k".to_string()) else { panic!("key released") };
+        let Role::Follower(rx) = c.join("k".to_string()) else { panic!("second request waits") };
+        drop(leader);
+        assert!(c.wait(rx).await.is_none());
+        assert!(matches!(c.join("k".to_string()), Role::Leader(_)));
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_private_response_not_shared() {
+        let c = coalescer("timeout_ms = 200");
+        let shared = |name: &'static str, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            SharedResponse {
+                status: StatusCode::OK,
+                headers,
+                body: Bytes::from_static(b"alice's page"),
+                upstream_addr: "u".to_string(),
+            }
+        };
+
+        // 带 Set-Cookie 或 Cache-Control: private / no-store 的响应不分发，等待者各自回源
+        for (name, value) in [("set-cookie", "sid=alice"), ("cache-control", "private"), ("cache-control", "no-store")] {
+            let Role::Leader(leader) = c.join("k".to_string()) else { panic!("first request leads") };
+            let Role::Follower(rx) = c.join("k".to_string()) else { panic!("second request waits") };
+            assert!(!leader.complete(shared(name, value)), "{name}: {value}");
+            assert!(c.wait(rx).await.is_none(), "{name}: {value}");
+        }
+
+        let Role::Leader(leader) = c.join("k".to_string()) else { panic!("key released") };
+        let Role::Follower(rx) = c.join("k".to_string()) else { panic!("second request waits") };
+        assert!(leader.complete(shared("cache-control", "public, max-age=60")));
+        assert!(c.wait(rx).await.is_some());
+    }
+
+    #[test]
+    fn test_coalesce_identity_requests_not_shareable() {
+        let user = HeaderName::from_static("x-auth-request-user");
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut h = HeaderMap::new();
+            for (k, v) in pairs {
+                h.insert(*k, HeaderValue::from_static(v));
+            }
+            h
+        };
+
+        let c = coalescer("");
+        assert!(c.shareable(&headers(&[("accept", "text/html")]), [&user]));
+        assert!(!c.shareable(&headers(&[("cookie", "sid=1")]), [&user]));
+        assert!(!c.shareable(&headers(&[("x-auth-request-user", "alice")]), [&user]));
+        // 未被认证模块写入的同名头不影响
+        assert!(c.shareable(&headers(&[("x-auth-request-user", "alice")]), []));
+
+        // key 模板按 Cookie / 身份请求头分组时允许合并
+        let c = coalescer(r#"key = "$request_uri $cookie_sid ${http_x_auth_request_user}""#);
+        assert!(c.shareable(&headers(&[("cookie", "sid=1"), ("x-auth-request-user", "alice")]), [&user]));
+        let c = coalescer(r#"key = "$request_uri $http_cookie""#);
+        assert!(c.shareable(&headers(&[("cookie", "sid=1")]), [&user]));
+        assert!(!c.shareable(&headers(&[("cookie", "sid=1"), ("x-auth-request-user", "alice")]), [&user]));
+    }
+}
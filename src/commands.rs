@@ -1,7 +1,13 @@
+use crate::api_keys;
+use crate::cache;
+use crate::capture;
 use crate::config;
 use crate::i18n;
 use crate::metrics;
 use crate::proxy;
+use crate::replay;
+use crate::route_table;
+use crate::tester;
 use crate::tray;
 use crate::update;
 use anyhow::Result;
@@ -20,6 +26,9 @@ pub async fn save_config(
     app: tauri::AppHandle,
     mut cfg: config::Config,
 ) -> Result<config::Config, String> {
+    // 0. 校验路由正则等配置，无效时直接拒绝保存
+    route_table::validate_config(&cfg).map_err(|e| e.to_string())?;
+
     let was_running = proxy::is_effectively_running();
 
     // 1. 如果正在运行，先停止服务
@@ -184,6 +193,26 @@ pub async fn refresh_blacklist_cache() -> Result<(), String> {
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn create_api_key(req: api_keys::ApiKeyRequest) -> Result<api_keys::CreatedApiKey, String> {
+    api_keys::create(req).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn update_api_key(req: api_keys::ApiKeyRequest) -> Result<api_keys::ApiKeyInfo, String> {
+    api_keys::update(req).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_api_key(name: String) -> Result<(), String> {
+    api_keys::delete(name).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_api_keys() -> Result<Vec<api_keys::ApiKeyInfo>, String> {
+    api_keys::list().await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn get_metrics_db_status() -> Result<metrics::MetricsDBStatus, String> {
     Ok(metrics::get_metrics_db_status())
@@ -203,6 +232,102 @@ pub async fn test_metrics_db_connection(db_path: String) -> Result<(bool, String
         .map_err(|e| e.to_string())
 }
 
+/// 清除响应缓存；route_id / key_prefix 为空时不做对应过滤，返回清除的条目数
+#[tauri::command]
+pub async fn purge_cache(route_id: Option<String>, key_prefix: Option<String>) -> Result<usize, String> {
+    let route_id = route_id.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let key_prefix = key_prefix.as_deref().filter(|s| !s.is_empty());
+    Ok(cache::purge(route_id, key_prefix).await)
+}
+
+/// 开始抓包，返回会话信息
+#[tauri::command]
+pub fn start_capture(req: capture::StartCaptureRequest) -> Result<capture::CaptureSessionInfo, String> {
+    capture::start(req).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn stop_capture(id: String) -> Result<bool, String> {
+    Ok(capture::stop(&id))
+}
+
+#[tauri::command]
+pub fn get_capture_sessions() -> Result<Vec<capture::CaptureSessionInfo>, String> {
+    Ok(capture::sessions())
+}
+
+/// session_id 为空时按时间倒序返回所有会话的记录
+#[tauri::command]
+pub async fn query_captures(
+    session_id: Option<String>,
+    page: i64,
+    page_size: i64,
+) -> Result<Vec<capture::CapturedExchange>, String> {
+    let page_size = page_size.clamp(1, 500);
+    capture::query(session_id, page_size, (page.max(1) - 1) * page_size)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_captures(session_id: Option<String>) -> Result<u64, String> {
+    capture::clear(session_id).await.map_err(|e| e.to_string())
+}
+
+/// 导出 HAR 1.2 JSON 文本
+#[tauri::command]
+pub async fn export_capture_har(session_id: Option<String>) -> Result<String, String> {
+    capture::export_har(session_id).await.map_err(|e| e.to_string())
+}
+
+fn rule_for_listen_addr<'a>(cfg: &'a config::Config, addr: &str) -> Result<&'a config::ListenRule, String> {
+    cfg.rules
+        .iter()
+        .find(|r| r.listen_addr.trim() == addr || r.listen_addrs.iter().any(|a| a.trim() == addr))
+        .ok_or_else(|| format!("未找到监听地址 {addr} 对应的规则"))
+}
+
+/// 路由匹配诊断：列出监听规则下每条路由的匹配结果，以及命中路由的上游地址与请求头
+#[tauri::command]
+pub fn explain_route(
+    listen_addr: String,
+    host: String,
+    uri: String,
+    method: String,
+    headers: Option<std::collections::HashMap<String, String>>,
+    body: Option<String>,
+    client_ip: Option<String>,
+) -> Result<proxy::RouteExplain, String> {
+    let cfg = config::get_config();
+    let addr = listen_addr.trim();
+    let rule = rule_for_listen_addr(&cfg, addr)?;
+    proxy::explain_request(
+        rule,
+        addr,
+        &host,
+        &uri,
+        &method,
+        &headers.unwrap_or_default(),
+        body.as_deref().map(str::as_bytes),
+        client_ip.as_deref(),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 按监听规则发送一次测试请求，返回完整响应与各阶段耗时
+#[tauri::command]
+pub async fn test_route_request(req: tester::TestRequest) -> Result<tester::TestResult, String> {
+    let cfg = config::get_config();
+    let rule = rule_for_listen_addr(&cfg, req.listen_addr.trim())?;
+    tester::run(rule, req).await.map_err(|e| e.to_string())
+}
+
+/// 回放抓包记录或请求日志，完成后返回对比报告；进度通过 replay-progress 事件推送
+#[tauri::command]
+pub async fn replay_traffic(app: tauri::AppHandle, req: replay::ReplayRequest) -> Result<replay::ReplayReport, String> {
+    replay::run(app, req).await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn open_cert_file_dialog(app: tauri::AppHandle) -> Result<Option<String>, String> {
     let file = app
@@ -117,6 +117,7 @@ pub struct Route {
     pub static_dir: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exclude_basic_auth: Option<bool>,
+    // 路由级 Basic Auth：basic_auth_enable = true 时使用路由自己的用户与设置，覆盖监听规则的配置
     #[serde(skip_serializing_if = "Option::is_none")]
     pub basic_auth_enable: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -125,6 +126,13 @@ pub struct Route {
     pub basic_auth_password: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub basic_auth_forward_header: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub basic_auth_users: Vec<BasicAuthUser>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic_auth_htpasswd_file: Option<String>,
+    // 401 响应 WWW-Authenticate 中的 realm，未配置时沿用监听规则的 realm
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic_auth_realm: Option<String>,
 
     #[serde(default = "default_follow_redirects")]
     pub follow_redirects: bool,
@@ -148,15 +156,481 @@ pub struct Route {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub remove_headers: Option<Vec<String>>,
 
+    // 响应头修改（值支持 $upstream_addr / $request_time / $route_id 等变量）
+    // 执行顺序：remove -> set（覆盖）-> add（追加）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add_response_headers: Option<std::collections::HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_response_headers: Option<std::collections::HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_response_headers: Option<Vec<String>>,
+
+    // CORS 策略：预检请求由代理直接应答，不转发上游
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cors: Option<CorsPolicy>,
+
     // 路由匹配增强（兼容 Nginx 风格）
+    // 匹配方式：exact(=) / prefix(默认) / prefix_no_regex(^~) / regex(~) / regex_case_insensitive(~*)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_type: Option<RouteMatchType>,
+    // 显式优先级：数值越大越先参与匹配（默认 0），同优先级内按 Nginx 规则选择
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub methods: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub headers: Option<std::collections::HashMap<String, String>>,
 
+    // 路由级自定义错误页（优先于监听器级）与上游错误拦截（未设置时继承监听器配置）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_pages: Option<Vec<ErrorPage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_intercept_errors: Option<bool>,
+
+    // 响应缓存（仅 GET/HEAD）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheConfig>,
+
+    // 合并并发的相同请求（仅 GET/HEAD），只向上游发送一次
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coalesce: Option<CoalesceConfig>,
+
+    // 流量镜像：异步复制请求到其他上游，忽略其响应
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirrors: Option<Vec<MirrorTarget>>,
+
+    // 按规则分流到其他上游组（按顺序匹配，未命中时使用 upstreams）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub splits: Option<Vec<TrafficSplit>>,
+
+    // 路由级超时（毫秒），覆盖全局 upstream_connect_timeout_ms / upstream_read_timeout_ms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_ms: Option<u64>,
+    // 单次读取上游数据的超时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_timeout_ms: Option<u64>,
+    // 整个上游请求的超时，默认取全局 upstream_read_timeout_ms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_timeout_ms: Option<u64>,
+
+    // 路由级请求/响应体大小上限（字节，0 表示不限制），覆盖全局 max_body_size / max_response_body_size
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_body_size: Option<usize>,
+
+    // 熔断器（覆盖监听规则的 circuit_breaker）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    // 故障注入（测试用）：延迟、中止、连接重置、限速
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fault: Option<FaultConfig>,
+
+    // Mock 响应：命中规则文件中的规则时直接返回，未命中时继续访问静态目录 / 上游
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mock: Option<MockConfig>,
+
+    // JWT 校验：Authorization: Bearer <token>，失败返回 401
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_auth: Option<JwtAuthConfig>,
+
+    // 外部认证（类似 Nginx auth_request）：转发前先请求认证服务，2xx 放行
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_auth: Option<ForwardAuthConfig>,
+
+    // OIDC 登录网关：未登录的浏览器跳转到身份提供方，登录后以加密 Cookie 保持会话
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oidc: Option<OidcConfig>,
+
+    // API Key 认证：按请求头或查询参数中的 Key 识别调用方，按 Key 限流与限额
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<ApiKeyAuthConfig>,
+
+    // 直接返回（Nginx `return`）：配置后不再访问静态目录或上游
+    #[serde(rename = "return", skip_serializing_if = "Option::is_none")]
+    pub return_directive: Option<ReturnDirective>,
+
+    #[serde(default)]
+    pub upstreams: Vec<Upstream>,
+}
+
+fn default_cache_max_size_mb() -> u64 {
+    64
+}
+
+fn default_cache_max_entry_kb() -> u64 {
+    1024
+}
+
+fn default_cache_lock_timeout_ms() -> u64 {
+    5000
+}
+
+/// 缓存存储方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStorage {
+    #[default]
+    Memory,
+    Disk,
+}
+
+/// 路由级响应缓存配置；遵循上游的 Cache-Control / Expires / Vary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub storage: CacheStorage,
+    /// 磁盘缓存目录（storage = "disk" 时必填）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_path: Option<String>,
+    /// 缓存键模板，默认 `$scheme://$host$request_uri`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// 缓存总大小上限（MB），超出后淘汰最久未访问的条目
+    #[serde(default = "default_cache_max_size_mb")]
+    pub max_size_mb: u64,
+    /// 单个响应体大小上限（KB），超出则不缓存
+    #[serde(default = "default_cache_max_entry_kb")]
+    pub max_entry_kb: u64,
+    /// 上游未给出缓存时间时的默认 TTL（秒），0 表示不缓存
+    #[serde(default)]
+    pub default_ttl_secs: u64,
+    /// 过期后仍可先返回旧内容并在后台刷新的时长（秒）；上游的 stale-while-revalidate 指令优先
+    #[serde(default)]
+    pub stale_while_revalidate_secs: u64,
+    /// 上游出错（连接失败或 5xx）时仍可返回旧内容的时长（秒）；上游的 stale-if-error 指令优先
+    #[serde(default)]
+    pub stale_if_error_secs: u64,
+    /// 并发未命中时等待首个回源请求的最长时间（毫秒），超时后各自回源
+    #[serde(default = "default_cache_lock_timeout_ms")]
+    pub lock_timeout_ms: u64,
+}
+
+fn default_coalesce_timeout_ms() -> u64 {
+    3000
+}
+
+/// 请求合并配置：相同 key 的并发请求等待首个请求的上游响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoalesceConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 合并键模板，默认 `$request_method $scheme://$host$request_uri`；
+    /// 响应因用户而异时应加入 `$cookie_<name>` / `$http_<name>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// 等待首个请求的最长时间（毫秒），超时后各自回源
+    #[serde(default = "default_coalesce_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_mirror_percent() -> f64 {
+    100.0
+}
+
+fn default_mirror_max_body_kb() -> u64 {
+    1024
+}
+
+/// 流量镜像目标：路径与主上游一致（同样应用 URL 重写与 proxy_pass_path）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorTarget {
+    pub url: String,
+    /// 采样比例（0-100）
+    #[serde(default = "default_mirror_percent")]
+    pub percent: f64,
+    /// 请求体超过该大小（KB）时不镜像
+    #[serde(default = "default_mirror_max_body_kb")]
+    pub max_body_kb: u64,
+    /// 比对主请求与镜像请求的状态码，不一致时记录日志
+    #[serde(default)]
+    pub compare_status: bool,
+}
+
+fn default_cb_window_secs() -> u64 {
+    10
+}
+
+fn default_cb_min_requests() -> u32 {
+    20
+}
+
+fn default_cb_error_rate_percent() -> f64 {
+    50.0
+}
+
+fn default_cb_consecutive_failures() -> u32 {
+    5
+}
+
+fn default_cb_open_secs() -> u64 {
+    30
+}
+
+fn default_cb_half_open_probes() -> u32 {
+    1
+}
+
+/// 熔断器配置：按上游地址统计连接失败 / 超时 / 5xx
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 错误率统计的滚动窗口（秒）
+    #[serde(default = "default_cb_window_secs")]
+    pub window_secs: u64,
+    /// 窗口内请求数达到该值才按错误率判断
+    #[serde(default = "default_cb_min_requests")]
+    pub min_requests: u32,
+    #[serde(default = "default_cb_error_rate_percent")]
+    pub error_rate_percent: f64,
+    /// 连续失败次数达到该值立即熔断，0 表示不按连续失败判断
+    #[serde(default = "default_cb_consecutive_failures")]
+    pub consecutive_failures: u32,
+    /// 熔断持续时间（秒），之后进入半开状态放行探测请求
+    #[serde(default = "default_cb_open_secs")]
+    pub open_secs: u64,
+    /// 半开状态下同时放行的探测请求数
+    #[serde(default = "default_cb_half_open_probes")]
+    pub half_open_probes: u32,
+}
+
+/// OIDC 登录配置（授权码模式 + PKCE），ID Token 支持 RS256 / ES256
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 身份提供方地址，从 `<issuer>/.well-known/openid-configuration` 读取各端点
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// 加密会话 Cookie 的密钥，至少 16 个字符
+    pub cookie_secret: String,
+    /// 回调路径，默认 `/oauth2/callback`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_path: Option<String>,
+    /// 登出路径，默认 `/oauth2/logout`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logout_path: Option<String>,
+    /// 完整回调地址；未配置时按请求的协议与 Host 拼接 callback_path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_url: Option<String>,
+    /// 默认 `["openid", "email", "profile"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// 会话 Cookie 名，默认 `spm_oidc`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookie_name: Option<String>,
+    /// 会话有效期（秒），默认 28800
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_seconds: Option<u64>,
+    /// 允许的邮箱；`@example.com` 表示整个域名；为空时不限制
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_emails: Vec<String>,
+    /// 允许的用户组，任一匹配即可；为空时不限制
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_groups: Vec<String>,
+    /// 用户组所在的 claim，默认 `groups`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups_claim: Option<String>,
+    /// 转发给上游的身份请求头：请求头名 -> claim 名；
+    /// 默认 X-Auth-Request-User = sub、X-Auth-Request-Email = email
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_claims: Option<std::collections::HashMap<String, String>>,
+}
+
+/// 外部认证配置：2xx 放行，401 / 403 原样返回给客户端，其他状态或请求失败返回 500
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardAuthConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 认证服务地址；子请求沿用原始方法与请求头（不带请求体），并附带 X-Forwarded-* / X-Original-URI
+    pub url: String,
+    /// 超时（毫秒），默认 5000
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// 放行时从认证响应复制到上游请求的请求头，例如 `X-User`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub copy_headers: Vec<String>,
+    /// 认证结果缓存时间（秒）；需同时配置 cache_key_header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_seconds: Option<u64>,
+    /// 缓存键取自该请求头，例如 `Cookie` / `Authorization`；请求没有该头时不缓存
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_key_header: Option<String>,
+}
+
+/// API Key 认证配置：Key 本身通过命令管理并以哈希形式保存在 metrics 数据库中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyAuthConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 读取 Key 的请求头，默认 `X-Api-Key`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// 读取 Key 的查询参数名，例如 `api_key`；未配置时只读取请求头
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_param: Option<String>,
+    /// 认证通过后把 Key 名称写入该请求头转发给上游，默认 `X-Api-Key-Name`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_header: Option<String>,
+}
+
+/// JWT 校验配置：密钥来源三选一（secret / key_file / jwks_url）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtAuthConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 允许的签名算法：HS256 / RS256 / ES256；为空时配置了 secret 用 HS256，否则 RS256 + ES256
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub algorithms: Vec<String>,
+    /// HS256 共享密钥
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// 密钥文件：HS256 为密钥原文，RS256 / ES256 为 PEM 公钥
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+    /// JWKS 地址：按令牌头的 kid 选择公钥
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_url: Option<String>,
+    /// JWKS 缓存时间（秒），默认 300；遇到未知 kid 时提前刷新
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwks_cache_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// 允许的 aud，任一匹配即可；为空时不校验
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audience: Vec<String>,
+    /// 必须存在的 claim，支持 `a.b` 访问嵌套字段
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_claims: Vec<String>,
+    /// exp / nbf 允许的时钟偏差（秒），默认 60
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leeway_seconds: Option<u64>,
+    /// 校验通过后转发给上游的 claim：请求头名 -> claim 名（客户端自带的同名请求头会被移除）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_claims: Option<std::collections::HashMap<String, String>>,
+}
+
+/// Mock 配置：规则文件（TOML，`.json` 扩展名按 JSON 解析）修改后自动重新加载
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub file: String,
+}
+
+/// 故障注入配置：各 *_percent 未配置时按 100% 生效
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 只对带有该请求头（可限定取值）的请求注入故障
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<SplitMatch>,
+    /// 固定延迟（毫秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+    /// 额外随机延迟上限（毫秒），在 0..=delay_jitter_ms 间均匀分布
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_jitter_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay_percent: Option<f64>,
+    /// 直接返回的状态码（4xx/5xx），不访问上游
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abort_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abort_percent: Option<f64>,
+    /// 按比例直接断开客户端连接
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_percent: Option<f64>,
+    /// 响应体限速（KB/s）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth_kbps: Option<u64>,
+}
+
+/// 分流条件：按名称取请求头 / Cookie / 查询参数，未配置 value 时只要求存在
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitMatch {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+/// 分流规则：所有已配置的条件都满足时，请求发往该规则的 upstreams
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficSplit {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<SplitMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cookie: Option<SplitMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<SplitMatch>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cidrs: Option<Vec<String>>,
+    /// 按 hash_key 的稳定哈希分桶，命中比例（0-100）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    /// 分桶哈希键模板，默认 `$client_ip`（可用 `$cookie_<name>` 让同一用户固定落在同一侧）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash_key: Option<String>,
     pub upstreams: Vec<Upstream>,
 }
 
+/// 自定义错误页；file / html 为 HTML 模板，json 为 JSON 模板，按客户端 Accept 选择
+///
+/// 模板变量：`$status`、`$status_text`、`$request_id`、`$client_ip` 以及其他请求变量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorPage {
+    /// 适用的状态码，例如 [502, 503, 504]
+    pub status: Vec<u16>,
+    /// HTML 模板文件路径（监听器启动时读取）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 内联 HTML 模板（优先于 file）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    /// JSON 模板；未配置时 JSON 客户端得到内置的 {"status","error","request_id"}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<String>,
+}
+
+/// 路由直接返回的固定响应或重定向
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReturnDirective {
+    /// 状态码；301/302/303/307/308 时必须配置 location
+    pub status: u16,
+    /// 重定向目标，支持变量，例如 `https://$host_name$request_uri`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// 响应体，支持变量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// 响应体的 Content-Type（默认 text/plain; charset=utf-8）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+/// 路由 path 的匹配方式，对应 Nginx location 修饰符
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteMatchType {
+    #[serde(alias = "=")]
+    Exact,
+    #[default]
+    Prefix,
+    #[serde(alias = "^~")]
+    PrefixNoRegex,
+    #[serde(alias = "~")]
+    Regex,
+    #[serde(alias = "~*")]
+    RegexCaseInsensitive,
+}
+
 /// URL 重写规则
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UrlRewriteRule {
@@ -169,6 +643,32 @@ pub struct UrlRewriteRule {
     pub enabled: bool,
 }
 
+/// 路由级 CORS 策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsPolicy {
+    /// 是否启用
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 允许的 Origin：`*`、精确值、通配（`https://*.example.com`）或 `~` 前缀的正则
+    #[serde(default)]
+    pub allow_origins: Vec<String>,
+    /// 允许的方法；为空时使用常用方法集合
+    #[serde(default)]
+    pub allow_methods: Vec<String>,
+    /// 允许的请求头；为空时回显预检请求的 Access-Control-Request-Headers
+    #[serde(default)]
+    pub allow_headers: Vec<String>,
+    /// 允许前端读取的响应头
+    #[serde(default)]
+    pub expose_headers: Vec<String>,
+    /// 是否允许携带凭证（Cookie / Authorization）
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// 预检结果缓存秒数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<u64>,
+}
+
 /// 请求/响应体替换规则
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BodyReplaceRule {
@@ -187,6 +687,14 @@ pub struct BodyReplaceRule {
     pub content_types: Option<String>,
 }
 
+/// Basic Auth 用户：password 可以是明文，也可以是 htpasswd 兼容的哈希
+/// （bcrypt `$2y$`、argon2 `$argon2id$`、SHA-crypt `$5$`/`$6$`、`$apr1$`、`{SHA}`）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BasicAuthUser {
+    pub username: String,
+    pub password: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListenRule {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -207,6 +715,15 @@ pub struct ListenRule {
     pub basic_auth_username: String,
     pub basic_auth_password: String,
     pub basic_auth_forward_header: bool,
+    // 多用户 Basic Auth：与 basic_auth_username/password 同时生效
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub basic_auth_users: Vec<BasicAuthUser>,
+    // 外部 htpasswd 文件，修改后自动重新加载
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic_auth_htpasswd_file: Option<String>,
+    // 401 响应 WWW-Authenticate 中的 realm，默认 SSLProxyManager
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic_auth_realm: Option<String>,
     pub routes: Vec<Route>,
 
     // 速率限制配置（可选，每个规则独立配置）
@@ -220,6 +737,30 @@ pub struct ListenRule {
     pub rate_limit_window_seconds: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rate_limit_ban_seconds: Option<u64>,
+
+    // 安全响应头预设（HSTS 仅在 TLS 监听上添加、X-Frame-Options、X-Content-Type-Options、Referrer-Policy）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_headers_enabled: Option<bool>,
+
+    // HTTP -> HTTPS 跳转伴随监听（仅 ssl_enable 时生效），例如 ":80"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_redirect_listen_addr: Option<String>,
+    // 跳转状态码：301（默认）/ 302 / 307 / 308
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_redirect_status: Option<u16>,
+    // ACME HTTP-01 验证目录：跳转端口上的 /.well-known/acme-challenge/<token> 从该目录读取
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acme_challenge_dir: Option<String>,
+
+    // 自定义错误页（按状态码）；proxy_intercept_errors 开启时上游 4xx/5xx 也使用错误页
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_pages: Option<Vec<ErrorPage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_intercept_errors: Option<bool>,
+
+    // 熔断器：按上游地址统计失败，熔断期间直接返回 503（路由可单独覆盖）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
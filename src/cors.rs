@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use regex::Regex;
+
+use crate::config;
+
+const DEFAULT_ALLOW_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, HEAD, OPTIONS";
+
+/// Origin 匹配方式
+enum OriginMatcher {
+    /// 精确匹配（不区分大小写）
+    Exact(String),
+    /// `https://*.example.com` 通配或 `~` 前缀的正则
+    Pattern(Regex),
+}
+
+/// 预编译的路由级 CORS 策略
+pub struct CompiledCors {
+    any_origin: bool,
+    origins: Vec<OriginMatcher>,
+    allow_methods: HeaderValue,
+    /// None 表示回显请求中的 Access-Control-Request-Headers
+    allow_headers: Option<HeaderValue>,
+    expose_headers: Option<HeaderValue>,
+    allow_credentials: bool,
+    max_age: Option<HeaderValue>,
+}
+
+fn join_header_value(items: &[String], label: &str, field: &str) -> Result<Option<HeaderValue>> {
+    let joined = items
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if joined.is_empty() {
+        return Ok(None);
+    }
+    HeaderValue::from_str(&joined)
+        .map(Some)
+        .map_err(|_| anyhow!("路由 {label} 的 cors.{field} 包含非法字符"))
+}
+
+impl CompiledCors {
+    pub fn compile(policy: &config::CorsPolicy, label: &str) -> Result<Self> {
+        let mut any_origin = false;
+        let mut origins = Vec::new();
+        for (i, raw) in policy.allow_origins.iter().enumerate() {
+            let origin = raw.trim();
+            if origin.is_empty() {
+                continue;
+            }
+            if origin == "*" {
+                any_origin = true;
+            } else if let Some(pattern) = origin.strip_prefix('~') {
+                let re = Regex::new(pattern.trim())
+                    .map_err(|e| anyhow!("路由 {label} 的 cors.allow_origins[{i}] 正则无效: {e}"))?;
+                origins.push(OriginMatcher::Pattern(re));
+            } else if origin.contains('*') {
+                // 通配符只匹配域名字符，避免 `https://*.example.com` 意外放行其他站点
+                let pattern = format!(
+                    "(?i)^{}$",
+                    regex::escape(origin).replace(r"\*", "[A-Za-z0-9.-]*")
+                );
+                let re = Regex::new(&pattern)
+                    .map_err(|e| anyhow!("路由 {label} 的 cors.allow_origins[{i}] 通配模式无效: {e}"))?;
+                origins.push(OriginMatcher::Pattern(re));
+            } else {
+                origins.push(OriginMatcher::Exact(origin.trim_end_matches('/').to_string()));
+            }
+        }
+
+        let allow_methods = join_header_value(&policy.allow_methods, label, "allow_methods")?
+            .unwrap_or_else(|| HeaderValue::from_static(DEFAULT_ALLOW_METHODS));
+
+        Ok(Self {
+            any_origin,
+            origins,
+            allow_methods,
+            allow_headers: join_header_value(&policy.allow_headers, label, "allow_headers")?,
+            expose_headers: join_header_value(&policy.expose_headers, label, "expose_headers")?,
+            allow_credentials: policy.allow_credentials,
+            max_age: policy.max_age.map(HeaderValue::from),
+        })
+    }
+
+    fn origin_allowed(&self, origin: &str) -> bool {
+        if self.any_origin {
+            return true;
+        }
+        self.origins.iter().any(|m| match m {
+            OriginMatcher::Exact(v) => v.eq_ignore_ascii_case(origin),
+            OriginMatcher::Pattern(re) => re.is_match(origin),
+        })
+    }
+
+    /// 返回应写入 Access-Control-Allow-Origin 的值；Origin 不被允许时返回 None
+    fn allow_origin_value(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        let s = origin.to_str().ok()?;
+        if !self.origin_allowed(s) {
+            return None;
+        }
+        // 携带凭证时浏览器不接受 `*`，必须回显具体 Origin
+        if self.any_origin && !self.allow_credentials {
+            Some(HeaderValue::from_static("*"))
+        } else {
+            Some(origin.clone())
+        }
+    }
+
+    fn set_common(&self, allow_origin: HeaderValue, headers: &mut HeaderMap) {
+        let echoes_origin = allow_origin != "*";
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if self.allow_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if echoes_origin {
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+
+    /// 直接应答预检请求（不转发上游）；Origin 不在白名单时返回 403
+    pub fn preflight_response(&self, req_headers: &HeaderMap) -> Response {
+        let allow_origin = req_headers
+            .get(header::ORIGIN)
+            .and_then(|o| self.allow_origin_value(o));
+
+        let Some(allow_origin) = allow_origin else {
+            let mut resp = Response::new(Body::from("CORS origin not allowed"));
+            *resp.status_mut() = StatusCode::FORBIDDEN;
+            return resp;
+        };
+
+        let mut resp = Response::new(Body::empty());
+        *resp.status_mut() = StatusCode::NO_CONTENT;
+        let headers = resp.headers_mut();
+        self.set_common(allow_origin, headers);
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, self.allow_methods.clone());
+
+        let allow_headers = self.allow_headers.clone().or_else(|| {
+            req_headers
+                .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+                .cloned()
+        });
+        if let Some(v) = allow_headers {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, v);
+        }
+        if self.allow_headers.is_none() {
+            headers.append(
+                header::VARY,
+                HeaderValue::from_static("Access-Control-Request-Headers"),
+            );
+        }
+        if let Some(v) = self.max_age.as_ref() {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, v.clone());
+        }
+        resp
+    }
+
+    /// 为普通（非预检）跨域请求的响应补充 CORS 头
+    pub fn apply(&self, origin: Option<&HeaderValue>, headers: &mut HeaderMap) {
+        let Some(allow_origin) = origin.and_then(|o| self.allow_origin_value(o)) else {
+            return;
+        };
+        self.set_common(allow_origin, headers);
+        if let Some(v) = self.expose_headers.as_ref() {
+            headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, v.clone());
+        }
+    }
+}
+
+/// 判断是否为 CORS 预检请求；是则返回其声明的实际方法
+pub fn preflight_method(method: &Method, headers: &HeaderMap) -> Option<Method> {
+    if method != Method::OPTIONS || !headers.contains_key(header::ORIGIN) {
+        return None;
+    }
+    headers
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|v| Method::from_bytes(v.as_bytes()).ok())
+}
+
+/// 监听器级安全响应头预设；仅在响应中尚未设置时补充，路由/上游的显式配置优先
+pub fn apply_security_headers(headers: &mut HeaderMap, is_tls: bool) {
+    if is_tls && !headers.contains_key(header::STRICT_TRANSPORT_SECURITY) {
+        headers.insert(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_static("max-age=31536000; includeSubDomains"),
+        );
+    }
+    if !headers.contains_key(header::X_FRAME_OPTIONS) {
+        headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"));
+    }
+    if !headers.contains_key(header::X_CONTENT_TYPE_OPTIONS) {
+        headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    }
+    if !headers.contains_key(header::REFERRER_POLICY) {
+        headers.insert(
+            header::REFERRER_POLICY,
+            HeaderValue::from_static("strict-origin-when-cross-origin"),
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// 跨域与安全响应头（cors）模块的单元测试
+
+#[cfg(test)]
+mod cors_tests {
+    use crate::config;
+    use crate::cors::{apply_security_headers, preflight_method, CompiledCors};
+    use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+
+    fn cors(toml_src: &str) -> CompiledCors {
+        let policy: config::CorsPolicy = toml::from_str(toml_src).expect("invalid cors toml");
+        CompiledCors::compile(&policy, "api").unwrap()
+    }
+
+    fn preflight_headers(origin: &str) -> HeaderMap {
+        let mut h = HeaderMap::new();
+        h.insert("origin", HeaderValue::from_str(origin).unwrap());
+        h.insert("access-control-request-method", HeaderValue::from_static("PUT"));
+        h.insert("access-control-request-headers", HeaderValue::from_static("x-token"));
+        h
+    }
+
+    fn allow_origin(c: &CompiledCors, origin: &str) -> Option<HeaderValue> {
+        let mut headers = HeaderMap::new();
+        c.apply(Some(&HeaderValue::from_str(origin).unwrap()), &mut headers);
+        headers.get("access-control-allow-origin").cloned()
+    }
+
+    #[test]
+    fn test_cors_preflight() {
+        let req = preflight_headers("https://app.example.com");
+        assert_eq!(preflight_method(&Method::OPTIONS, &req), Some(Method::PUT));
+        // 非 OPTIONS 或缺少 Origin 都不是预检
+        assert_eq!(preflight_method(&Method::GET, &req), None);
+        let mut no_origin = req.clone();
+        no_origin.remove("origin");
+        assert_eq!(preflight_method(&Method::OPTIONS, &no_origin), None);
+
+        let c = cors(
+            r#"allow_origins = ["https://app.example.com/"]
+               allow_methods = ["GET", "PUT"]
+               max_age = 600"#,
+        );
+        let resp = c.preflight_response(&req);
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let h = resp.headers();
+        assert_eq!(h["access-control-allow-origin"], "https://app.example.com");
+        assert_eq!(h["access-control-allow-methods"], "GET, PUT");
+        assert_eq!(h["access-control-max-age"], "600");
+        // 未配置 allow_headers 时回显请求头，并在 Vary 中声明
+        assert_eq!(h["access-control-allow-headers"], "x-token");
+        let vary: Vec<_> = h.get_all("vary").iter().collect();
+        assert_eq!(vary, ["Origin", "Access-Control-Request-Headers"]);
+
+        let resp = c.preflight_response(&preflight_headers("https://evil.com"));
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(!resp.headers().contains_key("access-control-allow-origin"));
+    }
+
+    #[test]
+    fn test_cors_origin_matching() {
+        let c = cors(r#"allow_origins = ["https://*.example.com", "~^https://(foo|bar)\\.test$", "HTTPS://Exact.org"]"#);
+
+        // (Origin, 是否放行)
+        let cases = [
+            ("https://a.example.com", true),
+            ("https://a.b.example.com", true),
+            ("https://A.EXAMPLE.com", true),
+            ("https://example.com.evil.com", false),
+            ("https://evil.com/.example.com", false),
+            ("http://a.example.com", false),
+            ("https://foo.test", true),
+            ("https://baz.test", false),
+            ("https://exact.org", true),
+            ("https://exact.org.cn", false),
+        ];
+        for (origin, want) in cases {
+            let got = allow_origin(&c, origin);
+            assert_eq!(got.is_some(), want, "origin={origin}");
+            // 非 `*` 策略总是回显具体 Origin
+            if let Some(v) = got {
+                assert_eq!(v, origin);
+            }
+        }
+
+        // 无效正则在编译期报错
+        let policy: config::CorsPolicy = toml::from_str(r#"allow_origins = ["~(unclosed"]"#).unwrap();
+        assert!(CompiledCors::compile(&policy, "api").is_err());
+    }
+
+    #[test]
+    fn test_cors_credentials_with_wildcard_origin() {
+        // 不带凭证：返回 `*`，无需 Vary
+        let c = cors(
+            r#"allow_origins = ["*"]
+               expose_headers = ["X-Total"]"#,
+        );
+        let mut h = HeaderMap::new();
+        c.apply(Some(&HeaderValue::from_static("https://a.com")), &mut h);
+        assert_eq!(h["access-control-allow-origin"], "*");
+        assert_eq!(h["access-control-expose-headers"], "X-Total");
+        assert!(!h.contains_key("access-control-allow-credentials"));
+        assert!(!h.contains_key("vary"));
+
+        // 带凭证：浏览器不接受 `*`，必须回显 Origin 并声明 Vary
+        let c = cors(
+            r#"allow_origins = ["*"]
+               allow_credentials = true"#,
+        );
+        let mut h = HeaderMap::new();
+        c.apply(Some(&HeaderValue::from_static("https://a.com")), &mut h);
+        assert_eq!(h["access-control-allow-origin"], "https://a.com");
+        assert_eq!(h["access-control-allow-credentials"], "true");
+        assert_eq!(h["vary"], "Origin");
+
+        // 没有 Origin 的同源请求不加任何 CORS 头
+        let mut h = HeaderMap::new();
+        c.apply(None, &mut h);
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn test_security_headers_preset() {
+        let mut h = HeaderMap::new();
+        apply_security_headers(&mut h, true);
+        assert_eq!(h["strict-transport-security"], "max-age=31536000; includeSubDomains");
+        assert_eq!(h["x-frame-options"], "SAMEORIGIN");
+        assert_eq!(h["x-content-type-options"], "nosniff");
+        assert_eq!(h["referrer-policy"], "strict-origin-when-cross-origin");
+
+        // 明文连接不发送 HSTS；已有的显式配置不被覆盖
+        let mut h = HeaderMap::new();
+        h.insert("x-frame-options", HeaderValue::from_static("DENY"));
+        apply_security_headers(&mut h, false);
+        assert!(!h.contains_key("strict-transport-security"));
+        assert_eq!(h["x-frame-options"], "DENY");
+        assert_eq!(h["x-content-type-options"], "nosniff");
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, StatusCode},
+    response::Response,
+};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{config, vars};
+
+/// 单个错误页：HTML 与 JSON 模板二选一或同时配置
+struct Page {
+    html: Option<String>,
+    json: Option<String>,
+}
+
+/// 预加载的错误页集合（文件在监听器启动时读取）
+#[derive(Default)]
+pub struct ErrorPages {
+    pages: HashMap<u16, Arc<Page>>,
+}
+
+impl ErrorPages {
+    pub fn compile(pages: Option<&Vec<config::ErrorPage>>, label: &str) -> Result<Self> {
+        let mut out = HashMap::new();
+        for (i, p) in pages.map(|v| v.as_slice()).unwrap_or_default().iter().enumerate() {
+            let file_html = match p.file.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+                Some(path) => Some(
+                    std::fs::read_to_string(path)
+                        .map_err(|e| anyhow!("{label} 的 error_pages[{i}] 读取文件失败 {path}: {e}"))?,
+                ),
+                None => None,
+            };
+            let page = Arc::new(Page {
+                html: p.html.clone().or(file_html),
+                json: p.json.clone(),
+            });
+            if page.html.is_none() && page.json.is_none() {
+                return Err(anyhow!("{label} 的 error_pages[{i}] 需要配置 file、html 或 json"));
+            }
+            for &code in &p.status {
+                if !(400..=599).contains(&code) {
+                    return Err(anyhow!("{label} 的 error_pages[{i}] 状态码无效: {code}"));
+                }
+                out.insert(code, page.clone());
+            }
+        }
+        Ok(Self { pages: out })
+    }
+
+    pub fn contains(&self, status: StatusCode) -> bool {
+        self.pages.contains_key(&status.as_u16())
+    }
+
+    /// 按 Accept 协商渲染错误页；未配置该状态码时返回 None
+    ///
+    /// 模板变量：`$status`、`$status_text` 以及 lookup 提供的请求变量；
+    /// 代入 HTML 时做实体转义，代入 JSON 时做字符串转义
+    pub fn render<F>(&self, status: StatusCode, accept: &str, lookup: F) -> Option<Response>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let page = self.pages.get(&status.as_u16())?;
+        let status_var = |name: &str| match name {
+            "status" => Some(status.as_u16().to_string()),
+            "status_text" => Some(status.canonical_reason().unwrap_or("").to_string()),
+            _ => lookup(name),
+        };
+
+        let want_json = wants_json(accept) || page.html.is_none();
+        let (body, content_type) = if want_json {
+            let body = match page.json.as_deref() {
+                Some(tpl) => vars::expand(tpl, |name| status_var(name).map(|v| escape_json(&v))),
+                None => serde_json::json!({
+                    "status": status.as_u16(),
+                    "error": status.canonical_reason().unwrap_or(""),
+                    "request_id": status_var("request_id").unwrap_or_default(),
+                })
+                .to_string(),
+            };
+            (body, "application/json; charset=utf-8")
+        } else {
+            let tpl = page.html.as_deref().unwrap_or_default();
+            let body = vars::expand(tpl, |name| status_var(name).map(|v| escape_html(&v)));
+            (body, "text/html; charset=utf-8")
+        };
+
+        let mut resp = Response::new(Body::from(body));
+        *resp.status_mut() = status;
+        resp.headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        Some(resp)
+    }
+}
+
+/// 客户端明确偏好 JSON（且未声明接受 HTML）时返回 JSON 错误页
+fn wants_json(accept: &str) -> bool {
+    let accept = accept.to_ascii_lowercase();
+    accept.contains("json") && !accept.contains("text/html")
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn escape_json(s: &str) -> String {
+    let quoted = serde_json::to_string(s).unwrap_or_default();
+    quoted
+        .strip_prefix('"')
+        .and_then(|q| q.strip_suffix('"'))
+        .unwrap_or("")
+        .to_string()
+}
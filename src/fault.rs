@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::response::Response;
+use futures_util::StreamExt;
+use std::time::Duration;
+
+use crate::config;
+
+/// 预编译的故障注入规则（用于测试客户端的容错能力）
+pub struct CompiledFault {
+    header: Option<(HeaderName, Option<String>)>,
+    /// (固定延迟, 随机抖动上限, 命中比例万分比)
+    delay: Option<(u64, u64, u64)>,
+    abort: Option<(StatusCode, u64)>,
+    reset: u64,
+    bytes_per_sec: Option<u64>,
+}
+
+/// 单个请求实际要注入的故障
+#[derive(Debug, Default)]
+pub struct FaultPlan {
+    pub delay: Option<Duration>,
+    pub abort: Option<StatusCode>,
+    pub reset: bool,
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl FaultPlan {
+    /// 写入 request_logs.fault 的描述，例如 `delay=230ms,abort=503`
+    pub fn label(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(d) = self.delay {
+            parts.push(format!("delay={}ms", d.as_millis()));
+        }
+        if self.reset {
+            parts.push("reset".to_string());
+        } else if let Some(s) = self.abort {
+            parts.push(format!("abort={}", s.as_u16()));
+        }
+        if let Some(bps) = self.bytes_per_sec {
+            parts.push(format!("throttle={}KB/s", bps / 1024));
+        }
+        parts.join(",")
+    }
+}
+
+#[inline]
+fn random_u64() -> u64 {
+    uuid::Uuid::new_v4().as_u64_pair().0
+}
+
+#[inline]
+fn roll(basis_points: u64) -> bool {
+    basis_points >= 10_000 || (basis_points > 0 && random_u64() % 10_000 < basis_points)
+}
+
+fn basis_points(percent: Option<f64>, field: &str, label: &str) -> Result<u64> {
+    let p = percent.unwrap_or(100.0);
+    if !(0.0..=100.0).contains(&p) {
+        return Err(anyhow!("路由 {label} 的 fault.{field} 需在 0-100 之间: {p}"));
+    }
+    Ok((p * 100.0).round() as u64)
+}
+
+impl CompiledFault {
+    pub fn compile(cfg: &config::FaultConfig, label: &str) -> Result<Self> {
+        let header = match cfg.header.as_ref() {
+            Some(h) => {
+                let n = HeaderName::from_bytes(h.name.trim().as_bytes())
+                    .map_err(|_| anyhow!("路由 {label} 的 fault.header 请求头名无效: {}", h.name))?;
+                Some((n, h.value.clone()))
+            }
+            None => None,
+        };
+
+        let delay = if cfg.delay_ms.is_some() || cfg.delay_jitter_ms.is_some() {
+            Some((
+                cfg.delay_ms.unwrap_or(0),
+                cfg.delay_jitter_ms.unwrap_or(0),
+                basis_points(cfg.delay_percent, "delay_percent", label)?,
+            ))
+        } else {
+            None
+        };
+
+        let abort = match cfg.abort_status {
+            Some(code) => {
+                let status = StatusCode::from_u16(code)
+                    .ok()
+                    .filter(|s| s.is_client_error() || s.is_server_error())
+                    .ok_or_else(|| anyhow!("路由 {label} 的 fault.abort_status 需为 4xx/5xx: {code}"))?;
+                Some((status, basis_points(cfg.abort_percent, "abort_percent", label)?))
+            }
+            None => None,
+        };
+
+        let reset = match cfg.reset_percent {
+            Some(_) => basis_points(cfg.reset_percent, "reset_percent", label)?,
+            None => 0,
+        };
+
+        Ok(Self {
+            header,
+            delay,
+            abort,
+            reset,
+            bytes_per_sec: cfg.bandwidth_kbps.filter(|k| *k > 0).map(|k| k * 1024),
+        })
+    }
+
+    /// 为当前请求抽样生成故障；配置了 header 条件但请求不满足时返回 None
+    pub fn plan(&self, headers: &HeaderMap) -> Option<FaultPlan> {
+        if let Some((name, expected)) = self.header.as_ref() {
+            let actual = headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim)?;
+            if expected.as_deref().is_some_and(|e| e != actual) {
+                return None;
+            }
+        }
+
+        let plan = FaultPlan {
+            delay: self.delay.and_then(|(fixed, jitter, bp)| {
+                roll(bp).then(|| {
+                    let extra = if jitter > 0 { random_u64() % (jitter + 1) } else { 0 };
+                    Duration::from_millis(fixed + extra)
+                })
+            }),
+            abort: self.abort.and_then(|(status, bp)| roll(bp).then_some(status)),
+            reset: roll(self.reset),
+            bytes_per_sec: self.bytes_per_sec,
+        };
+        let empty = plan.delay.is_none() && plan.abort.is_none() && !plan.reset && plan.bytes_per_sec.is_none();
+        (!empty).then_some(plan)
+    }
+}
+
+/// 模拟连接重置：响应体立即出错，服务端在发送响应头前后中断连接
+pub fn reset_response() -> Response {
+    let stream = futures_util::stream::once(async {
+        Err::<Bytes, std::io::Error>(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "fault injection: connection reset",
+        ))
+    });
+    Response::new(Body::from_stream(stream))
+}
+
+/// 按带宽限速输出响应体：每 100ms 发送 bytes_per_sec / 10 字节
+pub fn throttle(body: Body, bytes_per_sec: u64) -> Body {
+    let piece = (bytes_per_sec / 10).max(1) as usize;
+    let stream = futures_util::stream::unfold(
+        (body.into_data_stream(), Bytes::new()),
+        move |(mut inner, mut pending)| async move {
+            if pending.is_empty() {
+                match inner.next().await? {
+                    Ok(b) => pending = b,
+                    Err(e) => return Some((Err(e), (inner, Bytes::new()))),
+                }
+            }
+            let n = piece.min(pending.len());
+            let out = pending.split_to(n);
+            tokio::time::sleep(Duration::from_secs_f64(n as f64 / bytes_per_sec as f64)).await;
+            Some((Ok(out), (inner, pending)))
+        },
+    );
+    Body::from_stream(stream)
+}
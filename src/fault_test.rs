@@ -0,0 +1,45 @@
+// 故障注入（fault）模块的单元测试
+
+#[cfg(test)]
+mod fault_tests {
+    use crate::config;
+    use crate::fault::CompiledFault;
+    use axum::http::{HeaderMap, HeaderValue, StatusCode};
+
+    fn compile(toml_src: &str) -> anyhow::Result<CompiledFault> {
+        let cfg: config::FaultConfig = toml::from_str(toml_src).expect("invalid fault toml");
+        CompiledFault::compile(&cfg, "f")
+    }
+
+    #[test]
+    fn test_fault_plan() {
+        let fault = compile(
+            r#"header = { name = "x-chaos", value = "on" }
+               delay_ms = 100
+               delay_jitter_ms = 50
+               abort_status = 503"#,
+        )
+        .unwrap();
+
+        // 未带请求头或取值不符时不注入
+        assert!(fault.plan(&HeaderMap::new()).is_none());
+        let mut headers = HeaderMap::new();
+        headers.insert("x-chaos", HeaderValue::from_static("off"));
+        assert!(fault.plan(&headers).is_none());
+
+        headers.insert("x-chaos", HeaderValue::from_static("on"));
+        for _ in 0..20 {
+            let plan = fault.plan(&headers).expect("fault planned");
+            let delay = plan.delay.unwrap().as_millis();
+            assert!((100..=150).contains(&delay), "delay {delay}");
+            assert_eq!(plan.abort, Some(StatusCode::SERVICE_UNAVAILABLE));
+            assert!(!plan.reset);
+            assert!(plan.label().ends_with(",abort=503"));
+        }
+
+        // 非错误状态码、越界比例视为配置错误
+        for bad in ["abort_status = 200", "reset_percent = 150.0"] {
+            assert!(compile(bad).is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{anyhow, Context, Result};
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::Response;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{config, proxy};
+
+const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+/// 拒绝时返回给客户端的认证响应体上限
+const MAX_DENY_BODY: usize = 64 * 1024;
+/// 缓存条目超过该数量时清理过期项
+const CACHE_SWEEP_THRESHOLD: usize = 10_000;
+
+/// 认证结果
+pub enum AuthDecision {
+    /// 放行，附带需要写入上游请求的请求头
+    Allow(Vec<(HeaderName, HeaderValue)>),
+    /// 拒绝（401 / 403），原样返回认证服务的响应
+    Deny {
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    },
+}
+
+impl AuthDecision {
+    pub fn deny_response(&self) -> Option<Response> {
+        let AuthDecision::Deny { status, headers, body } = self else {
+            return None;
+        };
+        let mut resp = Response::new(Body::from(body.clone()));
+        *resp.status_mut() = *status;
+        *resp.headers_mut() = headers.clone();
+        Some(resp)
+    }
+}
+
+/// 子请求附带的原始请求信息
+pub struct AuthRequest<'a> {
+    pub method: &'a Method,
+    /// 原始请求 URI（路径 + 查询串）
+    pub uri: &'a str,
+    pub headers: &'a HeaderMap,
+    pub proto: &'a str,
+    pub host: &'a str,
+    pub client_ip: &'a str,
+}
+
+/// 预编译的外部认证配置
+pub struct CompiledForwardAuth {
+    url: String,
+    timeout: Duration,
+    copy_headers: Vec<HeaderName>,
+    cache: Option<(HeaderName, Duration)>,
+    entries: DashMap<String, (Instant, Arc<AuthDecision>)>,
+}
+
+impl CompiledForwardAuth {
+    pub fn compile(cfg: &config::ForwardAuthConfig, label: &str) -> Result<Self> {
+        let url = cfg.url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(anyhow!("路由 {label} 的 forward_auth.url 无效: {url}"));
+        }
+        let copy_headers = cfg
+            .copy_headers
+            .iter()
+            .map(|h| {
+                HeaderName::from_bytes(h.trim().as_bytes())
+                    .map_err(|_| anyhow!("路由 {label} 的 forward_auth.copy_headers 请求头名无效: {h}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let cache = match (cfg.cache_seconds.filter(|s| *s > 0), cfg.cache_key_header.as_deref()) {
+            (Some(secs), Some(h)) => Some((
+                HeaderName::from_bytes(h.trim().as_bytes())
+                    .map_err(|_| anyhow!("路由 {label} 的 forward_auth.cache_key_header 请求头名无效: {h}"))?,
+                Duration::from_secs(secs),
+            )),
+            (Some(_), None) => {
+                return Err(anyhow!("路由 {label} 的 forward_auth.cache_seconds 需要同时配置 cache_key_header"))
+            }
+            _ => None,
+        };
+        Ok(Self {
+            url: url.to_string(),
+            timeout: Duration::from_millis(cfg.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS).max(1)),
+            copy_headers,
+            cache,
+            entries: DashMap::new(),
+        })
+    }
+
+    /// 放行时从认证响应复制给上游的请求头
+    pub fn identity_headers(&self) -> &[HeaderName] {
+        &self.copy_headers
+    }
+
+    /// 写入放行时复制的请求头：先移除客户端自带的 copy_headers 同名头，
+    /// 避免认证响应未返回某个头时客户端伪造的值被转发给上游
+    pub fn apply_copied_headers(&self, copied: &[(HeaderName, HeaderValue)], headers: &mut HeaderMap) {
+        for name in &self.copy_headers {
+            headers.remove(name);
+        }
+        for (name, value) in copied {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+
+    /// 执行认证：命中缓存时直接返回；认证服务不可用或返回其他状态码时报错
+    pub async fn check(&self, client: &reqwest::Client, req: &AuthRequest<'_>) -> Result<Arc<AuthDecision>> {
+        // 缓存键包含方法与 Host + URI：同一凭据对不同资源的认证结果可能不同
+        let cache_key = self.cache.as_ref().and_then(|(name, _)| {
+            req.headers.get(name).map(|v| {
+                format!(
+                    "{} {}{}\n{}",
+                    req.method.as_str(),
+                    req.host,
+                    req.uri,
+                    String::from_utf8_lossy(v.as_bytes())
+                )
+            })
+        });
+        if let (Some(key), Some((_, ttl))) = (cache_key.as_ref(), self.cache.as_ref()) {
+            if let Some(entry) = self.entries.get(key) {
+                if entry.0.elapsed() < *ttl {
+                    return Ok(entry.1.clone());
+                }
+            }
+        }
+
+        let decision = Arc::new(self.request(client, req).await?);
+
+        if let (Some(key), Some((_, ttl))) = (cache_key, self.cache.as_ref()) {
+            if self.entries.len() >= CACHE_SWEEP_THRESHOLD {
+                self.entries.retain(|_, (at, _)| at.elapsed() < *ttl);
+            }
+            self.entries.insert(key, (Instant::now(), decision.clone()));
+        }
+        Ok(decision)
+    }
+
+    async fn request(&self, client: &reqwest::Client, req: &AuthRequest<'_>) -> Result<AuthDecision> {
+        let mut headers = HeaderMap::new();
+        for (k, v) in req.headers {
+            if proxy::is_hop_header_fast(k.as_str())
+                || k == header::HOST
+                || k == header::CONTENT_LENGTH
+                || k == header::ACCEPT_ENCODING
+            {
+                continue;
+            }
+            headers.append(k.clone(), v.clone());
+        }
+        let mut set = |name: &'static str, value: &str| {
+            if let Ok(v) = HeaderValue::from_str(value) {
+                headers.insert(HeaderName::from_static(name), v);
+            }
+        };
+        set("x-forwarded-method", req.method.as_str());
+        set("x-forwarded-proto", req.proto);
+        set("x-forwarded-host", req.host);
+        set("x-forwarded-uri", req.uri);
+        set("x-forwarded-for", req.client_ip);
+        set("x-original-uri", req.uri);
+        set("x-original-method", req.method.as_str());
+
+        let resp = client
+            .request(req.method.clone(), &self.url)
+            .headers(headers)
+            .timeout(self.timeout)
+            .send()
+            .await
+            .with_context(|| format!("认证服务请求失败: {}", self.url))?;
+        let status = resp.status();
+
+        if status.is_success() {
+            let copied = self
+                .copy_headers
+                .iter()
+                .filter_map(|name| resp.headers().get(name).map(|v| (name.clone(), v.clone())))
+                .collect();
+            return Ok(AuthDecision::Allow(copied));
+        }
+        if status != StatusCode::UNAUTHORIZED && status != StatusCode::FORBIDDEN {
+            return Err(anyhow!("认证服务返回非预期状态码 {}: {}", status.as_u16(), self.url));
+        }
+
+        let mut deny_headers = HeaderMap::new();
+        for (k, v) in resp.headers() {
+            if proxy::is_hop_header_fast(k.as_str()) || k == header::CONTENT_LENGTH {
+                continue;
+            }
+            deny_headers.append(k.clone(), v.clone());
+        }
+        let mut body = Vec::new();
+        let mut resp = resp;
+        while let Some(chunk) = resp.chunk().await.context("读取认证服务响应失败")? {
+            let room = MAX_DENY_BODY.saturating_sub(body.len());
+            body.extend_from_slice(&chunk[..room.min(chunk.len())]);
+            if room <= chunk.len() {
+                break;
+            }
+        }
+        Ok(AuthDecision::Deny {
+            status,
+            headers: deny_headers,
+            body: Bytes::from(body),
+        })
+    }
+}
@@ -0,0 +1,166 @@
+// 转发认证（forward_auth）模块的单元测试
+
+#[cfg(test)]
+mod forward_auth_tests {
+    use crate::config;
+    use crate::forward_auth::{AuthDecision, AuthRequest, CompiledForwardAuth};
+    use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::any;
+
+    fn forward_auth(toml_src: &str) -> anyhow::Result<CompiledForwardAuth> {
+        let cfg: config::ForwardAuthConfig = toml::from_str(toml_src).expect("invalid forward_auth toml");
+        CompiledForwardAuth::compile(&cfg, "shop")
+    }
+
+    #[tokio::test]
+    async fn test_forward_auth_check() {
+        // 认证服务：按 Cookie 决定结果，并校验 X-Forwarded-* 是否带上原始请求信息
+        let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = axum::Router::new().route(
+            "/auth",
+            any(move |method: Method, headers: HeaderMap| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                async move {
+                    assert_eq!(method, Method::POST);
+                    assert_eq!(headers["x-forwarded-uri"], "/orders?id=7");
+                    assert_eq!(headers["x-forwarded-host"], "shop.example.com");
+                    assert!(!headers.contains_key("content-length"));
+                    match headers.get("cookie").and_then(|v| v.to_str().ok()) {
+                        Some("session=good") => (StatusCode::OK, [("x-user", "alice")], "").into_response(),
+                        Some("session=bad") => (
+                            StatusCode::UNAUTHORIZED,
+                            [("www-authenticate", "Cookie realm=shop")],
+                            "login required",
+                        )
+                            .into_response(),
+                        _ => StatusCode::IM_A_TEAPOT.into_response(),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let fa = &forward_auth(&format!(
+            r#"url = "http://{addr}/auth"
+               copy_headers = ["X-User"]
+               cache_seconds = 30
+               cache_key_header = "Cookie""#
+        ))
+        .unwrap();
+        let client = reqwest::Client::new();
+        let check = |cookie: &'static str| {
+            let client = client.clone();
+            async move {
+                let mut headers = HeaderMap::new();
+                headers.insert("cookie", cookie.parse().unwrap());
+                headers.insert("content-length", "3".parse().unwrap());
+                fa.check(&client, &AuthRequest {
+                    method: &Method::POST,
+                    uri: "/orders?id=7",
+                    headers: &headers,
+                    proto: "https",
+                    host: "shop.example.com",
+                    client_ip: "10.0.0.1",
+                })
+                .await
+            }
+        };
+
+        for _ in 0..2 {
+            let d = check("session=good").await.unwrap();
+            let AuthDecision::Allow(copied) = d.as_ref() else { panic!("expected allow") };
+            assert_eq!(copied.len(), 1);
+            assert_eq!((copied[0].0.as_str(), copied[0].1.to_str().unwrap()), ("x-user", "alice"));
+        }
+        // 相同 Cookie 命中缓存
+        assert_eq!(hits.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+        let d = check("session=bad").await.unwrap();
+        let resp = d.deny_response().expect("deny");
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers()["www-authenticate"], "Cookie realm=shop");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"login required");
+
+        // 其他状态码视为认证服务异常，且不缓存
+        assert!(check("session=other").await.is_err());
+        assert!(check("session=other").await.is_err());
+        assert_eq!(hits.load(std::sync::atomic::Ordering::Relaxed), 4);
+
+        assert!(forward_auth(
+            r#"url = "http://127.0.0.1/auth"
+               cache_seconds = 10"#
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forward_auth_cache_key_and_spoofed_headers() {
+        // 认证服务：只允许 GET /public；放行时不返回 X-User
+        let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = hits.clone();
+        let app = axum::Router::new().route(
+            "/auth",
+            any(move |headers: HeaderMap| {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                async move {
+                    let allowed = headers["x-forwarded-method"] == "GET" && headers["x-forwarded-uri"] == "/public";
+                    if allowed { StatusCode::NO_CONTENT } else { StatusCode::FORBIDDEN }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let fa = &forward_auth(&format!(
+            r#"url = "http://{addr}/auth"
+               copy_headers = ["X-User"]
+               cache_seconds = 30
+               cache_key_header = "Cookie""#
+        ))
+        .unwrap();
+        let client = reqwest::Client::new();
+        let mut headers = HeaderMap::new();
+        headers.insert("cookie", HeaderValue::from_static("session=s1"));
+        let check = |method: &'static Method, host: &'static str, uri: &'static str| {
+            let client = client.clone();
+            let headers = headers.clone();
+            async move {
+                let req = AuthRequest {
+                    method,
+                    uri,
+                    headers: &headers,
+                    proto: "https",
+                    host,
+                    client_ip: "10.0.0.1",
+                };
+                let d = fa.check(&client, &req).await.unwrap();
+                matches!(d.as_ref(), AuthDecision::Allow(_))
+            }
+        };
+
+        // 缓存的放行结果不会被同一 Cookie 的其他方法、路径或 Host 复用
+        assert!(check(&Method::GET, "a.com", "/public").await);
+        assert!(check(&Method::GET, "a.com", "/public").await);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert!(!check(&Method::POST, "a.com", "/admin").await);
+        assert!(!check(&Method::GET, "a.com", "/admin").await);
+        assert!(!check(&Method::POST, "a.com", "/public").await);
+        assert!(check(&Method::GET, "b.com", "/public").await);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::Relaxed), 5);
+
+        // 认证响应未返回 copy_headers 时，客户端自带的同名头也不会转发给上游
+        let mut upstream = HeaderMap::new();
+        upstream.insert("x-user", HeaderValue::from_static("admin"));
+        fa.apply_copied_headers(&[], &mut upstream);
+        assert!(!upstream.contains_key("x-user"));
+        upstream.insert("x-user", HeaderValue::from_static("admin"));
+        fa.apply_copied_headers(&[("x-user".parse().unwrap(), HeaderValue::from_static("alice"))], &mut upstream);
+        assert_eq!(upstream.get_all("x-user").iter().collect::<Vec<_>>(), ["alice"]);
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path, State},
+    http::{header, uri::Authority, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{any, get},
+    Router,
+};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+};
+use tracing::info;
+
+use crate::{config, proxy};
+
+#[derive(Clone)]
+struct RedirectState {
+    status: StatusCode,
+    https_port: u16,
+    acme_dir: Option<Arc<PathBuf>>,
+}
+
+/// 校验并返回跳转状态码（默认 301）
+pub fn https_redirect_status(rule: &config::ListenRule) -> Result<StatusCode> {
+    match rule.https_redirect_status.unwrap_or(301) {
+        301 => Ok(StatusCode::MOVED_PERMANENTLY),
+        302 => Ok(StatusCode::FOUND),
+        307 => Ok(StatusCode::TEMPORARY_REDIRECT),
+        308 => Ok(StatusCode::PERMANENT_REDIRECT),
+        other => Err(anyhow!("https_redirect_status 仅支持 301/302/307/308，当前为 {other}")),
+    }
+}
+
+/// 规则是否配置了 HTTP -> HTTPS 跳转伴随监听，返回其监听地址
+pub fn redirect_listen_addr(rule: &config::ListenRule) -> Option<String> {
+    if !rule.ssl_enable {
+        return None;
+    }
+    rule.https_redirect_listen_addr
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+}
+
+/// 跳转目标端口：取该规则的第一个 HTTPS 监听地址
+fn https_port(rule: &config::ListenRule) -> Result<u16> {
+    let first = rule
+        .listen_addrs
+        .iter()
+        .map(|s| s.trim())
+        .find(|s| !s.is_empty())
+        .unwrap_or(rule.listen_addr.trim());
+    let (addr, _) = proxy::parse_listen_addr(first)?;
+    Ok(addr.port())
+}
+
+/// 构建跳转服务：/healthz、ACME HTTP-01 验证文件，其余请求跳转到 HTTPS
+pub fn redirect_router(rule: &config::ListenRule) -> Result<Router> {
+    let state = RedirectState {
+        status: https_redirect_status(rule)?,
+        https_port: https_port(rule)?,
+        acme_dir: rule
+            .acme_challenge_dir
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|d| Arc::new(PathBuf::from(d))),
+    };
+
+    Ok(Router::new()
+        .route("/healthz", any(proxy::healthz))
+        .route("/.well-known/acme-challenge/{token}", get(acme_challenge))
+        .fallback(any(redirect))
+        .with_state(state))
+}
+
+pub async fn start_redirect_server(
+    rule: config::ListenRule,
+    listen_addr: String,
+    shutdown_rx: tokio::sync::oneshot::Receiver<()>,
+) -> Result<()> {
+    let (addr, need_dual_stack) = proxy::parse_listen_addr(&listen_addr)?;
+    let https_port = https_port(&rule)?;
+    let app = redirect_router(&rule)?;
+
+    // 与主监听器一致：`:port` 绑定 [::]:port 同时接收 IPv4 和 IPv6；系统不支持 IPv6 时回退到只监听 IPv4
+    let listener = if need_dual_stack && addr.is_ipv6() {
+        match tokio::net::TcpListener::bind(addr).await {
+            Ok(l) => {
+                proxy::send_log(format!("HTTPS 跳转监听 IPv6 (dual-stack): {} (同时支持 IPv4 和 IPv6)", addr));
+                l
+            }
+            Err(e) => {
+                let v4 = SocketAddr::from((Ipv4Addr::UNSPECIFIED, addr.port()));
+                info!("HTTPS 跳转绑定 {} 失败（{}），回退到 {}", addr, e, v4);
+                tokio::net::TcpListener::bind(v4).await?
+            }
+        }
+    } else {
+        tokio::net::TcpListener::bind(addr).await?
+    };
+    let addr = listener.local_addr().unwrap_or(addr);
+    proxy::send_log(format!("HTTPS 跳转已启用: {} -> https 端口 {}", addr, https_port));
+
+    let mut shutdown_rx = shutdown_rx;
+    tokio::select! {
+        res = axum::serve(listener, app) => {
+            res.map_err(|e| anyhow!("HTTPS 跳转服务失败: {e}"))?;
+        }
+        _ = &mut shutdown_rx => {
+            info!("收到关闭信号，HTTPS 跳转服务 {} 即将停止", addr);
+        }
+    }
+    Ok(())
+}
+
+async fn acme_challenge(
+    State(state): State<RedirectState>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Response {
+    let Some(dir) = state.acme_dir.as_ref() else {
+        // 未配置验证目录时按普通请求跳转，交给 HTTPS 监听处理
+        return redirect(State(state), headers, uri).await;
+    };
+
+    // token 只允许 base64url 字符，防止路径穿越
+    let valid = !token.is_empty()
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if !valid {
+        return (StatusCode::NOT_FOUND, "Not Found").into_response();
+    }
+
+    match tokio::fs::read(dir.join(&token)).await {
+        Ok(bytes) => (
+            [(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"))],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => (StatusCode::NOT_FOUND, "Not Found").into_response(),
+    }
+}
+
+async fn redirect(State(state): State<RedirectState>, headers: HeaderMap, uri: Uri) -> Response {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|h| h.parse::<Authority>().ok())
+        .map(|a| a.host().to_string());
+    let Some(host) = host else {
+        return (StatusCode::BAD_REQUEST, "Missing Host").into_response();
+    };
+
+    let path_and_query = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let location = if state.https_port == 443 {
+        format!("https://{host}{path_and_query}")
+    } else {
+        format!("https://{host}:{}{path_and_query}", state.https_port)
+    };
+
+    match HeaderValue::from_str(&location) {
+        Ok(v) => (state.status, [(header::LOCATION, v)]).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "Bad Request").into_response(),
+    }
+}
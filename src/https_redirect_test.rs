@@ -0,0 +1,105 @@
+// HTTP -> HTTPS 跳转（https_redirect）模块的单元测试
+
+#[cfg(test)]
+mod https_redirect_tests {
+    use crate::config;
+    use crate::https_redirect::redirect_router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::response::Response;
+    use tower::util::ServiceExt;
+
+    fn rule(extra: &str) -> config::ListenRule {
+        toml::from_str(&format!(
+            r#"ssl_enable = true
+               cert_file = "cert.pem"
+               key_file = "key.pem"
+               basic_auth_enable = false
+               basic_auth_username = ""
+               basic_auth_password = ""
+               basic_auth_forward_header = false
+               https_redirect_listen_addr = ":80"
+               routes = []
+               {extra}"#
+        ))
+        .expect("invalid rule toml")
+    }
+
+    async fn get(rule: &config::ListenRule, host: Option<&str>, uri: &str) -> Response {
+        let mut req = Request::builder().uri(uri);
+        if let Some(host) = host {
+            req = req.header("host", host);
+        }
+        redirect_router(rule).unwrap().oneshot(req.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn body(resp: Response) -> String {
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_redirect_location() {
+        // 默认 443 端口不写入 Location；请求 Host 中的端口被丢弃
+        let r = rule(r#"listen_addr = ":443""#);
+        let resp = get(&r, Some("a.com:80"), "/a/b?x=1").await;
+        assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(resp.headers()["location"], "https://a.com/a/b?x=1");
+
+        // 非 443 端口取第一个 listen_addrs，并保留 IPv6 方括号
+        let r = rule(
+            r#"listen_addr = ":443"
+               listen_addrs = ["127.0.0.1:8443", ":9443"]
+               https_redirect_status = 308"#,
+        );
+        let resp = get(&r, Some("a.com"), "/").await;
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["location"], "https://a.com:8443/");
+        let resp = get(&r, Some("[::1]:8080"), "/x").await;
+        assert_eq!(resp.headers()["location"], "https://[::1]:8443/x");
+
+        assert_eq!(get(&r, None, "/x").await.status(), StatusCode::BAD_REQUEST);
+
+        // 不支持的状态码在构建时报错
+        assert!(redirect_router(&rule(
+            r#"listen_addr = ":443"
+               https_redirect_status = 303"#
+        ))
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_acme_challenge_and_healthz() {
+        let dir = std::env::temp_dir().join(format!("acme-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tok-EN_123"), "tok-EN_123.thumb").unwrap();
+        std::fs::write(dir.join("a.b"), "dotted").unwrap();
+        let r = rule(&format!(
+            r#"listen_addr = ":443"
+               acme_challenge_dir = "{}""#,
+            dir.display().to_string().replace('\\', "/")
+        ));
+
+        let resp = get(&r, Some("a.com"), "/.well-known/acme-challenge/tok-EN_123").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], "text/plain");
+        assert_eq!(body(resp).await, "tok-EN_123.thumb");
+
+        // token 只允许 base64url 字符；不存在的文件同样 404，均不跳转
+        for token in ["a.b", "..", "missing"] {
+            let resp = get(&r, Some("a.com"), &format!("/.well-known/acme-challenge/{token}")).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "token={token}");
+        }
+
+        // 未配置验证目录时按普通请求跳转
+        let plain = rule(r#"listen_addr = ":443""#);
+        let resp = get(&plain, Some("a.com"), "/.well-known/acme-challenge/tok").await;
+        assert_eq!(resp.headers()["location"], "https://a.com/.well-known/acme-challenge/tok");
+
+        // /healthz 不跳转
+        let resp = get(&plain, Some("a.com"), "/healthz").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(body(resp).await, "OK");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
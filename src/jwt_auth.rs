@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Context, Result};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, AlgorithmFamily, DecodingKey, Validation};
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+const DEFAULT_JWKS_CACHE_SECS: u64 = 300;
+/// 未知 kid 触发的提前刷新最短间隔，避免伪造 kid 打满 JWKS 服务
+const MIN_JWKS_REFETCH: Duration = Duration::from_secs(10);
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 校验失败原因：Missing 时 WWW-Authenticate 不带 error 参数（RFC 6750 3.1）
+#[derive(Debug)]
+pub enum JwtReject {
+    Missing,
+    Invalid(String),
+}
+
+impl JwtReject {
+    pub fn reason(&self) -> &str {
+        match self {
+            JwtReject::Missing => "missing bearer token",
+            JwtReject::Invalid(r) => r,
+        }
+    }
+
+    pub fn www_authenticate(&self) -> HeaderValue {
+        match self {
+            JwtReject::Missing => HeaderValue::from_static("Bearer realm=\"SSLProxyManager\""),
+            JwtReject::Invalid(_) => {
+                HeaderValue::from_static("Bearer realm=\"SSLProxyManager\", error=\"invalid_token\"")
+            }
+        }
+    }
+}
+
+enum KeySource {
+    Static(DecodingKey),
+    Jwks(JwksCache),
+}
+
+struct JwksCache {
+    url: String,
+    ttl: Duration,
+    client: reqwest::Client,
+    /// (拉取时间, 公钥集)
+    current: RwLock<Option<(Instant, JwkSet)>>,
+    /// 串行化刷新，并发请求只触发一次拉取
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl JwksCache {
+    async fn fetch(&self) -> Result<JwkSet> {
+        let resp = self
+            .client
+            .get(&self.url)
+            .send()
+            .await
+            .with_context(|| format!("获取 JWKS 失败: {}", self.url))?
+            .error_for_status()
+            .with_context(|| format!("获取 JWKS 失败: {}", self.url))?;
+        resp.json::<JwkSet>().await.with_context(|| format!("解析 JWKS 失败: {}", self.url))
+    }
+
+    /// 缓存过期，或 force 且距上次拉取超过 MIN_JWKS_REFETCH 时重新拉取；失败时保留旧缓存
+    async fn keys(&self, force: bool) -> Result<JwkSet> {
+        let fresh = |at: &Instant| at.elapsed() < self.ttl && !(force && at.elapsed() >= MIN_JWKS_REFETCH);
+        if let Some((at, set)) = self.current.read().as_ref() {
+            if fresh(at) {
+                return Ok(set.clone());
+            }
+        }
+
+        let _guard = self.refreshing.lock().await;
+        // 等锁期间可能已被其他请求刷新
+        if let Some((at, set)) = self.current.read().as_ref() {
+            if fresh(at) {
+                return Ok(set.clone());
+            }
+        }
+        match self.fetch().await {
+            Ok(set) => {
+                *self.current.write() = Some((Instant::now(), set.clone()));
+                Ok(set)
+            }
+            Err(e) => match self.current.read().as_ref() {
+                Some((_, set)) => {
+                    tracing::warn!("{e:#}，继续使用缓存的 JWKS");
+                    Ok(set.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+/// 预编译的 JWT 校验规则
+pub struct CompiledJwt {
+    keys: KeySource,
+    validation: Validation,
+    required: Vec<String>,
+    forward: Vec<(HeaderName, String)>,
+}
+
+fn parse_algorithm(name: &str, label: &str) -> Result<Algorithm> {
+    let alg = Algorithm::from_str(name.trim().to_ascii_uppercase().as_str())
+        .map_err(|_| anyhow!("路由 {label} 的 jwt_auth.algorithms 不支持: {name}"))?;
+    match alg {
+        Algorithm::HS256 | Algorithm::RS256 | Algorithm::ES256 => Ok(alg),
+        _ => Err(anyhow!("路由 {label} 的 jwt_auth.algorithms 仅支持 HS256 / RS256 / ES256: {name}")),
+    }
+}
+
+impl CompiledJwt {
+    pub fn compile(cfg: &config::JwtAuthConfig, label: &str) -> Result<Self> {
+        let algorithms = if cfg.algorithms.is_empty() {
+            if cfg.secret.is_some() {
+                vec![Algorithm::HS256]
+            } else {
+                vec![Algorithm::RS256, Algorithm::ES256]
+            }
+        } else {
+            cfg.algorithms
+                .iter()
+                .map(|a| parse_algorithm(a, label))
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let sources = [cfg.secret.is_some(), cfg.key_file.is_some(), cfg.jwks_url.is_some()];
+        if sources.iter().filter(|s| **s).count() != 1 {
+            return Err(anyhow!(
+                "路由 {label} 的 jwt_auth 需要且只能配置 secret / key_file / jwks_url 之一"
+            ));
+        }
+
+        let keys = if let Some(url) = cfg.jwks_url.as_deref() {
+            let url = url.trim();
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(anyhow!("路由 {label} 的 jwt_auth.jwks_url 无效: {url}"));
+            }
+            KeySource::Jwks(JwksCache {
+                url: url.to_string(),
+                ttl: Duration::from_secs(cfg.jwks_cache_seconds.unwrap_or(DEFAULT_JWKS_CACHE_SECS).max(1)),
+                client: reqwest::Client::builder().timeout(JWKS_FETCH_TIMEOUT).build()?,
+                current: RwLock::new(None),
+                refreshing: tokio::sync::Mutex::new(()),
+            })
+        } else {
+            // 单个静态密钥只能对应一种算法族
+            let family = algorithms[0].family();
+            if algorithms.iter().any(|a| a.family() != family) {
+                return Err(anyhow!(
+                    "路由 {label} 的 jwt_auth 使用单个密钥时 algorithms 必须属于同一类（HS / RS / ES）"
+                ));
+            }
+            let key = match (cfg.secret.as_deref(), cfg.key_file.as_deref()) {
+                (Some(secret), _) => {
+                    if family != AlgorithmFamily::Hmac {
+                        return Err(anyhow!("路由 {label} 的 jwt_auth.secret 只能用于 HS256"));
+                    }
+                    DecodingKey::from_secret(secret.as_bytes())
+                }
+                (None, Some(path)) => {
+                    let raw = std::fs::read(path)
+                        .with_context(|| format!("路由 {label} 的 jwt_auth.key_file 读取失败: {path}"))?;
+                    match family {
+                        AlgorithmFamily::Hmac => DecodingKey::from_secret(raw.trim_ascii()),
+                        AlgorithmFamily::Rsa => DecodingKey::from_rsa_pem(&raw)
+                            .with_context(|| format!("路由 {label} 的 jwt_auth.key_file 不是有效的 RSA 公钥: {path}"))?,
+                        AlgorithmFamily::Ec => DecodingKey::from_ec_pem(&raw)
+                            .with_context(|| format!("路由 {label} 的 jwt_auth.key_file 不是有效的 EC 公钥: {path}"))?,
+                        _ => return Err(anyhow!("路由 {label} 的 jwt_auth 不支持该密钥类型")),
+                    }
+                }
+                (None, None) => unreachable!(),
+            };
+            KeySource::Static(key)
+        };
+
+        let mut validation = Validation::new(algorithms[0]);
+        validation.algorithms = algorithms;
+        validation.leeway = cfg.leeway_seconds.unwrap_or(60);
+        validation.validate_nbf = true;
+        if let Some(iss) = cfg.issuer.as_deref() {
+            validation.set_issuer(&[iss]);
+        }
+        if cfg.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&cfg.audience);
+        }
+
+        let mut forward = Vec::new();
+        for (header, claim) in cfg.forward_claims.iter().flatten() {
+            let name = HeaderName::from_bytes(header.trim().as_bytes())
+                .map_err(|_| anyhow!("路由 {label} 的 jwt_auth.forward_claims 请求头名无效: {header}"))?;
+            forward.push((name, claim.trim().to_string()));
+        }
+
+        Ok(Self {
+            keys,
+            validation,
+            required: cfg.required_claims.iter().map(|c| c.trim().to_string()).collect(),
+            forward,
+        })
+    }
+
+    /// 校验 Authorization: Bearer 令牌，返回 claims
+    pub async fn verify(&self, headers: &HeaderMap) -> std::result::Result<Value, JwtReject> {
+        let token = headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                v.split_once(' ')
+                    .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+                    .map(|(_, t)| t.trim())
+            })
+            .filter(|t| !t.is_empty())
+            .ok_or(JwtReject::Missing)?;
+        self.verify_token(token).await
+    }
+
+    /// 校验令牌本身（签名、exp / nbf / iss / aud 与 required_claims），返回 claims
+    pub async fn verify_token(&self, token: &str) -> std::result::Result<Value, JwtReject> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| JwtReject::Invalid(format!("malformed token: {e}")))?;
+
+        let claims = match &self.keys {
+            KeySource::Static(key) => self.decode(token, key)?,
+            KeySource::Jwks(cache) => {
+                let mut set = cache.keys(false).await.map_err(|e| JwtReject::Invalid(format!("{e:#}")))?;
+                if let Some(kid) = header.kid.as_deref() {
+                    if set.find(kid).is_none() {
+                        // 可能是密钥轮换，提前刷新一次
+                        set = cache.keys(true).await.map_err(|e| JwtReject::Invalid(format!("{e:#}")))?;
+                    }
+                    let jwk = set
+                        .find(kid)
+                        .ok_or_else(|| JwtReject::Invalid(format!("unknown kid: {kid}")))?;
+                    let key = DecodingKey::from_jwk(jwk)
+                        .map_err(|e| JwtReject::Invalid(format!("invalid jwk {kid}: {e}")))?;
+                    self.decode(token, &key)?
+                } else {
+                    // 没有 kid 时依次尝试同类型的公钥
+                    let mut last = JwtReject::Invalid("no matching key in JWKS".to_string());
+                    let mut found = None;
+                    for jwk in &set.keys {
+                        let Ok(key) = DecodingKey::from_jwk(jwk) else { continue };
+                        if key.family() != header.alg.family() {
+                            continue;
+                        }
+                        match self.decode(token, &key) {
+                            Ok(c) => {
+                                found = Some(c);
+                                break;
+                            }
+                            Err(e) => last = e,
+                        }
+                    }
+                    found.ok_or(last)?
+                }
+            }
+        };
+
+        for name in &self.required {
+            if claim(&claims, name).is_none_or(Value::is_null) {
+                return Err(JwtReject::Invalid(format!("missing claim: {name}")));
+            }
+        }
+        Ok(claims)
+    }
+
+    fn decode(&self, token: &str, key: &DecodingKey) -> std::result::Result<Value, JwtReject> {
+        // jsonwebtoken 要求 algorithms 全部与密钥同族，JWKS 同时允许 RS / ES 时按密钥类型收窄
+        let validation = if self.validation.algorithms.iter().all(|a| a.family() == key.family()) {
+            std::borrow::Cow::Borrowed(&self.validation)
+        } else {
+            let mut v = self.validation.clone();
+            v.algorithms.retain(|a| a.family() == key.family());
+            if v.algorithms.is_empty() {
+                return Err(JwtReject::Invalid("key type not allowed".to_string()));
+            }
+            std::borrow::Cow::Owned(v)
+        };
+        jsonwebtoken::decode::<Value>(token, key, &validation)
+            .map(|d| d.claims)
+            .map_err(|e| JwtReject::Invalid(e.to_string()))
+    }
+
+    /// 转发给上游的身份请求头
+    pub fn identity_headers(&self) -> impl Iterator<Item = &HeaderName> {
+        self.forward.iter().map(|(name, _)| name)
+    }
+
+    /// 移除客户端自带的转发头，再写入 claims 中对应的值
+    pub fn apply_forward_headers(&self, claims: &Value, headers: &mut HeaderMap) {
+        for (name, claim_name) in &self.forward {
+            headers.remove(name);
+            let Some(v) = claim(claims, claim_name) else { continue };
+            let text = match v {
+                Value::Null => continue,
+                Value::String(s) => s.clone(),
+                Value::Array(items) if items.iter().all(Value::is_string) => items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<Vec<_>>()
+                    .join(","),
+                other => other.to_string(),
+            };
+            if let Ok(hv) = HeaderValue::from_str(&text) {
+                headers.insert(name.clone(), hv);
+            }
+        }
+    }
+}
+
+/// 按 `a.b.c` 取嵌套 claim
+fn claim<'a>(claims: &'a Value, name: &str) -> Option<&'a Value> {
+    name.split('.').try_fold(claims, |v, key| v.get(key))
+}
@@ -1,18 +1,78 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod api_keys;
+#[cfg(test)]
+mod api_keys_test;
 mod app;
+mod basic_auth;
+#[cfg(test)]
+mod basic_auth_test;
+mod cache;
+#[cfg(test)]
+mod cache_test;
+mod capture;
+#[cfg(test)]
+mod capture_test;
+mod circuit_breaker;
+#[cfg(test)]
+mod circuit_breaker_test;
+mod coalesce;
+#[cfg(test)]
+mod coalesce_test;
 mod commands;
 mod config;
+mod fault;
+#[cfg(test)]
+mod fault_test;
+mod forward_auth;
+#[cfg(test)]
+mod forward_auth_test;
 mod metrics;
 mod proxy;
+#[cfg(test)]
+mod proxy_test;
+mod replay;
+#[cfg(test)]
+mod replay_test;
+mod tester;
+#[cfg(test)]
+mod tester_test;
 mod ws_proxy;
 mod stream_proxy;
 mod access_control;
 #[cfg(test)]
 mod access_control_test;
 mod rate_limit;
+mod route_table;
+#[cfg(test)]
+mod route_table_test;
+mod cors;
+#[cfg(test)]
+mod cors_test;
+mod error_pages;
+mod https_redirect;
+#[cfg(test)]
+mod https_redirect_test;
+mod mirror;
+#[cfg(test)]
+mod mirror_test;
+mod mock;
+#[cfg(test)]
+mod mock_test;
+mod oidc;
+#[cfg(test)]
+mod oidc_test;
+mod traffic_split;
+#[cfg(test)]
+mod traffic_split_test;
+mod vars;
+#[cfg(test)]
+mod vars_test;
 mod i18n;
+mod jwt_auth;
+#[cfg(test)]
+mod jwt_auth_test;
 
 use tauri::Manager;
 
@@ -58,9 +118,23 @@ fn main() {
             commands::remove_blacklist_entry,
             commands::get_blacklist_entries,
             commands::refresh_blacklist_cache,
+            commands::create_api_key,
+            commands::update_api_key,
+            commands::delete_api_key,
+            commands::list_api_keys,
             commands::get_metrics_db_status,
             commands::get_metrics_db_status_detail,
             commands::test_metrics_db_connection,
+            commands::purge_cache,
+            commands::start_capture,
+            commands::stop_capture,
+            commands::get_capture_sessions,
+            commands::query_captures,
+            commands::clear_captures,
+            commands::export_capture_har,
+            commands::replay_traffic,
+            commands::explain_route,
+            commands::test_route_request,
             commands::open_cert_file_dialog,
             commands::open_key_file_dialog,
             commands::open_directory_dialog,
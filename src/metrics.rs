@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Context, Result};
+use dashmap::DashMap;
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -6,9 +7,12 @@ use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePo
 use sqlx::{ConnectOptions, QueryBuilder}; // 移除了未使用的 Row
 use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::api_keys;
+
 // 增加批量大小以利用 Bulk Insert 优势
 const DB_FLUSH_BATCH_SIZE: usize = 2000;
 const DB_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
@@ -114,6 +118,15 @@ pub struct RequestLog {
     pub referer: String,
     #[sqlx(default)]
     pub matched_route_id: String,
+    /// 注入的故障描述（如 `delay=200ms,abort=503`），未注入时为空
+    #[sqlx(default)]
+    pub fault: String,
+    /// Basic Auth 认证通过的用户名，未认证时为空
+    #[sqlx(default)]
+    pub auth_user: String,
+    /// API Key 名称，未使用 API Key 认证时为空
+    #[sqlx(default)]
+    pub api_key: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -171,6 +184,9 @@ pub struct RequestLogInsert {
     pub user_agent: String,
     pub referer: String,
     pub matched_route_id: String,
+    pub fault: String,
+    pub auth_user: String,
+    pub api_key: String,
 }
 
 #[inline]
@@ -274,6 +290,24 @@ pub struct MetricsPayload {
     pub top_client_ips: Option<Vec<TopListItem>>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "topUpstreamErrors")]
     pub top_upstream_errors: Option<Vec<TopListItem>>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "cacheStats")]
+    pub cache_stats: Option<Vec<CacheStat>>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "mirrorStats")]
+    pub mirror_stats: Option<Vec<MirrorStat>>,
+    #[serde(skip_serializing_if = "Option::is_none", rename = "splitStats")]
+    pub split_stats: Option<Vec<SplitStat>>,
+}
+
+/// 路由级响应缓存统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStat {
+    pub route: String,
+    pub hits: u64,
+    pub misses: u64,
+    pub stale: u64,
+    pub bypass: u64,
+    #[serde(rename = "hitRatio")]
+    pub hit_ratio: f64,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -617,10 +651,167 @@ impl RealtimeAgg {
             top_paths: if top_paths.is_empty() { None } else { Some(top_paths) },
             top_client_ips: if top_client_ips.is_empty() { None } else { Some(top_client_ips) },
             top_upstream_errors: if top_upstream_errors.is_empty() { None } else { Some(top_upstream_errors) },
+            cache_stats: cache_stats(),
+            mirror_stats: mirror_stats(),
+            split_stats: split_stats(),
+        }
+    }
+}
+
+// --- Cache Stats ---
+
+/// 按路由累计的缓存结果：[HIT, MISS, STALE, BYPASS]
+static CACHE_COUNTERS: Lazy<DashMap<String, [AtomicU64; 4]>> = Lazy::new(DashMap::new);
+
+/// 记录一次缓存结果（取值与 X-Cache 响应头一致）
+pub fn record_cache_result(route_id: &str, result: &str) {
+    let idx = match result {
+        "HIT" => 0,
+        "MISS" => 1,
+        "STALE" => 2,
+        _ => 3,
+    };
+    if let Some(c) = CACHE_COUNTERS.get(route_id) {
+        c[idx].fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+    CACHE_COUNTERS.entry(route_id.to_string()).or_default()[idx].fetch_add(1, Ordering::Relaxed);
+}
+
+fn cache_stats() -> Option<Vec<CacheStat>> {
+    let mut v: Vec<CacheStat> = CACHE_COUNTERS
+        .iter()
+        .map(|e| {
+            let [hits, misses, stale, bypass] = e.value().each_ref().map(|c| c.load(Ordering::Relaxed));
+            let lookups = hits + misses + stale;
+            CacheStat {
+                route: e.key().clone(),
+                hits,
+                misses,
+                stale,
+                bypass,
+                hit_ratio: if lookups == 0 { 0.0 } else { (hits + stale) as f64 / lookups as f64 },
+            }
+        })
+        .collect();
+    if v.is_empty() {
+        return None;
+    }
+    v.sort_by(|a, b| a.route.cmp(&b.route));
+    Some(v)
+}
+
+// --- Mirror Stats ---
+
+/// 流量镜像统计：与主请求的指标分开，不计入 request_logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorStat {
+    pub route: String,
+    pub target: String,
+    pub requests: i64,
+    pub errors: i64,
+    pub s2xx: i64,
+    pub s3xx: i64,
+    pub s4xx: i64,
+    pub s5xx: i64,
+    #[serde(rename = "avgLatencyMs")]
+    pub avg_latency_ms: f64,
+    #[serde(rename = "maxLatencyMs")]
+    pub max_latency_ms: f64,
+    #[serde(rename = "statusCompared")]
+    pub status_compared: i64,
+    #[serde(rename = "statusMismatches")]
+    pub status_mismatches: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MirrorAgg {
+    bucket: RtBucket,
+    compared: i64,
+    mismatches: i64,
+}
+
+static MIRROR_AGG: Lazy<DashMap<(String, String), MirrorAgg>> = Lazy::new(DashMap::new);
+
+/// 记录一次镜像请求结果；status 为 0 表示请求失败，mismatch 为 None 表示未比对
+pub fn record_mirror_result(route_id: &str, target: &str, status: u16, latency_ms: f64, mismatch: Option<bool>) {
+    let mut agg = MIRROR_AGG
+        .entry((route_id.to_string(), target.to_string()))
+        .or_default();
+    agg.bucket.add(status as i32, latency_ms);
+    if let Some(m) = mismatch {
+        agg.compared += 1;
+        if m {
+            agg.mismatches += 1;
         }
     }
 }
 
+fn mirror_stats() -> Option<Vec<MirrorStat>> {
+    let mut v: Vec<MirrorStat> = MIRROR_AGG
+        .iter()
+        .map(|e| {
+            let (route, target) = e.key().clone();
+            let a = e.value();
+            let b = &a.bucket;
+            MirrorStat {
+                route,
+                target,
+                requests: b.count,
+                errors: b.s0,
+                s2xx: b.s2xx,
+                s3xx: b.s3xx,
+                s4xx: b.s4xx,
+                s5xx: b.s5xx,
+                avg_latency_ms: b.avg_latency_ms(),
+                max_latency_ms: b.latency_max_ms,
+                status_compared: a.compared,
+                status_mismatches: a.mismatches,
+            }
+        })
+        .collect();
+    if v.is_empty() {
+        return None;
+    }
+    v.sort_by(|a, b| (&a.route, &a.target).cmp(&(&b.route, &b.target)));
+    Some(v)
+}
+
+// --- Split Stats ---
+
+/// 分流命中计数；split 为 "default" 表示未命中任何分流规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitStat {
+    pub route: String,
+    pub split: String,
+    pub count: u64,
+}
+
+static SPLIT_COUNTERS: Lazy<DashMap<(String, String), AtomicU64>> = Lazy::new(DashMap::new);
+
+pub fn record_split(route_id: &str, split: &str) {
+    SPLIT_COUNTERS
+        .entry((route_id.to_string(), split.to_string()))
+        .or_default()
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+fn split_stats() -> Option<Vec<SplitStat>> {
+    let mut v: Vec<SplitStat> = SPLIT_COUNTERS
+        .iter()
+        .map(|e| SplitStat {
+            route: e.key().0.clone(),
+            split: e.key().1.clone(),
+            count: e.value().load(Ordering::Relaxed),
+        })
+        .collect();
+    if v.is_empty() {
+        return None;
+    }
+    v.sort_by(|a, b| (&a.route, &a.split).cmp(&(&b.route, &b.split)));
+    Some(v)
+}
+
 // --- DB Utils ---
 
 fn default_db_path() -> Result<PathBuf> {
@@ -673,7 +864,7 @@ pub fn is_ip_blacklisted(ip: &str) -> bool {
     }
 }
 
-fn pool() -> Option<Arc<SqlitePool>> {
+pub(crate) fn pool() -> Option<Arc<SqlitePool>> {
     DB_POOL.read().clone()
 }
 
@@ -737,7 +928,10 @@ pub async fn init_db(db_path: String) -> Result<()> {
               latency_ms REAL NOT NULL,
               user_agent TEXT NOT NULL,
               referer TEXT NOT NULL,
-              matched_route_id TEXT NOT NULL DEFAULT ''
+              matched_route_id TEXT NOT NULL DEFAULT '',
+              fault TEXT NOT NULL DEFAULT '',
+              auth_user TEXT NOT NULL DEFAULT '',
+              api_key TEXT NOT NULL DEFAULT ''
             );
             "#,
         )
@@ -762,6 +956,27 @@ pub async fn init_db(db_path: String) -> Result<()> {
             .await
             .context("迁移 request_logs.matched_route_id 失败")?;
         }
+        let has_fault = cols.iter().any(|(_, name, _, _, _, _)| name == "fault");
+        if !has_fault {
+            sqlx::query("ALTER TABLE request_logs ADD COLUMN fault TEXT NOT NULL DEFAULT ''")
+                .execute(&pool)
+                .await
+                .context("迁移 request_logs.fault 失败")?;
+        }
+        let has_auth_user = cols.iter().any(|(_, name, _, _, _, _)| name == "auth_user");
+        if !has_auth_user {
+            sqlx::query("ALTER TABLE request_logs ADD COLUMN auth_user TEXT NOT NULL DEFAULT ''")
+                .execute(&pool)
+                .await
+                .context("迁移 request_logs.auth_user 失败")?;
+        }
+        let has_api_key = cols.iter().any(|(_, name, _, _, _, _)| name == "api_key");
+        if !has_api_key {
+            sqlx::query("ALTER TABLE request_logs ADD COLUMN api_key TEXT NOT NULL DEFAULT ''")
+                .execute(&pool)
+                .await
+                .context("迁移 request_logs.api_key 失败")?;
+        }
 
         sqlx::query(
             r#"CREATE INDEX IF NOT EXISTS idx_request_logs_ts ON request_logs(timestamp);"#,
@@ -819,7 +1034,77 @@ pub async fn init_db(db_path: String) -> Result<()> {
         .await
         .context("创建 blacklist 表失败")?;
 
+        // API Key（只保存 SHA-256 哈希）与按天累计的用量
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_keys (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              name TEXT NOT NULL UNIQUE,
+              key_hash TEXT NOT NULL UNIQUE,
+              key_prefix TEXT NOT NULL,
+              allowed_routes TEXT NOT NULL DEFAULT '',
+              expires_at INTEGER NOT NULL DEFAULT 0,
+              rate_limit_per_second INTEGER NOT NULL DEFAULT 0,
+              rate_limit_burst INTEGER NOT NULL DEFAULT 0,
+              daily_quota INTEGER NOT NULL DEFAULT 0,
+              enabled INTEGER NOT NULL DEFAULT 1,
+              created_at INTEGER NOT NULL
+            );
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .context("创建 api_keys 表失败")?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_key_usage (
+              name TEXT NOT NULL,
+              day TEXT NOT NULL,
+              count INTEGER NOT NULL,
+              PRIMARY KEY (name, day)
+            );
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .context("创建 api_key_usage 表失败")?;
+
+        // 抓包记录（请求 / 响应体按会话配置截断，*_body_size 为原始大小）
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS captures (
+              id INTEGER PRIMARY KEY AUTOINCREMENT,
+              session_id TEXT NOT NULL,
+              timestamp_ms INTEGER NOT NULL,
+              route_id TEXT NOT NULL,
+              client_ip TEXT NOT NULL,
+              method TEXT NOT NULL,
+              url TEXT NOT NULL,
+              http_version TEXT NOT NULL,
+              status_code INTEGER NOT NULL,
+              upstream TEXT NOT NULL,
+              latency_ms REAL NOT NULL,
+              request_headers TEXT NOT NULL,
+              request_body BLOB NOT NULL,
+              request_body_size INTEGER NOT NULL,
+              response_headers TEXT NOT NULL,
+              response_body BLOB NOT NULL,
+              response_body_size INTEGER NOT NULL
+            );
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .context("创建 captures 表失败")?;
+
+        sqlx::query(r#"CREATE INDEX IF NOT EXISTS idx_captures_session ON captures(session_id, id);"#)
+            .execute(&pool)
+            .await
+            .context("创建 captures.session_id 索引失败")?;
+
         refresh_blacklist_cache_internal(&pool).await.ok();
+        api_keys::reload(&pool).await.ok();
 
         *DB_POOL.write() = Some(Arc::new(pool));
         *DB_PATH.write() = path.to_string_lossy().to_string();
@@ -1156,6 +1441,7 @@ pub async fn init_request_log_writer() {
         let mut buf: Vec<RequestLogInsert> = Vec::with_capacity(DB_FLUSH_BATCH_SIZE);
         let mut last_flush = Instant::now();
         let mut last_cleanup = Instant::now();
+        let mut last_usage_flush = Instant::now();
         let mut last_retention_check = Instant::now();
 
         loop {
@@ -1185,6 +1471,15 @@ pub async fn init_request_log_writer() {
                 last_cleanup = Instant::now();
             }
 
+            // API Key 当天用量写回数据库
+            if last_usage_flush.elapsed() >= DB_FLUSH_INTERVAL {
+                let pool_opt = DB_POOL.read().clone();
+                if let Some(pool) = pool_opt {
+                    api_keys::flush_usage(&pool).await;
+                }
+                last_usage_flush = Instant::now();
+            }
+
             // request_logs 日志保留：每天检查一次
             if last_retention_check.elapsed() >= REQUEST_LOG_RETENTION_CHECK_INTERVAL {
                 let pool_opt = DB_POOL.read().clone();
@@ -1242,7 +1537,7 @@ async fn flush_request_logs(buf: &mut Vec<RequestLogInsert>) {
     
     for chunk in buf.chunks(CHUNK_SIZE) {
         let mut query_builder = QueryBuilder::new(
-            "INSERT INTO request_logs (timestamp, listen_addr, client_ip, remote_ip, method, request_path, request_host, status_code, upstream, latency_ms, user_agent, referer, matched_route_id) "
+            "INSERT INTO request_logs (timestamp, listen_addr, client_ip, remote_ip, method, request_path, request_host, status_code, upstream, latency_ms, user_agent, referer, matched_route_id, fault, auth_user, api_key) "
         );
 
         query_builder.push_values(chunk, |mut b, it| {
@@ -1258,7 +1553,10 @@ async fn flush_request_logs(buf: &mut Vec<RequestLogInsert>) {
              .push_bind(it.latency_ms)
              .push_bind(&it.user_agent)
              .push_bind(&it.referer)
-             .push_bind(&it.matched_route_id);
+             .push_bind(&it.matched_route_id)
+             .push_bind(&it.fault)
+             .push_bind(&it.auth_user)
+             .push_bind(&it.api_key);
         });
 
         let query = query_builder.build();
@@ -1320,7 +1618,7 @@ pub async fn query_request_logs(req: QueryRequestLogsRequest) -> Result<QueryReq
 
     // SELECT
     let mut sel_qb = QueryBuilder::new(
-        "SELECT id, timestamp, listen_addr, client_ip, remote_ip, method, request_path, request_host, status_code, upstream, latency_ms, user_agent, referer, matched_route_id FROM request_logs WHERE timestamp >= "
+        "SELECT id, timestamp, listen_addr, client_ip, remote_ip, method, request_path, request_host, status_code, upstream, latency_ms, user_agent, referer, matched_route_id, fault, auth_user, api_key FROM request_logs WHERE timestamp >= "
     );
     sel_qb.push_bind(req.start_time);
     sel_qb.push(" AND timestamp <= ");
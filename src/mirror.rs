@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use axum::body::Bytes;
+use axum::http::{HeaderMap, Method};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+use crate::{config, metrics, proxy};
+
+/// 预编译的流量镜像目标
+pub struct CompiledMirror {
+    pub url: String,
+    /// 采样比例（万分比）
+    basis_points: u64,
+    max_body: usize,
+    compare_status: bool,
+    seq: AtomicU64,
+}
+
+/// 一次镜像请求所需的全部信息（请求头、请求体已在主请求中处理好）
+pub struct MirrorRequest {
+    pub route_id: String,
+    pub method: Method,
+    pub target: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl CompiledMirror {
+    pub fn compile(cfg: &config::MirrorTarget, label: &str) -> Result<Self> {
+        let url = cfg.url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(anyhow!("路由 {label} 的镜像地址无效: {url}"));
+        }
+        if !(0.0..=100.0).contains(&cfg.percent) {
+            return Err(anyhow!("路由 {label} 的镜像 percent 需在 0-100 之间: {}", cfg.percent));
+        }
+        Ok(Self {
+            url: url.to_string(),
+            basis_points: (cfg.percent * 100.0).round() as u64,
+            max_body: cfg.max_body_kb.saturating_mul(1024) as usize,
+            compare_status: cfg.compare_status,
+            seq: AtomicU64::new(0),
+        })
+    }
+
+    /// 按比例采样：逐请求累加，比例精确且无需随机数
+    pub fn sampled(&self) -> bool {
+        if self.basis_points >= 10_000 {
+            return true;
+        }
+        let n = self.seq.fetch_add(1, Ordering::Relaxed);
+        (n + 1) * self.basis_points / 10_000 > n * self.basis_points / 10_000
+    }
+
+    pub fn accepts_body(&self, len: usize) -> bool {
+        len <= self.max_body
+    }
+
+    /// 异步发送镜像请求，忽略其响应体；返回的 Sender 用于回传主请求状态码以比对
+    pub fn spawn(
+        self: &Arc<Self>,
+        client: reqwest::Client,
+        req: MirrorRequest,
+    ) -> Option<oneshot::Sender<u16>> {
+        let (tx, rx) = oneshot::channel::<u16>();
+        let mirror = self.clone();
+        tokio::spawn(async move {
+            let started = std::time::Instant::now();
+            let result = client
+                .request(req.method.clone(), &req.target)
+                .headers(req.headers)
+                .body(req.body)
+                .send()
+                .await;
+            let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+            let status = match result {
+                Ok(resp) => resp.status().as_u16(),
+                Err(e) => {
+                    proxy::send_log(format!("流量镜像失败: {} {} -> {}", req.method, req.target, e));
+                    0
+                }
+            };
+
+            let mismatch = if mirror.compare_status {
+                match rx.await {
+                    Ok(primary) if primary != status => {
+                        proxy::send_log(format!(
+                            "流量镜像状态码不一致: {} {} 主={} 镜像={}",
+                            req.method, req.target, primary, status
+                        ));
+                        Some(true)
+                    }
+                    Ok(_) => Some(false),
+                    Err(_) => None,
+                }
+            } else {
+                None
+            };
+
+            metrics::record_mirror_result(&req.route_id, &mirror.url, status, latency_ms, mismatch);
+        });
+        self.compare_status.then_some(tx)
+    }
+}
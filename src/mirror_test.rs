@@ -0,0 +1,29 @@
+// 流量镜像（mirror）模块的单元测试
+
+#[cfg(test)]
+mod mirror_tests {
+    use crate::config;
+    use crate::mirror::CompiledMirror;
+
+    fn mirror(toml_src: &str) -> anyhow::Result<CompiledMirror> {
+        let cfg: config::MirrorTarget = toml::from_str(toml_src).expect("invalid mirror toml");
+        CompiledMirror::compile(&cfg, "shadow")
+    }
+
+    #[test]
+    fn test_mirror_sampling() {
+        let mirrors = [
+            mirror("url = \"http://127.0.0.1:9000\"\npercent = 25").unwrap(),
+            mirror("url = \"http://127.0.0.1:9001\"").unwrap(),
+        ];
+        let sampled = |i: usize| (0..100).filter(|_| mirrors[i].sampled()).count();
+        assert_eq!(sampled(0), 25);
+        assert_eq!(sampled(1), 100);
+        assert!(mirrors[0].accepts_body(1024 * 1024));
+        assert!(!mirrors[0].accepts_body(1024 * 1024 + 1));
+
+        for bad in ["url = \"ftp://x\"", "url = \"http://x\"\npercent = 101"] {
+            assert!(mirror(bad).is_err(), "{bad}");
+        }
+    }
+}
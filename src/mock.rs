@@ -0,0 +1,455 @@
+use anyhow::{anyhow, Result};
+use axum::body::Body;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::Response;
+use parking_lot::RwLock;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::{config, proxy, vars};
+
+/// 两次检查规则文件是否变化的最小间隔（毫秒）
+const RELOAD_CHECK_INTERVAL_MS: i64 = 1000;
+
+/// Mock 规则文件：`.json` 按 JSON 解析，其他扩展名按 TOML 解析
+#[derive(Debug, Deserialize)]
+struct MockFile {
+    #[serde(default)]
+    mocks: Vec<MockRule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MockRule {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    method: Option<String>,
+    path: String,
+    /// 默认精确匹配，可选 prefix / regex（`~`）/ regex_case_insensitive（`~*`）
+    #[serde(default)]
+    match_type: Option<config::RouteMatchType>,
+    /// 查询参数，值为 `*` 时只要求参数存在
+    #[serde(default)]
+    query: HashMap<String, String>,
+    /// 请求头，值为 `*` 时只要求请求头存在
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// 按 JSONPath 匹配 JSON 请求体
+    #[serde(default)]
+    body: Vec<BodyMatch>,
+    #[serde(default)]
+    response: MockResponseRule,
+}
+
+#[derive(Debug, Deserialize)]
+struct BodyMatch {
+    path: String,
+    /// 未配置时只要求该路径存在
+    #[serde(default)]
+    value: Option<Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MockResponseRule {
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+    /// 以 JSON 值作为响应体（自动补 Content-Type: application/json）
+    #[serde(default)]
+    json: Option<Value>,
+    #[serde(default)]
+    body_file: Option<String>,
+    #[serde(default)]
+    delay_ms: u64,
+}
+
+enum PathPattern {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+/// JSONPath 的一段：对象字段或数组下标
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// 预编译的单条 mock 规则
+pub struct CompiledMock {
+    pub name: String,
+    method: Option<Method>,
+    path: PathPattern,
+    query: Vec<(String, String)>,
+    headers: Vec<(HeaderName, String)>,
+    body: Vec<(Vec<Segment>, Option<Value>)>,
+    status: StatusCode,
+    response_headers: Vec<(HeaderName, String)>,
+    response_body: String,
+    pub delay_ms: u64,
+}
+
+/// 命中结果：规则及其路径正则的捕获组
+pub struct MockHit {
+    pub rule: Arc<CompiledMock>,
+    captures: Vec<(String, String)>,
+}
+
+struct Loaded {
+    fingerprint: Option<(SystemTime, u64)>,
+    rules: Arc<Vec<Arc<CompiledMock>>>,
+}
+
+/// 规则文件及其当前解析结果；可在后台线程中重新加载
+struct Source {
+    file: PathBuf,
+    label: String,
+    loaded: RwLock<Loaded>,
+}
+
+impl Source {
+    /// 文件变化时重新加载；解析失败保留旧规则并写日志
+    fn refresh(&self) {
+        let fp = fingerprint(&self.file);
+        if fp == self.loaded.read().fingerprint {
+            return;
+        }
+        let result = load_rules(&self.file, &self.label);
+        let mut loaded = self.loaded.write();
+        loaded.fingerprint = fp;
+        match result {
+            Ok(rules) => {
+                proxy::send_log(format!("{} 的 mock 文件已重新加载: {} 条规则", self.label, rules.len()));
+                loaded.rules = Arc::new(rules);
+            }
+            Err(e) => proxy::send_log(format!("{e}，继续使用旧规则")),
+        }
+    }
+}
+
+/// 路由的 mock 规则集：规则文件变化后自动重新加载
+pub struct MockSet {
+    source: Arc<Source>,
+    last_check: AtomicI64,
+}
+
+/// 解析 JSONPath 子集：`$.a.b[0]`、`$['a b']`
+fn parse_json_path(raw: &str) -> Option<Vec<Segment>> {
+    let mut rest = raw.trim().strip_prefix('$')?;
+    let mut out = Vec::new();
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('.') {
+            let end = r.find(['.', '[']).unwrap_or(r.len());
+            if end == 0 {
+                return None;
+            }
+            out.push(Segment::Key(r[..end].to_string()));
+            rest = &r[end..];
+        } else if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']')?;
+            let inner = r[..end].trim();
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            out.push(match quoted {
+                Some(k) => Segment::Key(k.to_string()),
+                None => Segment::Index(inner.parse().ok()?),
+            });
+            rest = &r[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(out)
+}
+
+fn json_lookup<'a>(v: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(v, |cur, seg| match seg {
+        Segment::Key(k) => cur.get(k.as_str()),
+        Segment::Index(i) => cur.get(*i),
+    })
+}
+
+/// 字符串按原样输出，其他 JSON 值按 JSON 文本输出
+fn json_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn header_pairs(map: &HashMap<String, String>, what: &str, label: &str) -> Result<Vec<(HeaderName, String)>> {
+    map.iter()
+        .map(|(k, v)| {
+            HeaderName::from_bytes(k.trim().as_bytes())
+                .map(|n| (n, v.clone()))
+                .map_err(|_| anyhow!("{label} 的 {what} 请求头名无效: {k}"))
+        })
+        .collect()
+}
+
+fn query_value<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?.split('&').find_map(|kv| {
+        let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+        (k == name).then_some(v)
+    })
+}
+
+impl CompiledMock {
+    fn compile(rule: &MockRule, label: &str) -> Result<Self> {
+        let name = rule.name.clone().unwrap_or_else(|| rule.path.clone());
+        let label = format!("{label} 的 mock {name}");
+
+        let method = match rule.method.as_deref().map(str::trim).filter(|m| !m.is_empty() && *m != "*") {
+            Some(m) => Some(
+                Method::from_bytes(m.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| anyhow!("{label} 的 method 无效: {m}"))?,
+            ),
+            None => None,
+        };
+
+        let path = match rule.match_type.unwrap_or(config::RouteMatchType::Exact) {
+            config::RouteMatchType::Exact => PathPattern::Exact(rule.path.clone()),
+            config::RouteMatchType::Prefix | config::RouteMatchType::PrefixNoRegex => {
+                PathPattern::Prefix(rule.path.clone())
+            }
+            kind @ (config::RouteMatchType::Regex | config::RouteMatchType::RegexCaseInsensitive) => {
+                let re = RegexBuilder::new(&rule.path)
+                    .case_insensitive(kind == config::RouteMatchType::RegexCaseInsensitive)
+                    .build()
+                    .map_err(|e| anyhow!("{label} 的 path 正则无效: {e}"))?;
+                PathPattern::Regex(re)
+            }
+        };
+
+        let mut body = Vec::new();
+        for m in &rule.body {
+            let segs = parse_json_path(&m.path).ok_or_else(|| anyhow!("{label} 的 JSONPath 无效: {}", m.path))?;
+            body.push((segs, m.value.clone()));
+        }
+
+        let resp = &rule.response;
+        let status = StatusCode::from_u16(resp.status.unwrap_or(200))
+            .map_err(|_| anyhow!("{label} 的 response.status 无效: {:?}", resp.status))?;
+        let mut response_headers = header_pairs(&resp.headers, "response.headers", &label)?;
+        let response_body = match (resp.body.as_ref(), resp.json.as_ref(), resp.body_file.as_deref()) {
+            (Some(b), _, _) => b.clone(),
+            (None, Some(j), _) => {
+                if !response_headers.iter().any(|(k, _)| k == header::CONTENT_TYPE) {
+                    response_headers.push((header::CONTENT_TYPE, "application/json".to_string()));
+                }
+                serde_json::to_string_pretty(j)?
+            }
+            (None, None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("{label} 读取 body_file 失败 {path}: {e}"))?,
+            (None, None, None) => String::new(),
+        };
+
+        Ok(Self {
+            name,
+            method,
+            path,
+            query: rule.query.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            headers: header_pairs(&rule.headers, "headers", &label)?,
+            body,
+            status,
+            response_headers,
+            response_body,
+            delay_ms: resp.delay_ms,
+        })
+    }
+
+    fn matches(
+        &self,
+        method: &Method,
+        path: &str,
+        query: Option<&str>,
+        headers: &HeaderMap,
+        body: Option<&Value>,
+    ) -> Option<Vec<(String, String)>> {
+        if self.method.as_ref().is_some_and(|m| m != method) {
+            return None;
+        }
+
+        let captures = match &self.path {
+            PathPattern::Exact(p) => (path == p).then(Vec::new)?,
+            PathPattern::Prefix(p) => path.starts_with(p.as_str()).then(Vec::new)?,
+            PathPattern::Regex(re) => {
+                let caps = re.captures(path)?;
+                let mut out = Vec::new();
+                for (i, name) in re.capture_names().enumerate().skip(1) {
+                    if let Some(m) = caps.get(i) {
+                        out.push((i.to_string(), m.as_str().to_string()));
+                        if let Some(name) = name {
+                            out.push((name.to_string(), m.as_str().to_string()));
+                        }
+                    }
+                }
+                out
+            }
+        };
+
+        for (name, expected) in &self.query {
+            let actual = query_value(query, name)?;
+            if expected != "*" && actual != expected {
+                return None;
+            }
+        }
+
+        for (name, expected) in &self.headers {
+            let actual = headers.get(name).and_then(|v| v.to_str().ok())?.trim();
+            if expected != "*" && actual != expected {
+                return None;
+            }
+        }
+
+        if !self.body.is_empty() {
+            let body = body?;
+            for (path, expected) in &self.body {
+                let actual = json_lookup(body, path)?;
+                // 期望值为字符串时与实际值的文本形式比较，便于用 "42" 匹配数字
+                let ok = match expected {
+                    None => true,
+                    Some(Value::String(e)) => json_text(actual) == *e,
+                    Some(e) => actual == e,
+                };
+                if !ok {
+                    return None;
+                }
+            }
+        }
+        Some(captures)
+    }
+}
+
+impl MockHit {
+    /// 渲染响应：响应头与响应体支持变量，额外提供路径捕获组、`$arg_<name>` 与 `${body.<path>}`
+    pub fn render<F>(&self, query: Option<&str>, body: Option<&Value>, lookup: F) -> Response
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let var = |name: &str| -> Option<String> {
+            if let Some((_, v)) = self.captures.iter().find(|(k, _)| k == name) {
+                return Some(v.clone());
+            }
+            if let Some(arg) = name.strip_prefix("arg_") {
+                return Some(query_value(query, arg).unwrap_or("").to_string());
+            }
+            if let Some(p) = name.strip_prefix("body.") {
+                let v = body.zip(parse_json_path(&format!("$.{p}"))).and_then(|(b, segs)| json_lookup(b, &segs).map(json_text));
+                return Some(v.unwrap_or_default());
+            }
+            lookup(name)
+        };
+
+        let rule = &self.rule;
+        let mut resp = Response::new(Body::from(vars::expand(&rule.response_body, var)));
+        *resp.status_mut() = rule.status;
+        for (k, v) in &rule.response_headers {
+            if let Ok(v) = HeaderValue::from_str(&vars::expand(v, var)) {
+                resp.headers_mut().insert(k.clone(), v);
+            }
+        }
+        resp
+    }
+}
+
+fn fingerprint(path: &PathBuf) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+fn load_rules(path: &PathBuf, label: &str) -> Result<Vec<Arc<CompiledMock>>> {
+    let text = std::fs::read_to_string(path).map_err(|e| anyhow!("{label} 读取 mock 文件失败 {}: {e}", path.display()))?;
+    let file: MockFile = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json")) {
+        serde_json::from_str(&text).map_err(|e| anyhow!("{label} 解析 mock 文件失败: {e}"))?
+    } else {
+        toml::from_str(&text).map_err(|e| anyhow!("{label} 解析 mock 文件失败: {e}"))?
+    };
+    file.mocks
+        .iter()
+        .map(|r| CompiledMock::compile(r, label).map(Arc::new))
+        .collect()
+}
+
+impl MockSet {
+    pub fn load(cfg: &config::MockConfig, label: &str) -> Result<Self> {
+        let file = PathBuf::from(cfg.file.trim());
+        if cfg.file.trim().is_empty() {
+            return Err(anyhow!("路由 {label} 的 mock.file 不能为空"));
+        }
+        let label = format!("路由 {label}");
+        let fp = fingerprint(&file);
+        let rules = load_rules(&file, &label)?;
+        Ok(Self {
+            source: Arc::new(Source {
+                file,
+                label,
+                loaded: RwLock::new(Loaded {
+                    fingerprint: fp,
+                    rules: Arc::new(rules),
+                }),
+            }),
+            last_check: AtomicI64::new(chrono::Utc::now().timestamp_millis()),
+        })
+    }
+
+    /// 同步检查规则文件并在变化时重新加载
+    pub(crate) fn refresh(&self) {
+        self.source.refresh();
+    }
+
+    fn rules(&self) -> Arc<Vec<Arc<CompiledMock>>> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let last = self.last_check.load(Ordering::Relaxed);
+        if now - last >= RELOAD_CHECK_INTERVAL_MS
+            && self
+                .last_check
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            // 文件检查与解析放到阻塞线程池，本次请求继续使用当前规则
+            match tokio::runtime::Handle::try_current() {
+                Ok(rt) => {
+                    let source = self.source.clone();
+                    rt.spawn_blocking(move || source.refresh());
+                }
+                Err(_) => self.refresh(),
+            }
+        }
+        self.source.loaded.read().rules.clone()
+    }
+
+    /// 是否有规则需要匹配请求体（需要时调用方先读取请求体）
+    pub fn needs_body(&self) -> bool {
+        self.rules().iter().any(|r| !r.body.is_empty())
+    }
+
+    /// 按文件中的顺序返回第一条命中的规则
+    pub fn find(
+        &self,
+        method: &Method,
+        path: &str,
+        query: Option<&str>,
+        headers: &HeaderMap,
+        body: Option<&Value>,
+    ) -> Option<MockHit> {
+        self.rules().iter().find_map(|rule| {
+            rule.matches(method, path, query, headers, body).map(|captures| MockHit {
+                rule: rule.clone(),
+                captures,
+            })
+        })
+    }
+}
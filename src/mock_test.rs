@@ -0,0 +1,100 @@
+// Mock 响应（mock）模块的单元测试
+
+#[cfg(test)]
+mod mock_tests {
+    use crate::config;
+    use crate::mock::MockSet;
+    use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+
+    #[test]
+    fn test_mock_rules() {
+        let file = std::env::temp_dir().join(format!("mock-test-{}.toml", uuid::Uuid::new_v4()));
+        let write = |src: &str| std::fs::write(&file, src).unwrap();
+        write(
+            r#"
+            [[mocks]]
+            name = "user"
+            method = "GET"
+            path = '^/api/users/(\d+)$'
+            match_type = "regex"
+            [mocks.response]
+            status = 200
+            headers = { "x-mock" = "$request_method" }
+            body = '{"id": $1, "page": "$arg_page"}'
+
+            [[mocks]]
+            name = "login-admin"
+            method = "POST"
+            path = "/api/login"
+            headers = { "x-env" = "*" }
+            body = [{ path = "$.user.roles[0]", value = "admin" }]
+            [mocks.response]
+            status = 201
+            body = "hello ${body.user.name}"
+            "#,
+        );
+
+        let cfg: config::MockConfig = toml::from_str(&format!("file = {:?}", file.to_str().unwrap())).unwrap();
+        let mocks = MockSet::load(&cfg, "m").expect("mock loaded");
+        let lookup = |name: &str| (name == "request_method").then(|| "GET".to_string());
+
+        let hit = mocks
+            .find(&Method::GET, "/api/users/42", Some("page=3"), &HeaderMap::new(), None)
+            .expect("regex mock");
+        let resp = hit.render(Some("page=3"), None, lookup);
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["x-mock"], "GET");
+        let body = futures_util::FutureExt::now_or_never(axum::body::to_bytes(resp.into_body(), usize::MAX));
+        assert_eq!(body.unwrap().unwrap(), r#"{"id": 42, "page": "3"}"#);
+
+        // 请求体 JSONPath 与请求头存在性
+        assert!(mocks.needs_body());
+        let json: serde_json::Value = serde_json::json!({ "user": { "name": "bob", "roles": ["admin"] } });
+        let mut headers = HeaderMap::new();
+        assert!(mocks.find(&Method::POST, "/api/login", None, &headers, Some(&json)).is_none());
+        headers.insert("x-env", HeaderValue::from_static("dev"));
+        let hit = mocks.find(&Method::POST, "/api/login", None, &headers, Some(&json)).expect("body mock");
+        assert_eq!(hit.rule.name, "login-admin");
+        let other = serde_json::json!({ "user": { "roles": ["guest"] } });
+        assert!(mocks.find(&Method::POST, "/api/login", None, &headers, Some(&other)).is_none());
+
+        // 未命中时交给上游；文件修改后重新加载，语法错误时保留旧规则
+        assert!(mocks.find(&Method::GET, "/api/orders", None, &HeaderMap::new(), None).is_none());
+        write("[[mocks]]\npath = \"/api/orders\"\n");
+        mocks.refresh();
+        assert!(mocks.find(&Method::GET, "/api/orders", None, &HeaderMap::new(), None).is_some());
+        assert!(mocks.find(&Method::GET, "/api/users/1", None, &HeaderMap::new(), None).is_none());
+        write("[[mocks]\n");
+        mocks.refresh();
+        assert!(mocks.find(&Method::GET, "/api/orders", None, &HeaderMap::new(), None).is_some());
+
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[tokio::test]
+    async fn test_mock_reload_in_background() {
+        let file = std::env::temp_dir().join(format!("mock-test-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "[[mocks]]\npath = \"/a\"\n").unwrap();
+        let cfg: config::MockConfig = toml::from_str(&format!("file = {:?}", file.to_str().unwrap())).unwrap();
+        let mocks = MockSet::load(&cfg, "m").expect("mock loaded");
+        let found = |path: &str| mocks.find(&Method::GET, path, None, &HeaderMap::new(), None).is_some();
+
+        std::fs::write(&file, "[[mocks]]\npath = \"/bb\"\n").unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        // 到达检查间隔的请求不等待文件读取，重新加载在后台完成
+        found("/a");
+        let mut reloaded = false;
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            if found("/bb") {
+                reloaded = true;
+                break;
+            }
+        }
+        assert!(reloaded);
+        assert!(!found("/a"));
+
+        let _ = std::fs::remove_file(&file);
+    }
+}
@@ -0,0 +1,564 @@
+use anyhow::{anyhow, Context, Result};
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use axum::body::Body;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use axum::response::Response;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{config, jwt_auth, proxy};
+
+const DEFAULT_CALLBACK_PATH: &str = "/oauth2/callback";
+const DEFAULT_LOGOUT_PATH: &str = "/oauth2/logout";
+const DEFAULT_COOKIE_NAME: &str = "spm_oidc";
+const DEFAULT_SESSION_SECS: u64 = 8 * 3600;
+/// 从跳转登录到回调的最长时间
+const LOGIN_STATE_SECS: i64 = 600;
+/// 访问身份提供方（discovery / token_endpoint）的超时
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 身份提供方的端点与 ID Token 校验器（首次使用时通过 discovery 获取）
+struct Provider {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    verifier: jwt_auth::CompiledJwt,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+/// 跳转登录时写入临时 Cookie，回调时校验
+#[derive(Serialize, Deserialize)]
+struct LoginState {
+    state: String,
+    nonce: String,
+    verifier: String,
+    return_to: String,
+    exp: i64,
+}
+
+/// 会话 Cookie 内容：只保存需要转发给上游的身份请求头
+#[derive(Serialize, Deserialize)]
+struct Session {
+    sub: String,
+    headers: Vec<(String, String)>,
+    exp: i64,
+}
+
+/// 预编译的 OIDC 登录配置
+pub struct CompiledOidc {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    callback_path: String,
+    logout_path: String,
+    // 路由前缀：Cookie 的 Path 与登出后的跳转地址
+    base_path: String,
+    redirect_url: Option<String>,
+    scopes: String,
+    cookie_name: String,
+    session_secs: i64,
+    allowed_emails: Vec<String>,
+    allowed_groups: Vec<String>,
+    groups_claim: String,
+    forward_claims: HashMap<String, String>,
+    forward_names: Vec<HeaderName>,
+    key: LessSafeKey,
+    // 路由与访问策略的摘要，作为 Cookie 加密的附加数据：会话只在签发它的路由上有效
+    binding: String,
+    // 访问身份提供方专用：校验证书，不复用转发上游的客户端
+    client: reqwest::Client,
+    provider: tokio::sync::OnceCell<Provider>,
+}
+
+fn random_token() -> String {
+    let mut buf = [0u8; 32];
+    // 系统随机数不可用时退回 uuid v4
+    if aws_lc_rs::rand::fill(&mut buf).is_err() {
+        buf[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+        buf[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    }
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+fn text_response(status: StatusCode, body: impl Into<String>) -> Response {
+    let mut resp = Response::new(Body::from(body.into()));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    resp
+}
+
+fn redirect(location: &str) -> Response {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::FOUND;
+    if let Ok(v) = HeaderValue::from_str(location) {
+        resp.headers_mut().insert(header::LOCATION, v);
+    }
+    resp
+}
+
+fn request_cookies(headers: &HeaderMap) -> impl Iterator<Item = (&str, &str)> {
+    headers
+        .get_all(header::COOKIE)
+        .into_iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+}
+
+impl CompiledOidc {
+    /// `base_path` 为路由的路径前缀（如 `/grafana`），回调 / 登出路径默认位于其下
+    pub fn compile(cfg: &config::OidcConfig, base_path: &str, label: &str) -> Result<Self> {
+        let base_path = base_path.trim_end_matches('/');
+        let issuer = cfg.issuer.trim().trim_end_matches('/');
+        if !(issuer.starts_with("http://") || issuer.starts_with("https://")) {
+            return Err(anyhow!("路由 {label} 的 oidc.issuer 无效: {issuer}"));
+        }
+        if cfg.client_id.trim().is_empty() {
+            return Err(anyhow!("路由 {label} 的 oidc.client_id 不能为空"));
+        }
+        if cfg.cookie_secret.chars().count() < 16 {
+            return Err(anyhow!("路由 {label} 的 oidc.cookie_secret 至少需要 16 个字符"));
+        }
+        let path_opt = |p: Option<&str>, default: &str, field: &str| -> Result<String> {
+            match p.map(str::trim).filter(|p| !p.is_empty()) {
+                Some(p) if !p.starts_with('/') => Err(anyhow!("路由 {label} 的 oidc.{field} 需以 / 开头: {p}")),
+                Some(p) => Ok(p.to_string()),
+                None => Ok(format!("{base_path}{default}")),
+            }
+        };
+
+        let forward_claims = cfg.forward_claims.clone().unwrap_or_else(|| {
+            HashMap::from([
+                ("X-Auth-Request-User".to_string(), "sub".to_string()),
+                ("X-Auth-Request-Email".to_string(), "email".to_string()),
+            ])
+        });
+        let forward_names = forward_claims
+            .keys()
+            .map(|h| {
+                HeaderName::from_bytes(h.trim().as_bytes())
+                    .map_err(|_| anyhow!("路由 {label} 的 oidc.forward_claims 请求头名无效: {h}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let scopes = if cfg.scopes.is_empty() {
+            "openid email profile".to_string()
+        } else {
+            let mut s: Vec<&str> = cfg.scopes.iter().map(|s| s.trim()).collect();
+            if !s.contains(&"openid") {
+                s.insert(0, "openid");
+            }
+            s.join(" ")
+        };
+
+        let mut claims: Vec<String> = forward_claims.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        claims.sort();
+        let policy = serde_json::json!([
+            label,
+            issuer,
+            cfg.client_id.trim(),
+            cfg.allowed_emails,
+            cfg.allowed_groups,
+            cfg.groups_claim,
+            claims
+        ]);
+        let binding = hex::encode(Sha256::digest(policy.to_string().as_bytes()));
+
+        let digest = Sha256::digest(cfg.cookie_secret.as_bytes());
+        let key = UnboundKey::new(&AES_256_GCM, &digest)
+            .map_err(|_| anyhow!("路由 {label} 的 oidc.cookie_secret 无法生成密钥"))?;
+
+        Ok(Self {
+            issuer: issuer.to_string(),
+            client_id: cfg.client_id.trim().to_string(),
+            client_secret: cfg.client_secret.clone(),
+            callback_path: path_opt(cfg.callback_path.as_deref(), DEFAULT_CALLBACK_PATH, "callback_path")?,
+            logout_path: path_opt(cfg.logout_path.as_deref(), DEFAULT_LOGOUT_PATH, "logout_path")?,
+            base_path: base_path.to_string(),
+            redirect_url: cfg.redirect_url.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(String::from),
+            scopes,
+            cookie_name: cfg
+                .cookie_name
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .unwrap_or(DEFAULT_COOKIE_NAME)
+                .to_string(),
+            session_secs: cfg.session_seconds.unwrap_or(DEFAULT_SESSION_SECS).max(60) as i64,
+            allowed_emails: cfg.allowed_emails.iter().map(|e| e.trim().to_ascii_lowercase()).collect(),
+            allowed_groups: cfg.allowed_groups.iter().map(|g| g.trim().to_string()).collect(),
+            groups_claim: cfg.groups_claim.clone().unwrap_or_else(|| "groups".to_string()),
+            forward_claims,
+            forward_names,
+            key: LessSafeKey::new(key),
+            binding,
+            client: reqwest::Client::builder().timeout(PROVIDER_TIMEOUT).build()?,
+            provider: tokio::sync::OnceCell::new(),
+        })
+    }
+
+    /// 由代理直接应答的回调与登出路径
+    pub fn endpoint_paths(&self) -> [&str; 2] {
+        [&self.callback_path, &self.logout_path]
+    }
+
+    /// 已登录时写入的身份请求头
+    pub fn identity_headers(&self) -> &[HeaderName] {
+        &self.forward_names
+    }
+
+    /// 处理请求：已登录时写入身份请求头并返回 None；
+    /// 否则返回要直接发给客户端的响应（跳转登录、回调、登出、401 / 403）
+    pub async fn handle(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        headers: &mut HeaderMap,
+        https: bool,
+        host: &str,
+    ) -> Option<Response> {
+        let path = uri.path();
+        if path == self.callback_path {
+            return Some(self.callback(uri, headers, https, host).await);
+        }
+        if path == self.logout_path {
+            let mut resp = redirect(&format!("{}/", self.base_path));
+            self.set_cookie(&mut resp, &self.cookie_name, "", 0, https);
+            return Some(resp);
+        }
+
+        // 其他路由签发的同名 Cookie 解密失败，继续尝试下一个
+        let session = request_cookies(headers)
+            .filter(|(name, _)| *name == self.cookie_name)
+            .find_map(|(_, v)| self.open::<Session>(&self.cookie_name, v))
+            .filter(|s| s.exp > chrono::Utc::now().timestamp());
+        if let Some(session) = session {
+            self.strip_own_cookies(headers);
+            for name in &self.forward_names {
+                headers.remove(name);
+            }
+            for (k, v) in &session.headers {
+                if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(k.as_bytes()), HeaderValue::from_str(v)) {
+                    headers.insert(name, value);
+                }
+            }
+            return None;
+        }
+
+        // 只有浏览器页面请求跳转登录，API / 非 GET 请求直接返回 401
+        let wants_html = headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.contains("text/html"));
+        if !(wants_html && (method == Method::GET || method == Method::HEAD)) {
+            return Some(text_response(StatusCode::UNAUTHORIZED, "Unauthorized"));
+        }
+        Some(self.start_login(uri, https, host).await)
+    }
+
+    fn redirect_uri(&self, https: bool, host: &str) -> String {
+        match self.redirect_url.as_deref() {
+            Some(u) => u.to_string(),
+            None => format!("{}://{host}{}", if https { "https" } else { "http" }, self.callback_path),
+        }
+    }
+
+    async fn provider(&self) -> Result<&Provider> {
+        self.provider
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.issuer);
+                let d: Discovery = self
+                    .client
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .with_context(|| format!("获取 OIDC 配置失败: {url}"))?
+                    .json()
+                    .await
+                    .with_context(|| format!("解析 OIDC 配置失败: {url}"))?;
+                let verifier = jwt_auth::CompiledJwt::compile(
+                    &config::JwtAuthConfig {
+                        enabled: true,
+                        algorithms: vec!["RS256".to_string(), "ES256".to_string()],
+                        secret: None,
+                        key_file: None,
+                        jwks_url: Some(d.jwks_uri),
+                        jwks_cache_seconds: None,
+                        issuer: Some(d.issuer),
+                        audience: vec![self.client_id.clone()],
+                        required_claims: vec!["sub".to_string()],
+                        leeway_seconds: None,
+                        forward_claims: Some(self.forward_claims.clone()),
+                    },
+                    &self.issuer,
+                )?;
+                Ok::<_, anyhow::Error>(Provider {
+                    authorization_endpoint: d.authorization_endpoint,
+                    token_endpoint: d.token_endpoint,
+                    verifier,
+                })
+            })
+            .await
+    }
+
+    async fn start_login(&self, uri: &Uri, https: bool, host: &str) -> Response {
+        let provider = match self.provider().await {
+            Ok(p) => p,
+            Err(e) => {
+                proxy::send_log(format!("OIDC 登录失败: {e:#}"));
+                return text_response(StatusCode::BAD_GATEWAY, "OIDC provider unavailable");
+            }
+        };
+
+        let login = LoginState {
+            state: random_token(),
+            nonce: random_token(),
+            verifier: random_token(),
+            return_to: uri.path_and_query().map_or("/", |p| p.as_str()).to_string(),
+            exp: chrono::Utc::now().timestamp() + LOGIN_STATE_SECS,
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(login.verifier.as_bytes()));
+        let mut target = match url::Url::parse(&provider.authorization_endpoint) {
+            Ok(u) => u,
+            Err(e) => {
+                proxy::send_log(format!("OIDC authorization_endpoint 无效: {e}"));
+                return text_response(StatusCode::BAD_GATEWAY, "OIDC provider misconfigured");
+            }
+        };
+        target
+            .query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri(https, host))
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", &login.state)
+            .append_pair("nonce", &login.nonce)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        let mut resp = redirect(target.as_str());
+        let state_cookie = self.state_cookie_name();
+        let sealed = self.seal(&state_cookie, &login);
+        self.set_cookie(&mut resp, &state_cookie, &sealed, LOGIN_STATE_SECS, https);
+        resp
+    }
+
+    async fn callback(&self, uri: &Uri, headers: &HeaderMap, https: bool, host: &str) -> Response {
+        let params: HashMap<String, String> = url::form_urlencoded::parse(uri.query().unwrap_or("").as_bytes())
+            .into_owned()
+            .collect();
+        if let Some(err) = params.get("error") {
+            proxy::send_log(format!("OIDC 登录被拒绝: {err}"));
+            return text_response(StatusCode::UNAUTHORIZED, format!("login failed: {err}"));
+        }
+
+        let state_cookie = self.state_cookie_name();
+        let login = request_cookies(headers)
+            .filter(|(name, _)| *name == state_cookie)
+            .find_map(|(_, v)| self.open::<LoginState>(&state_cookie, v))
+            .filter(|l| l.exp > chrono::Utc::now().timestamp());
+        let (Some(login), Some(code)) = (login, params.get("code")) else {
+            return text_response(StatusCode::BAD_REQUEST, "invalid or expired login state");
+        };
+        if params.get("state") != Some(&login.state) {
+            return text_response(StatusCode::BAD_REQUEST, "state mismatch");
+        }
+
+        let claims = match self.exchange(code, &login, https, host).await {
+            Ok(c) => c,
+            Err(e) => {
+                proxy::send_log(format!("OIDC 回调失败: {e:#}"));
+                return text_response(StatusCode::UNAUTHORIZED, "login failed");
+            }
+        };
+        if let Err(reason) = self.authorize(&claims) {
+            proxy::send_log(format!(
+                "OIDC 用户无权访问: sub={} reason={reason}",
+                claims.get("sub").and_then(Value::as_str).unwrap_or("")
+            ));
+            return text_response(StatusCode::FORBIDDEN, "Forbidden");
+        }
+
+        let Ok(provider) = self.provider().await else {
+            return text_response(StatusCode::BAD_GATEWAY, "OIDC provider unavailable");
+        };
+        let mut forwarded = HeaderMap::new();
+        provider.verifier.apply_forward_headers(&claims, &mut forwarded);
+        let session = Session {
+            sub: claims.get("sub").and_then(Value::as_str).unwrap_or("").to_string(),
+            headers: forwarded
+                .iter()
+                .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.to_string(), v.to_string())))
+                .collect(),
+            exp: chrono::Utc::now().timestamp() + self.session_secs,
+        };
+
+        let return_to = if login.return_to.starts_with('/') && !login.return_to.starts_with("//") {
+            login.return_to.as_str()
+        } else {
+            "/"
+        };
+        let mut resp = redirect(return_to);
+        let sealed = self.seal(&self.cookie_name, &session);
+        self.set_cookie(&mut resp, &self.cookie_name, &sealed, self.session_secs, https);
+        self.set_cookie(&mut resp, &state_cookie, "", 0, https);
+        resp
+    }
+
+    /// 用授权码换取 ID Token 并校验签名、iss / aud / exp 与 nonce
+    async fn exchange(&self, code: &str, login: &LoginState, https: bool, host: &str) -> Result<Value> {
+        let provider = self.provider().await?;
+        let form = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.redirect_uri(https, host))
+            .append_pair("client_id", &self.client_id)
+            .append_pair("client_secret", &self.client_secret)
+            .append_pair("code_verifier", &login.verifier)
+            .finish();
+        let resp = self
+            .client
+            .post(&provider.token_endpoint)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json")
+            .body(form)
+            .send()
+            .await
+            .context("请求 token_endpoint 失败")?;
+        let status = resp.status();
+        let body: Value = resp.json().await.context("解析 token 响应失败")?;
+        if !status.is_success() {
+            return Err(anyhow!("token_endpoint 返回 {}: {body}", status.as_u16()));
+        }
+        let id_token = body
+            .get("id_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("token 响应缺少 id_token"))?;
+        let claims = provider
+            .verifier
+            .verify_token(id_token)
+            .await
+            .map_err(|e| anyhow!("ID Token 校验失败: {}", e.reason()))?;
+        if claims.get("nonce").and_then(Value::as_str) != Some(login.nonce.as_str()) {
+            return Err(anyhow!("ID Token nonce 不匹配"));
+        }
+        Ok(claims)
+    }
+
+    /// 检查 allowed_emails / allowed_groups
+    fn authorize(&self, claims: &Value) -> std::result::Result<(), String> {
+        if !self.allowed_emails.is_empty() {
+            let email = claims
+                .get("email")
+                .and_then(Value::as_str)
+                .map(str::to_ascii_lowercase)
+                .ok_or("missing email")?;
+            if claims.get("email_verified").and_then(Value::as_bool) == Some(false) {
+                return Err(format!("email not verified: {email}"));
+            }
+            let ok = self.allowed_emails.iter().any(|allowed| match allowed.strip_prefix('@') {
+                Some(domain) => email.rsplit_once('@').is_some_and(|(_, d)| d == domain),
+                None => *allowed == email,
+            });
+            if !ok {
+                return Err(format!("email not allowed: {email}"));
+            }
+        }
+        if !self.allowed_groups.is_empty() {
+            let groups: Vec<&str> = match claims.get(&self.groups_claim) {
+                Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+                Some(Value::String(s)) => vec![s.as_str()],
+                _ => Vec::new(),
+            };
+            if !groups.iter().any(|g| self.allowed_groups.iter().any(|a| a == g)) {
+                return Err(format!("groups not allowed: {}", groups.join(",")));
+            }
+        }
+        Ok(())
+    }
+
+    fn state_cookie_name(&self) -> String {
+        format!("{}_state", self.cookie_name)
+    }
+
+    /// 会话 Cookie 不转发给上游
+    fn strip_own_cookies(&self, headers: &mut HeaderMap) {
+        let state_cookie = self.state_cookie_name();
+        let rest: Vec<String> = request_cookies(headers)
+            .filter(|(name, _)| *name != self.cookie_name && *name != state_cookie)
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect();
+        headers.remove(header::COOKIE);
+        if !rest.is_empty() {
+            if let Ok(v) = HeaderValue::from_str(&rest.join("; ")) {
+                headers.insert(header::COOKIE, v);
+            }
+        }
+    }
+
+    fn set_cookie(&self, resp: &mut Response, name: &str, value: &str, max_age: i64, https: bool) {
+        let cookie = format!(
+            "{name}={value}; Path={}/; Max-Age={max_age}; HttpOnly; SameSite=Lax{}",
+            self.base_path,
+            if https { "; Secure" } else { "" }
+        );
+        if let Ok(v) = HeaderValue::from_str(&cookie) {
+            resp.headers_mut().append(header::SET_COOKIE, v);
+        }
+    }
+
+    /// 附加数据：Cookie 名 + 路由绑定，换到其他路由或修改访问策略后旧 Cookie 无法解密
+    fn aad(&self, cookie: &str) -> Vec<u8> {
+        format!("{cookie}\n{}", self.binding).into_bytes()
+    }
+
+    /// AES-256-GCM 加密：base64url(nonce || 密文 || tag)，附加数据见 `aad`
+    fn seal<T: Serialize>(&self, cookie: &str, value: &T) -> String {
+        let mut buf = serde_json::to_vec(value).unwrap_or_default();
+        let mut nonce = [0u8; NONCE_LEN];
+        if aws_lc_rs::rand::fill(&mut nonce).is_err() {
+            nonce.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[..NONCE_LEN]);
+        }
+        if self
+            .key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(self.aad(cookie)), &mut buf)
+            .is_err()
+        {
+            return String::new();
+        }
+        let mut out = nonce.to_vec();
+        out.extend_from_slice(&buf);
+        URL_SAFE_NO_PAD.encode(out)
+    }
+
+    fn open<T: DeserializeOwned>(&self, cookie: &str, value: &str) -> Option<T> {
+        let raw = URL_SAFE_NO_PAD.decode(value.trim()).ok()?;
+        if raw.len() <= NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = raw.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut sealed = sealed.to_vec();
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::from(self.aad(cookie)), &mut sealed)
+            .ok()?;
+        serde_json::from_slice(plain).ok()
+    }
+}
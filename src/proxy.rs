@@ -1,9 +1,7 @@
-use crate::{access_control, config, metrics, ws_proxy, stream_proxy, rate_limit};
-use regex::Regex;
+use crate::{access_control, basic_auth, cache, capture, circuit_breaker, coalesce, config, cors, error_pages, fault, forward_auth, https_redirect, metrics, mirror, route_table, vars, ws_proxy, stream_proxy, rate_limit};
 use anyhow::{anyhow, Context, Result};
-use axum::body::Bytes;
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{connect_info::ConnectInfo, State},
     http::{HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode, Uri},
     response::{IntoResponse, Response},
@@ -13,7 +11,7 @@ use axum::{
 use parking_lot::RwLock;
 use reqwest::redirect::Policy;
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     net::SocketAddr,
     sync::Arc,
     time::Duration,
@@ -83,6 +81,10 @@ struct SmoothLbState {
 }
 
 // 使用 DashMap 替代 RwLock<HashMap>，减少锁竞争
+// 运行中监听器的路由表（listen_addr -> 路由表），路由诊断优先使用，避免重新编译
+static LIVE_ROUTES: once_cell::sync::Lazy<DashMap<String, Arc<route_table::RouteTable>>> =
+    once_cell::sync::Lazy::new(DashMap::new);
+
 static UPSTREAM_LB: once_cell::sync::Lazy<DashMap<String, Arc<RwLock<SmoothLbState>>>> =
     once_cell::sync::Lazy::new(|| DashMap::new());
 
@@ -90,6 +92,8 @@ static UPSTREAM_LB: once_cell::sync::Lazy<DashMap<String, Arc<RwLock<SmoothLbSta
 #[derive(Clone)]
 struct AppState {
     rule: config::ListenRule,
+    // 预编译路由表：正则在监听器启动时一次性编译
+    routes: Arc<route_table::RouteTable>,
     client_follow: reqwest::Client,
     client_nofollow: reqwest::Client,
     app: tauri::AppHandle,
@@ -104,6 +108,11 @@ struct AppState {
     allow_all_lan: bool,
     allow_all_ip: bool,
     whitelist: Arc<[config::WhitelistEntry]>,
+    // 监听器级自定义错误页与上游错误拦截开关
+    error_pages: Arc<error_pages::ErrorPages>,
+    proxy_intercept_errors: bool,
+    // 监听规则级 Basic Auth（未启用时为 None）
+    basic_auth: Option<Arc<basic_auth::BasicAuth>>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -113,21 +122,31 @@ pub struct RuleStartErrorPayload {
 }
 
 // 请求上下文：统一管理请求相关数据，减少参数传递
-struct RequestContext {
+pub(crate) struct RequestContext {
     client_ip: String,
+    remote_ip: String,
+    is_tls: bool,
     started_at: std::time::Instant,
     client_ip_header: String,
     real_ip_header: String,
     host_header: String,
     referer_header: String,
     user_agent_header: String,
+    origin: Option<HeaderValue>,
+    accept_header: String,
+    // 请求 ID：沿用客户端的 X-Request-Id，否则生成新的
+    request_id: String,
     method: Method,
     uri: Uri,
     path: String,
+    // Basic Auth 认证通过的用户名（未认证时为空）
+    auth_user: String,
+    // API Key 名称（未使用 API Key 时为空）
+    api_key: String,
 }
 
 impl RequestContext {
-    fn new(remote: SocketAddr, headers: &HeaderMap, method: Method, uri: Uri) -> Self {
+    pub(crate) fn new(remote: SocketAddr, headers: &HeaderMap, method: Method, uri: Uri, is_tls: bool) -> Self {
         let path = uri.path().to_string();
 
         // 只提取日志/指标需要的少数字段，避免 HeaderMap 全量 clone
@@ -157,15 +176,28 @@ impl RequestContext {
 
         Self {
             client_ip: access_control::client_ip_from_headers(&remote, headers),
+            remote_ip: remote.ip().to_string(),
+            is_tls,
             started_at: std::time::Instant::now(),
             client_ip_header: xff,
             real_ip_header: xri,
             host_header: host,
             referer_header: referer,
             user_agent_header: ua,
+            origin: headers.get(axum::http::header::ORIGIN).cloned(),
+            accept_header: header_to_string(headers, "accept"),
+            request_id: headers
+                .get("x-request-id")
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|s| !s.is_empty() && s.len() <= 128)
+                .map(str::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()),
             method,
             uri,
             path,
+            auth_user: String::new(),
+            api_key: String::new(),
         }
     }
 
@@ -178,6 +210,47 @@ impl RequestContext {
     fn elapsed_s(&self) -> f64 {
         self.started_at.elapsed().as_secs_f64()
     }
+
+    /// 客户端请求的完整 URL（用于抓包导出）
+    fn full_url(&self) -> String {
+        format!(
+            "{}://{}{}",
+            if self.is_tls { "https" } else { "http" },
+            self.host_header,
+            self.uri.path_and_query().map_or(self.path.as_str(), |pq| pq.as_str())
+        )
+    }
+
+    /// 请求级变量查找，供 `vars::expand` 展开 set_headers / 响应头等配置中的 `$var`
+    fn var(&self, name: &str) -> Option<String> {
+        match name {
+            "remote_addr" => Some(self.remote_ip.clone()),
+            "client_ip" => Some(self.client_ip.clone()),
+            "scheme" => Some(if self.is_tls { "https" } else { "http" }.to_string()),
+            "host" | "http_host" => Some(self.host_header.clone()),
+            // 去掉端口后的主机名（IPv6 保留方括号）
+            "host_name" => Some(route_table::normalize_host(&self.host_header).to_string()),
+            "request_method" => Some(self.method.as_str().to_string()),
+            "uri" => Some(self.path.clone()),
+            "request_uri" => Some(
+                self.uri
+                    .path_and_query()
+                    .map(|pq| pq.as_str().to_string())
+                    .unwrap_or_else(|| self.path.clone()),
+            ),
+            "args" | "query_string" => Some(self.uri.query().unwrap_or("").to_string()),
+            "request_id" => Some(self.request_id.clone()),
+            "proxy_add_x_forwarded_for" => {
+                let prior = self.client_ip_header.trim();
+                Some(if prior.is_empty() || prior == "-" {
+                    self.remote_ip.clone()
+                } else {
+                    format!("{}, {}", prior, self.remote_ip)
+                })
+            }
+            _ => None,
+        }
+    }
 }
 
 pub fn start_server(app: tauri::AppHandle) -> Result<()> {
@@ -260,22 +333,25 @@ pub fn start_server(app: tauri::AppHandle) -> Result<()> {
         let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
 
         let handle = tauri::async_runtime::spawn(async move {
-                if let Err(e) = precheck_rule(&rule_clone, &listen_addr_clone).await {
-                    error!("启动监听器失败({listen_addr_clone}): {e}");
-                    send_log(format!("启动监听器失败({listen_addr_clone}): {e}"));
+                let routes = match precheck_rule(&rule_clone, &listen_addr_clone).await {
+                    Ok(t) => t,
+                    Err(e) => {
+                        error!("启动监听器失败({listen_addr_clone}): {e}");
+                        send_log(format!("启动监听器失败({listen_addr_clone}): {e}"));
 
-                let payload = RuleStartErrorPayload {
-                        listen_addr: listen_addr_clone.clone(),
-                    error: e.to_string(),
+                        let payload = RuleStartErrorPayload {
+                            listen_addr: listen_addr_clone.clone(),
+                            error: e.to_string(),
+                        };
+                        let _ = app_handle.emit("server-start-error", payload);
+
+                        *START_FAILED.write() = true;
+                        *IS_RUNNING.write() = false;
+                        *STARTING.write() = false;
+                        let _ = app_handle.emit("status", "stopped");
+                        return;
+                    }
                 };
-                let _ = app_handle.emit("server-start-error", payload);
-
-                *START_FAILED.write() = true;
-                *IS_RUNNING.write() = false;
-                *STARTING.write() = false;
-                let _ = app_handle.emit("status", "stopped");
-                return;
-            }
 
             {
                 let mut started = START_STARTED_COUNT.write();
@@ -313,6 +389,7 @@ pub fn start_server(app: tauri::AppHandle) -> Result<()> {
                 match start_rule_server(
                     app_handle.clone(),
                     rule_clone,
+                    routes,
                     listen_addr_clone.clone(),
                     shutdown_rx,
                 )
@@ -338,6 +415,27 @@ pub fn start_server(app: tauri::AppHandle) -> Result<()> {
         });
         handles.push(ServerHandle { handle, shutdown_tx });
         }
+
+        // HTTP -> HTTPS 跳转伴随监听：失败只上报，不影响主监听的启动状态
+        if let Some(redirect_addr) = https_redirect::redirect_listen_addr(&rule) {
+            let app_handle = app.clone();
+            let rule_clone = rule.clone();
+            let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+            let handle = tauri::async_runtime::spawn(async move {
+                if let Err(e) =
+                    https_redirect::start_redirect_server(rule_clone, redirect_addr.clone(), shutdown_rx).await
+                {
+                    error!("启动 HTTPS 跳转监听失败({redirect_addr}): {e}");
+                    send_log(format!("启动 HTTPS 跳转监听失败({redirect_addr}): {e}"));
+                    let payload = RuleStartErrorPayload {
+                        listen_addr: redirect_addr.clone(),
+                        error: e.to_string(),
+                    };
+                    let _ = app_handle.emit("server-start-error", payload);
+                }
+            });
+            handles.push(ServerHandle { handle, shutdown_tx });
+        }
     }
 
     *SERVERS.write() = handles;
@@ -363,6 +461,7 @@ pub fn stop_server(app: tauri::AppHandle) -> Result<()> {
     for handle in handles {
         handle.abort();
     }
+    LIVE_ROUTES.clear();
 
     let _ = app.emit("status", "stopped");
 
@@ -449,9 +548,12 @@ pub fn send_log_with_app(app: &tauri::AppHandle, message: String) {
     let _ = app.emit("log-line", message);
 }
 
-async fn precheck_rule(rule: &config::ListenRule, listen_addr: &str) -> Result<()> {
+async fn precheck_rule(rule: &config::ListenRule, listen_addr: &str) -> Result<route_table::RouteTable> {
     let (addr, _need_dual_stack) = parse_listen_addr(listen_addr)?;
 
+    // 预编译路由正则：无效模式在此直接报错，而不是在请求时静默跳过
+    let routes = route_table::RouteTable::compile(rule)?;
+
     if rule.ssl_enable {
         let _ = axum_server::tls_rustls::RustlsConfig::from_pem_file(
             rule.cert_file.clone(),
@@ -467,12 +569,13 @@ async fn precheck_rule(rule: &config::ListenRule, listen_addr: &str) -> Result<(
         drop(listener);
     }
 
-    Ok(())
+    Ok(routes)
 }
 
 async fn start_rule_server(
     app: tauri::AppHandle,
     rule: config::ListenRule,
+    mut routes: route_table::RouteTable,
     listen_addr: String,
     shutdown_rx: tokio::sync::oneshot::Receiver<()>,
 ) -> Result<()> {
@@ -481,7 +584,7 @@ async fn start_rule_server(
 
     let cfg = crate::config::get_config();
 
-    let client_builder = || {
+    let client_builder = |connect_timeout_ms: u64, read_timeout_ms: Option<u64>| {
         let mut builder = reqwest::Client::builder()
             .redirect(Policy::limited(10))
             .danger_accept_invalid_certs(true)
@@ -489,8 +592,13 @@ async fn start_rule_server(
             .pool_idle_timeout(Duration::from_secs(cfg.upstream_pool_idle_timeout_sec))
             .tcp_keepalive(Duration::from_secs(60))
             .tcp_nodelay(true)
-            .connect_timeout(Duration::from_millis(cfg.upstream_connect_timeout_ms))
-            .timeout(Duration::from_millis(cfg.upstream_read_timeout_ms));
+            .connect_timeout(Duration::from_millis(connect_timeout_ms));
+
+        // 路由覆盖读取超时时不再套用全局总超时，否则较大的路由值仍会在全局上限处失败
+        builder = match read_timeout_ms {
+            Some(ms) => builder.read_timeout(Duration::from_millis(ms)),
+            None => builder.timeout(Duration::from_millis(cfg.upstream_read_timeout_ms)),
+        };
 
         if !cfg.enable_http2 {
             builder = builder.http1_only();
@@ -499,16 +607,37 @@ async fn start_rule_server(
         builder
     };
 
-    let follow_builder = client_builder();
-    let nofollow_builder = client_builder().redirect(Policy::none());
+    let follow_builder = client_builder(cfg.upstream_connect_timeout_ms, None);
+    let nofollow_builder = client_builder(cfg.upstream_connect_timeout_ms, None).redirect(Policy::none());
 
     let client_follow = follow_builder.build().context("创建上游 HTTP client 失败")?;
 
     let client_nofollow = nofollow_builder.build().context("创建上游 HTTP client 失败")?;
 
+    // 配置了连接/读取超时的路由使用独立 client（这两项只能在 client 上设置）
+    for cr in routes.routes_mut() {
+        let r = &cr.route;
+        if r.connect_timeout_ms.is_none() && r.read_timeout_ms.is_none() {
+            continue;
+        }
+        let connect = r.connect_timeout_ms.unwrap_or(cfg.upstream_connect_timeout_ms);
+        let follow = client_builder(connect, r.read_timeout_ms)
+            .build()
+            .context("创建路由上游 HTTP client 失败")?;
+        let nofollow = client_builder(connect, r.read_timeout_ms)
+            .redirect(Policy::none())
+            .build()
+            .context("创建路由上游 HTTP client 失败")?;
+        cr.clients = Some((follow, nofollow));
+    }
+
+    let error_pages = error_pages::ErrorPages::compile(rule.error_pages.as_ref(), "监听规则")?;
+    let basic_auth = basic_auth::BasicAuth::for_rule(&rule)?.map(Arc::new);
+
     // 缓存常用配置到 AppState
     let state = AppState {
         rule: rule.clone(),
+        routes: Arc::new(routes),
         client_follow,
         client_nofollow,
         app: app.clone(),
@@ -521,6 +650,9 @@ async fn start_rule_server(
         allow_all_lan: cfg.allow_all_lan,
         allow_all_ip: cfg.allow_all_ip,
         whitelist: Arc::from(cfg.whitelist),
+        error_pages: Arc::new(error_pages),
+        proxy_intercept_errors: rule.proxy_intercept_errors.unwrap_or(false),
+        basic_auth,
     };
 
     // 初始化速率限制器（如果在该规则中启用）
@@ -536,8 +668,19 @@ async fn start_rule_server(
         }
     }
 
+    LIVE_ROUTES.insert(listen_addr.trim().to_string(), state.routes.clone());
+
     let router = Router::new().route("/healthz", any(healthz));
     let mut app = router.fallback(any(proxy_handler)).with_state(state);
+
+    // 安全响应头预设（监听器级开关）
+    if rule.security_headers_enabled.unwrap_or(false) {
+        let is_tls = rule.ssl_enable;
+        app = app.layer(axum::middleware::map_response(move |mut resp: Response| async move {
+            cors::apply_security_headers(resp.headers_mut(), is_tls);
+            resp
+        }));
+    }
     
     // 应用压缩中间件（如果启用）
     if cfg.compression_enabled {
@@ -643,7 +786,7 @@ async fn start_rule_server(
 }
 
 /// 解析监听地址，返回主地址和是否需要同时绑定 IPv4/IPv6
-fn parse_listen_addr(s: &str) -> Result<(SocketAddr, bool)> {
+pub(crate) fn parse_listen_addr(s: &str) -> Result<(SocketAddr, bool)> {
     let trimmed = s.trim();
     let (normalized, need_dual_stack) = if trimmed.starts_with(':') {
         // :port 格式：同时监听 IPv4 和 IPv6
@@ -670,156 +813,12 @@ fn parse_listen_addr(s: &str) -> Result<(SocketAddr, bool)> {
     Ok((normalized, need_dual_stack))
 }
 
-async fn healthz() -> impl IntoResponse {
+pub(crate) async fn healthz() -> impl IntoResponse {
     (StatusCode::OK, "OK")
 }
 
-#[inline]
-fn normalize_host(host: &str) -> &str {
-    // 去除端口号，只保留主机名部分
-    host.split(':').next().unwrap_or(host).trim()
-}
-
-/// 检查请求的 Host 是否匹配路由配置的 Host
-/// 支持：
-/// 1. 精确匹配（不区分大小写）
-/// 2. 通配符匹配：*.example.com 匹配 example.com, www.example.com, api.example.com 等
-fn host_matches(route_host: &str, request_host: &str) -> bool {
-    let route_host = normalize_host(route_host);
-    let request_host = normalize_host(request_host);
-    
-    // 如果请求 Host 为空，只匹配路由 Host 也为空的情况
-    if request_host.is_empty() {
-        return route_host.is_empty();
-    }
-    
-    // 精确匹配（不区分大小写）
-    if route_host.eq_ignore_ascii_case(request_host) {
-        return true;
-    }
-    
-    // 通配符匹配：*.example.com
-    if route_host.starts_with("*.") {
-        let suffix = &route_host[2..]; // 去掉 "*."
-        if !suffix.is_empty() && request_host.ends_with(suffix) {
-            // 确保匹配的是完整的域名部分，而不是部分匹配
-            // 例如 *.example.com 应该匹配 www.example.com，但不匹配 evil-example.com
-            let prefix_len = request_host.len() - suffix.len();
-            if prefix_len > 0 {
-                // 检查是否有正确的分隔符（点）
-                let prefix = &request_host[..prefix_len];
-                // 前缀不能包含点（确保是子域名），且不能为空
-                if !prefix.contains('.') && !prefix.is_empty() {
-                    return true;
-                }
-            }
-        }
-    }
-    
-    false
-}
-
-#[inline]
-fn match_route<'a>(
-    routes: &'a [config::Route],
-    request_host: &str,
-    path: &str,
-    method: &Method,
-    headers: &HeaderMap,
-) -> (Option<&'a config::Route>, String) {
-    let host = normalize_host(request_host);
-    
-    let mut best: Option<(&config::Route, bool, usize)> = None; // (route, has_host_constraint, path_len)
-
-    for r in routes {
-        if !r.enabled {
-            continue;
-        }
-
-        // 1. Path 前缀匹配（保持原有逻辑）
-        let p = match r.path.as_deref() {
-            Some(v) => v,
-            None => continue,
-        };
-        if !path.starts_with(p) {
-            continue;
-        }
-
-        // 2. Host 匹配（保持原有逻辑）
-        let host_ok = match r.host.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
-            None => true, // 如果路由没有配置 Host，匹配所有请求
-            Some(h) => host_matches(h, host), // 使用改进的 Host 匹配函数
-        };
-        if !host_ok {
-            continue;
-        }
-
-        // 3. Method 匹配（新增）
-        if let Some(ref methods) = r.methods {
-            if !methods.iter().any(|m| m.eq_ignore_ascii_case(method.as_str())) {
-                continue;
-            }
-        }
-
-        // 4. Header 匹配（新增）
-        if let Some(ref required_headers) = r.headers {
-            let mut headers_ok = true;
-            for (key, expected) in required_headers {
-                let actual = headers
-                    .get(key)
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("");
-                // 支持精确匹配或包含匹配（如果 expected 包含 *）
-                if expected.contains('*') {
-                    let pattern = expected.replace('*', ".*");
-                    if let Ok(re) = regex::Regex::new(&pattern) {
-                        if !re.is_match(actual) {
-                            headers_ok = false;
-                            break;
-                        }
-                    } else {
-                        // 如果正则解析失败，回退到包含匹配
-                        if !actual.contains(expected.replace('*', "").as_str()) {
-                            headers_ok = false;
-                            break;
-                        }
-                    }
-                } else if !actual.eq_ignore_ascii_case(expected.trim()) {
-                    headers_ok = false;
-                    break;
-                }
-            }
-            if !headers_ok {
-                continue;
-            }
-        }
-
-        let cand = (r, r.host.as_ref().is_some(), p.len());
-        best = match best {
-            None => Some(cand),
-            Some((best_r, best_has_host, best_plen)) => {
-                if cand.1 != best_has_host {
-                    if cand.1 { Some(cand) } else { Some((best_r, best_has_host, best_plen)) }
-                } else if cand.2 > best_plen {
-                    Some(cand)
-                } else {
-                    Some((best_r, best_has_host, best_plen))
-                }
-            }
-        };
-    }
-
-    if let Some((r, _, _)) = best {
-        (Some(r), r.id.as_deref().unwrap_or("").to_string())
-    } else {
-        
-        (None, String::new())
-    }
-}
-
-fn upstream_signature(route: &config::Route) -> String {
-    let mut parts: Vec<String> = route
-        .upstreams
+fn upstream_signature(upstreams: &[config::Upstream]) -> String {
+    let mut parts: Vec<String> = upstreams
         .iter()
         .map(|u| format!("{}#{}", u.url, u.weight))
         .collect();
@@ -828,23 +827,24 @@ fn upstream_signature(route: &config::Route) -> String {
 }
 
 fn pick_upstream_smooth(route: &config::Route) -> Option<String> {
-    if route.upstreams.is_empty() {
+    let route_id = route.id.as_deref().unwrap_or("").trim();
+    pick_upstream_from(route_id, &route.upstreams)
+}
+
+/// 在一组上游中做平滑加权轮询；lb_key 为空时固定取第一个
+fn pick_upstream_from(lb_key: &str, upstreams: &[config::Upstream]) -> Option<String> {
+    if upstreams.is_empty() {
         return None;
     }
-    if route.upstreams.len() == 1 {
-        return Some(route.upstreams[0].url.clone());
-    }
-
-    let route_id = route.id.as_deref().unwrap_or("").trim();
-    if route_id.is_empty() {
-        return Some(route.upstreams[0].url.clone());
+    if upstreams.len() == 1 || lb_key.is_empty() {
+        return Some(upstreams[0].url.clone());
     }
 
-    let sig = upstream_signature(route);
+    let sig = upstream_signature(upstreams);
 
     // 使用 DashMap：无需全局读锁，性能更好
     let state_lock = UPSTREAM_LB
-        .entry(route_id.to_string())
+        .entry(lb_key.to_string())
         .or_insert_with(|| {
             Arc::new(RwLock::new(SmoothLbState {
                 signature: String::new(),
@@ -856,9 +856,8 @@ fn pick_upstream_smooth(route: &config::Route) -> Option<String> {
 
     let mut entry = state_lock.write();
 
-    if entry.signature != sig || entry.upstreams.len() != route.upstreams.len() {
-        let ups: Vec<SmoothUpstream> = route
-            .upstreams
+    if entry.signature != sig || entry.upstreams.len() != upstreams.len() {
+        let ups: Vec<SmoothUpstream> = upstreams
             .iter()
             .map(|u| SmoothUpstream {
                 url: u.url.clone(),
@@ -889,41 +888,29 @@ fn pick_upstream_smooth(route: &config::Route) -> Option<String> {
     Some(entry.upstreams[best_idx].url.clone())
 }
 
+/// 校验 Basic Auth：路由自己的配置优先于监听规则。
+/// 通过时返回认证用户名（无需认证时为空串），失败时返回 401 响应的 WWW-Authenticate 头
 #[inline]
-fn is_basic_auth_ok(
-    rule: &config::ListenRule,
-    route: Option<&config::Route>,
+async fn basic_auth_user(
+    state: &AppState,
+    route: Option<&route_table::CompiledRoute>,
     headers: &HeaderMap,
-) -> bool {
-    if let Some(r) = route {
-        if r.exclude_basic_auth.unwrap_or(false) {
-            return true;
-        }
-    }
-
-    if !rule.basic_auth_enable {
-        return true;
-    }
-
-    let Some(auth) = headers.get(axum::http::header::AUTHORIZATION) else {
-        return false;
-    };
-    let Ok(auth) = auth.to_str() else {
-        return false;
-    };
-    let Some(b64) = auth.strip_prefix("Basic ") else {
-        return false;
-    };
-
-    let Ok(decoded) = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, b64) else {
-        return false;
+) -> Result<String, HeaderValue> {
+    let rule = &state.rule;
+    let (auth, realm) = match basic_auth::scope(rule, route.map(|r| &r.route)) {
+        basic_auth::Scope::None => return Ok(String::new()),
+        basic_auth::Scope::Rule => (state.basic_auth.as_deref(), None),
+        basic_auth::Scope::Route => (
+            route.and_then(|r| r.basic_auth.as_ref()),
+            route.and_then(|r| r.route.basic_auth_realm.as_deref()),
+        ),
     };
-    let Ok(s) = String::from_utf8(decoded) else {
-        return false;
+    let realm = realm.or(rule.basic_auth_realm.as_deref());
+    let user = match auth {
+        Some(a) => a.check(headers).await,
+        None => None,
     };
-
-    let expected = format!("{}:{}", rule.basic_auth_username, rule.basic_auth_password);
-    s == expected
+    user.ok_or_else(|| basic_auth::www_authenticate(realm))
 }
 
 
@@ -973,6 +960,35 @@ fn format_access_log(node: &str, ctx: &RequestContext, status: StatusCode) -> St
     )
 }
 
+/// 写入请求日志：除路由、状态码、上游与故障标记外的字段均取自请求上下文
+fn log_request(
+    state: &AppState,
+    ctx: &RequestContext,
+    route_id: &str,
+    status: StatusCode,
+    upstream: &str,
+    fault: &str,
+) {
+    metrics::try_enqueue_request_log(metrics::RequestLogInsert {
+        timestamp: chrono::Utc::now().timestamp(),
+        listen_addr: state.listen_addr.to_string(),
+        client_ip: ctx.client_ip.clone(),
+        remote_ip: ctx.remote_ip.clone(),
+        method: ctx.method.as_str().to_string(),
+        request_path: ctx.path.clone(),
+        request_host: ctx.host_header.clone(),
+        status_code: status.as_u16() as i32,
+        upstream: upstream.to_string(),
+        latency_ms: ctx.elapsed_ms(),
+        user_agent: ctx.user_agent_header.clone(),
+        referer: ctx.referer_header.clone(),
+        matched_route_id: route_id.to_string(),
+        fault: fault.to_string(),
+        auth_user: ctx.auth_user.clone(),
+        api_key: ctx.api_key.clone(),
+    });
+}
+
 // 延迟日志格式化：只在需要时才格式化
 fn push_log_lazy<F>(_app: &tauri::AppHandle, f: F)
 where
@@ -1023,17 +1039,35 @@ async fn proxy_handler(
     State(state): State<AppState>,
     req: Request<Body>,
 ) -> Response {
-    let ctx = RequestContext::new(remote, req.headers(), req.method().clone(), req.uri().clone());
+    let mut ctx = RequestContext::new(
+        remote,
+        req.headers(),
+        req.method().clone(),
+        req.uri().clone(),
+        state.rule.ssl_enable,
+    );
 
     let node = &*state.listen_addr;
-    let (route, matched_route_id) = match_route(
-        &state.rule.routes,
+    let (mut route, mut matched_route_id) = state.routes.match_route(
         &ctx.host_header,
         &ctx.path,
         &ctx.method,
         req.headers()
     );
 
+    // CORS 预检：若按 OPTIONS 未命中配置了 cors 的路由，再按声明的实际方法匹配一次，
+    // 使限定了 methods 的路由也能应答预检
+    let preflight_method = cors::preflight_method(&ctx.method, req.headers());
+    if let Some(m) = preflight_method.as_ref() {
+        if route.as_ref().is_none_or(|m| m.route.cors.is_none()) {
+            let (r2, id2) = state.routes.match_route(&ctx.host_header, &ctx.path, m, req.headers());
+            if r2.as_ref().is_some_and(|m| m.route.cors.is_some()) {
+                route = r2;
+                matched_route_id = id2;
+            }
+        }
+    }
+
     // 0. 访问控制
     if state.http_access_control_enabled {
         if metrics::is_ip_blacklisted(&ctx.client_ip) {
@@ -1055,23 +1089,9 @@ async fn proxy_handler(
                 inbound_headers_line
             ));
 
-            metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-                timestamp: chrono::Utc::now().timestamp(),
-                listen_addr: node.to_string(),
-                client_ip: ctx.client_ip.clone(),
-                remote_ip: remote.ip().to_string(),
-                method: ctx.method.as_str().to_string(),
-                request_path: ctx.path.clone(),
-                request_host: ctx.host_header.clone(),
-                status_code: status.as_u16() as i32,
-                upstream: "".to_string(),
-                latency_ms: ctx.elapsed_ms(),
-                user_agent: ctx.user_agent_header.clone(),
-                referer: ctx.referer_header.clone(),
-                matched_route_id: matched_route_id.clone(),
-            });
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
 
-            return (status, "IP Forbidden").into_response();
+            return error_response(&state, route.as_ref().map(|m| m.route), &ctx, status, "IP Forbidden");
         }
 
         let allowed = access_control::is_allowed_fast(
@@ -1114,23 +1134,9 @@ async fn proxy_handler(
                 state.whitelist.len()
             ));
 
-            metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-                timestamp: chrono::Utc::now().timestamp(),
-                listen_addr: node.to_string(),
-                client_ip: ctx.client_ip.clone(),
-                remote_ip: remote.ip().to_string(),
-                method: ctx.method.as_str().to_string(),
-                request_path: ctx.path.clone(),
-                request_host: ctx.host_header.clone(),
-                status_code: status.as_u16() as i32,
-                upstream: "".to_string(),
-                latency_ms: ctx.elapsed_ms(),
-                user_agent: ctx.user_agent_header.clone(),
-                referer: ctx.referer_header.clone(),
-                matched_route_id: matched_route_id.clone(),
-            });
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
 
-            return (status, "Forbidden").into_response();
+            return error_response(&state, route.as_ref().map(|m| m.route), &ctx, status, "Forbidden");
         }
     }
 
@@ -1167,93 +1173,271 @@ async fn proxy_handler(
                 let status = StatusCode::TOO_MANY_REQUESTS;
                 push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-                metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-                    timestamp: chrono::Utc::now().timestamp(),
-                    listen_addr: node.to_string(),
-                    client_ip: ctx.client_ip.clone(),
-                    remote_ip: remote.ip().to_string(),
-                    method: ctx.method.as_str().to_string(),
-                    request_path: ctx.path.clone(),
-                    request_host: ctx.host_header.clone(),
-                    status_code: status.as_u16() as i32,
-                    upstream: "".to_string(),
-                    latency_ms: ctx.elapsed_ms(),
-                    user_agent: ctx.user_agent_header.clone(),
-                    referer: ctx.referer_header.clone(),
-                    matched_route_id: matched_route_id.clone(),
-                });
+                log_request(&state, &ctx, &matched_route_id, status, "", "");
 
-                return (status, "Rate limit exceeded").into_response();
+                return error_response(&state, route.as_ref().map(|m| m.route), &ctx, status, "Rate limit exceeded");
             }
         }
     }
 
-    // 1. 检查 Basic Auth
-    if !is_basic_auth_ok(&state.rule, route, req.headers()) {
-        let status = StatusCode::UNAUTHORIZED;
-        push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+    // 0.8. CORS 预检：在认证之前直接应答，不转发上游
+    if preflight_method.is_some() {
+        if let Some(policy) = route.as_ref().and_then(|m| m.route.cors.as_ref()) {
+            let resp = policy.preflight_response(req.headers());
+            let status = resp.status();
+            push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-        // 401响应详细日志
-        let inbound_headers_line = req.headers()
-            .iter()
-            .map(|(k, v)| format!("{}: {}", k, v.to_str().unwrap_or("[invalid utf8]")))
-            .collect::<Vec<_>>()
-            .join(" ## ");
-
-        send_log_with_app(&state.app, format!(
-            "反代错误(IN): {} {} -> [Basic Auth失败] status={} | inbound_headers=[{}]",
-            ctx.method.as_str(),
-            ctx.uri,
-            status.as_u16(),
-            inbound_headers_line
-        ));
-
-        metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-            timestamp: chrono::Utc::now().timestamp(),
-            listen_addr: node.to_string(),
-            client_ip: ctx.client_ip.clone(),
-            remote_ip: remote.ip().to_string(),
-            method: ctx.method.as_str().to_string(),
-            request_path: ctx.path.clone(),
-            request_host: ctx.host_header.clone(),
-            status_code: status.as_u16() as i32,
-            upstream: "".to_string(),
-            latency_ms: ctx.elapsed_ms(),
-            user_agent: ctx.user_agent_header.clone(),
-            referer: ctx.referer_header.clone(),
-            matched_route_id: matched_route_id.clone(),
-        });
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
 
-        let mut resp = Response::new(Body::from("Unauthorized"));
-        *resp.status_mut() = status;
-        resp.headers_mut().insert(
-            axum::http::header::WWW_AUTHENTICATE,
-            HeaderValue::from_static("Basic realm=\"SSLProxyManager\""),
-        );
-        return resp;
+            return resp;
+        }
     }
 
-    let Some(route) = route else {
+    // 0.9. return 指令：与 Nginx rewrite 阶段一致，在认证之前直接应答
+    if let Some(m) = route.as_ref() {
+        if let Some(ret) = m.route.return_directive.as_ref() {
+            let mut resp = ret.response(|name| m.capture(name).or_else(|| ctx.var(name)));
+            finish_response_headers(m.route, &ctx, &matched_route_id, "", resp.headers_mut());
+
+            let status = resp.status();
+            push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+            return resp;
+        }
+    }
+
+    // 1. 检查 Basic Auth
+    let auth_user = basic_auth_user(&state, route.as_ref().map(|m| m.route), req.headers()).await;
+    let auth_user = match auth_user {
+        Ok(user) => user,
+        Err(challenge) => {
+            let status = StatusCode::UNAUTHORIZED;
+            push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+            // 401响应详细日志
+            let inbound_headers_line = req.headers()
+                .iter()
+                .map(|(k, v)| format!("{}: {}", k, v.to_str().unwrap_or("[invalid utf8]")))
+                .collect::<Vec<_>>()
+                .join(" ## ");
+
+            send_log_with_app(&state.app, format!(
+                "反代错误(IN): {} {} -> [Basic Auth失败] status={} | inbound_headers=[{}]",
+                ctx.method.as_str(),
+                ctx.uri,
+                status.as_u16(),
+                inbound_headers_line
+            ));
+
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+            let mut resp = error_response(&state, route.as_ref().map(|m| m.route), &ctx, status, "Unauthorized");
+            resp.headers_mut().insert(axum::http::header::WWW_AUTHENTICATE, challenge);
+            return resp;
+        }
+    };
+    ctx.auth_user = auth_user;
+
+    let Some(matched) = route else {
         let status = StatusCode::NOT_FOUND;
         push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-        metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-            timestamp: chrono::Utc::now().timestamp(),
-            listen_addr: node.to_string(),
-            client_ip: ctx.client_ip.clone(),
-            remote_ip: remote.ip().to_string(),
-            method: ctx.method.as_str().to_string(),
-            request_path: ctx.path.clone(),
-            request_host: ctx.host_header.clone(),
-            status_code: status.as_u16() as i32,
-            upstream: "".to_string(),
-            latency_ms: ctx.elapsed_ms(),
-            user_agent: ctx.user_agent_header.clone(),
-            referer: ctx.referer_header.clone(),
-            matched_route_id: matched_route_id.clone(),
-        });
+        log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+        return error_response(&state, None, &ctx, status, "No route");
+    };
+    let compiled = matched.route;
+    let route = &compiled.route;
+
+    let mut req = req;
+
+    // 1.1 OIDC 登录：未登录时跳转身份提供方，回调 / 登出路径由代理直接应答
+    if let Some(oidc) = compiled.oidc.as_ref() {
+        let https = state.rule.ssl_enable;
+        if let Some(resp) = oidc
+            .handle(&ctx.method, &ctx.uri, req.headers_mut(), https, &ctx.host_header)
+            .await
+        {
+            let status = resp.status();
+            push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+            if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+                send_log_with_app(&state.app, format!(
+                    "反代错误(IN): {} {} -> [OIDC认证失败] status={}",
+                    ctx.method.as_str(),
+                    ctx.uri,
+                    status.as_u16()
+                ));
+            }
+
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+            return resp;
+        }
+    }
+
+    // 1.2 JWT 校验：通过后把 forward_claims 写入请求头转发给上游
+    if let Some(jwt) = compiled.jwt_auth.as_ref() {
+        match jwt.verify(req.headers()).await {
+            Ok(claims) => jwt.apply_forward_headers(&claims, req.headers_mut()),
+            Err(reject) => {
+                let status = StatusCode::UNAUTHORIZED;
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+                send_log_with_app(&state.app, format!(
+                    "反代错误(IN): {} {} -> [JWT校验失败] status={} | reason={}",
+                    ctx.method.as_str(),
+                    ctx.uri,
+                    status.as_u16(),
+                    reject.reason()
+                ));
+
+                log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+                let mut resp = error_response(&state, Some(compiled), &ctx, status, "Unauthorized");
+                resp.headers_mut()
+                    .insert(axum::http::header::WWW_AUTHENTICATE, reject.www_authenticate());
+                return resp;
+            }
+        }
+    }
+
+    // 1.3 外部认证：2xx 放行并复制 copy_headers；401 / 403 把认证服务的响应返回给客户端
+    if let Some(fa) = compiled.forward_auth.as_ref() {
+        let auth_req = forward_auth::AuthRequest {
+            method: &ctx.method,
+            uri: ctx.uri.path_and_query().map_or("/", |p| p.as_str()),
+            headers: req.headers(),
+            proto: if state.rule.ssl_enable { "https" } else { "http" },
+            host: &ctx.host_header,
+            client_ip: &ctx.client_ip,
+        };
+        let denied = match fa.check(&state.client_nofollow, &auth_req).await {
+            Ok(decision) => match decision.as_ref() {
+                forward_auth::AuthDecision::Allow(copied) => {
+                    fa.apply_copied_headers(copied, req.headers_mut());
+                    None
+                }
+                forward_auth::AuthDecision::Deny { status, .. } => {
+                    send_log_with_app(&state.app, format!(
+                        "反代错误(IN): {} {} -> [外部认证拒绝] status={}",
+                        ctx.method.as_str(),
+                        ctx.uri,
+                        status.as_u16()
+                    ));
+                    decision.deny_response()
+                }
+            },
+            Err(e) => {
+                send_log_with_app(&state.app, format!(
+                    "反代错误(IN): {} {} -> [外部认证失败] error={}",
+                    ctx.method.as_str(),
+                    ctx.uri,
+                    e
+                ));
+                Some(error_response(
+                    &state,
+                    Some(compiled),
+                    &ctx,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Auth service error",
+                ))
+            }
+        };
 
-        return (status, "No route").into_response();
+        if let Some(resp) = denied {
+            let status = resp.status();
+            push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+            log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+            return resp;
+        }
+    }
+
+    // 1.4 API Key：按 Key 校验路由权限、限流与每日限额，Key 名称转发给上游并记入请求日志
+    if let Some(ak) = compiled.api_key.as_ref() {
+        match ak.authenticate(&matched_route_id, req.headers_mut(), &mut ctx.uri) {
+            Ok(name) => ctx.api_key = name,
+            Err(reject) => {
+                let status = reject.status();
+                ctx.api_key = reject.key_name().to_string();
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+                send_log_with_app(&state.app, format!(
+                    "反代错误(IN): {} {} -> [API Key校验失败] status={} | reason={}",
+                    ctx.method.as_str(),
+                    ctx.uri,
+                    status.as_u16(),
+                    reject.reason()
+                ));
+
+                log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+                let mut resp = error_response(&state, Some(compiled), &ctx, status, reject.reason());
+                if let Some(v) = reject.retry_after() {
+                    resp.headers_mut().insert(axum::http::header::RETRY_AFTER, v);
+                }
+                return resp;
+            }
+        }
+    }
+
+    // 1.5 Mock 响应：命中规则时直接返回；规则需要匹配请求体时先读取请求体，未命中时原样交给后续流程
+    let req = match compiled.mock.as_ref() {
+        Some(mocks) => {
+            let (parts, body) = req.into_parts();
+            let (body, json) = if mocks.needs_body() {
+                let max_body_size = route.max_body_size.unwrap_or(state.max_body_size);
+                let limit = if max_body_size > 0 { max_body_size } else { usize::MAX };
+                let bytes = match axum::body::to_bytes(body, limit).await {
+                    Ok(b) => b,
+                    Err(e) => {
+                        let status = if is_length_limit_error(&e) {
+                            StatusCode::PAYLOAD_TOO_LARGE
+                        } else {
+                            StatusCode::BAD_REQUEST
+                        };
+                        push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+                        log_request(&state, &ctx, &matched_route_id, status, "", "");
+
+                        return error_response(
+                            &state,
+                            Some(compiled),
+                            &ctx,
+                            status,
+                            format!("read request body failed: {e}"),
+                        );
+                    }
+                };
+                let json = serde_json::from_slice::<serde_json::Value>(&bytes).ok();
+                (Body::from(bytes), json)
+            } else {
+                (body, None)
+            };
+
+            if let Some(hit) = mocks.find(&parts.method, &ctx.path, ctx.uri.query(), &parts.headers, json.as_ref()) {
+                if hit.rule.delay_ms > 0 {
+                    tokio::time::sleep(Duration::from_millis(hit.rule.delay_ms)).await;
+                }
+                let mut resp = hit.render(ctx.uri.query(), json.as_ref(), |name| {
+                    matched.capture(name).or_else(|| key_var(&ctx, &parts.headers, name))
+                });
+                finish_response_headers(compiled, &ctx, &matched_route_id, "", resp.headers_mut());
+
+                let status = resp.status();
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+                log_request(&state, &ctx, &matched_route_id, status, &format!("mock {}", hit.rule.name), "");
+
+                return resp;
+            }
+            Request::from_parts(parts, body)
+        }
+        None => req,
     };
 
     // 2. 优先处理静态资源
@@ -1263,26 +1447,13 @@ async fn proxy_handler(
         match serve_dir.oneshot(req).await {
             Ok(response) => {
                 let status = response.status();
-                let response = response.map(Body::new);
+                let mut response = response.map(Body::new);
+                finish_response_headers(compiled, &ctx, &matched_route_id, "", response.headers_mut());
 
                 if status.is_success() || status.is_redirection() {
                     push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-                    metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-                        timestamp: chrono::Utc::now().timestamp(),
-                        listen_addr: node.to_string(),
-                        client_ip: ctx.client_ip.clone(),
-                        remote_ip: remote.ip().to_string(),
-                        method: ctx.method.as_str().to_string(),
-                        request_path: ctx.path.clone(),
-                        request_host: ctx.host_header.clone(),
-                        status_code: status.as_u16() as i32,
-                        upstream: "".to_string(),
-                        latency_ms: ctx.elapsed_ms(),
-                        user_agent: ctx.user_agent_header.clone(),
-                        referer: ctx.referer_header.clone(),
-                        matched_route_id: matched_route_id.clone(),
-                    });
+                    log_request(&state, &ctx, &matched_route_id, status, "", "");
 
                     return response;
                 }
@@ -1300,25 +1471,12 @@ async fn proxy_handler(
                             axum::http::header::CONTENT_TYPE,
                             HeaderValue::from_static("text/html; charset=utf-8"),
                         );
+                        finish_response_headers(compiled, &ctx, &matched_route_id, "", resp.headers_mut());
 
                         let status = StatusCode::OK;
                         push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-                        metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-                            timestamp: chrono::Utc::now().timestamp(),
-                            listen_addr: node.to_string(),
-                            client_ip: ctx.client_ip.clone(),
-                            remote_ip: remote.ip().to_string(),
-                            method: ctx.method.as_str().to_string(),
-                            request_path: ctx.path.clone(),
-                            request_host: ctx.host_header.clone(),
-                            status_code: status.as_u16() as i32,
-                            upstream: "".to_string(),
-                            latency_ms: ctx.elapsed_ms(),
-                            user_agent: ctx.user_agent_header.clone(),
-                            referer: ctx.referer_header.clone(),
-                            matched_route_id: matched_route_id.clone(),
-                        });
+                        log_request(&state, &ctx, &matched_route_id, status, "", "");
 
                         return resp;
                     }
@@ -1332,45 +1490,35 @@ async fn proxy_handler(
         let status = StatusCode::NOT_FOUND;
         push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-        metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-            timestamp: chrono::Utc::now().timestamp(),
-            listen_addr: node.to_string(),
-            client_ip: ctx.client_ip.clone(),
-            remote_ip: remote.ip().to_string(),
-            method: ctx.method.as_str().to_string(),
-            request_path: ctx.path.clone(),
-            request_host: ctx.host_header.clone(),
-            status_code: status.as_u16() as i32,
-            upstream: "".to_string(),
-            latency_ms: ctx.elapsed_ms(),
-            user_agent: ctx.user_agent_header.clone(),
-            referer: ctx.referer_header.clone(),
-            matched_route_id: matched_route_id.clone(),
-        });
+        log_request(&state, &ctx, &matched_route_id, status, "", "");
 
-        return (status, "Static file not found").into_response();
+        let mut resp = error_response(&state, Some(compiled), &ctx, status, "Static file not found");
+        finish_response_headers(compiled, &ctx, &matched_route_id, "", resp.headers_mut());
+        return resp;
     }
 
-    // 3. 处理反代逻辑
-    if let Some(mut upstream_url) = pick_upstream_smooth(route) {
-        // 3.1 URL 重写（在构建目标URL之前）
-        let mut final_uri = ctx.uri.clone();
-        if let Some(rules) = route.url_rewrite_rules.as_ref() {
-            for rule in rules {
-                if !rule.enabled {
-                    continue;
-                }
-                if let Ok(re) = Regex::new(&rule.pattern) {
-                    let original = final_uri.to_string();
-                    let rewritten = re.replace_all(&original, &rule.replacement);
-                    if rewritten != original {
-                        if let Ok(new_uri) = rewritten.parse::<Uri>() {
-                            final_uri = new_uri;
-                        }
-                    }
-                }
-            }
-        }
+    // 3. 处理反代逻辑：先按分流规则选择上游组，均未命中时使用路由的 upstreams
+    let split = compiled.splits.iter().find(|s| {
+        s.matches(req.headers(), ctx.uri.query(), &ctx.client_ip, |name| {
+            key_var(&ctx, req.headers(), name)
+        })
+    });
+    if !compiled.splits.is_empty() {
+        metrics::record_split(&matched_route_id, split.map_or("default", |s| s.name.as_str()));
+    }
+    let picked = match split {
+        Some(s) => pick_upstream_from(&format!("{}#{}", matched_route_id, s.name), &s.upstreams),
+        None => pick_upstream_smooth(route),
+    };
+    // 命中分流时在请求日志的 upstream 中记录分流名称
+    let upstream_log = |u: &str| match split {
+        Some(s) => format!("{} {}", s.name, u),
+        None => u.to_string(),
+    };
+
+    if let Some(mut upstream_url) = picked {
+        // 3.1 URL 重写（在构建目标URL之前，正则已预编译）
+        let final_uri = compiled.rewrite_uri(&ctx.uri);
 
         // 支持在 upstream URL 中使用 $server_port 占位符（例如 http://192.168.1.121:$server_port）
         if upstream_url.contains("$server_port") {
@@ -1378,10 +1526,21 @@ async fn proxy_handler(
             upstream_url = upstream_url.replace("$server_port", &port_str);
         }
 
+        // proxy_pass_path 支持引用正则捕获组；正则路由下它作为完整的上游路径
+        let proxy_pass_path = route
+            .proxy_pass_path
+            .as_deref()
+            .map(|pp| vars::expand(pp, |name| matched.capture(name)));
+        let route_path = if matched.is_regex() {
+            Some(final_uri.path())
+        } else {
+            route.path.as_deref()
+        };
+
         let target = match build_upstream_url(
             &upstream_url,
-            route.path.as_deref(),
-            route.proxy_pass_path.as_deref(),
+            route_path,
+            proxy_pass_path.as_deref(),
             &final_uri,
         ) {
             Ok(u) => u,
@@ -1389,206 +1548,233 @@ async fn proxy_handler(
                 let status = StatusCode::BAD_GATEWAY;
                 push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-                metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-                    timestamp: chrono::Utc::now().timestamp(),
-                    listen_addr: node.to_string(),
-                    client_ip: ctx.client_ip.clone(),
-                    remote_ip: remote.ip().to_string(),
-                    method: ctx.method.as_str().to_string(),
-                    request_path: ctx.path.clone(),
-                    request_host: ctx.host_header.clone(),
-                    status_code: status.as_u16() as i32,
-                    upstream: upstream_url.clone(),
-                    latency_ms: ctx.elapsed_ms(),
-                    user_agent: ctx.user_agent_header.clone(),
-                    referer: ctx.referer_header.clone(),
-                    matched_route_id: matched_route_id.clone(),
-                });
+                log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&upstream_url), "");
 
-                return (status, format!("bad upstream url: {e}")).into_response();
+                return error_response(&state, Some(compiled), &ctx, status, format!("bad upstream url: {e}"));
             }
         };
 
-        let client = if route.follow_redirects {
-            state.client_follow.clone()
-        } else {
-            state.client_nofollow.clone()
-        };
+        // 故障注入：访问上游前按配置延迟、中止或断开连接，注入情况单独记录到请求日志的 fault 列
+        let fault_plan = compiled.fault.as_ref().and_then(|f| f.plan(req.headers()));
+        let fault_log = fault_plan.as_ref().map(|p| p.label()).unwrap_or_default();
+        if let Some(plan) = fault_plan.as_ref() {
+            if let Some(delay) = plan.delay {
+                tokio::time::sleep(delay).await;
+            }
+            if plan.reset || plan.abort.is_some() {
+                // 连接重置按 Nginx 惯例记为 444
+                let status = match plan.abort {
+                    Some(s) if !plan.reset => s,
+                    _ => StatusCode::from_u16(444).unwrap_or(StatusCode::BAD_GATEWAY),
+                };
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-        let (req_parts, req_body_axum) = req.into_parts();
-        let inbound_headers = req_parts.headers.clone();
-        let method_up = req_parts.method.clone();
+                log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&upstream_url), &fault_log);
 
-        // 读取请求体
-        let (reqwest_body, req_body_size) = if state.stream_proxy {
-            let body_stream = req_body_axum.into_data_stream();
-            (reqwest::Body::wrap_stream(body_stream), None)
-        } else {
-            let bytes = match axum::body::to_bytes(req_body_axum, state.max_body_size).await {
-                Ok(b) => b,
-                Err(e) => {
-                    return (
-                        StatusCode::BAD_REQUEST,
-                        format!("read request body failed: {e}"),
-                    )
-                        .into_response();
+                if plan.reset {
+                    return fault::reset_response();
                 }
-            };
+                return error_response(&state, Some(compiled), &ctx, status, "fault injected");
+            }
+        }
 
-            // 3.2 请求体修改（如果配置了替换规则）
-            let final_bytes = if let Some(rules) = route.request_body_replace.as_ref() {
-                if let Ok(body_str) = String::from_utf8(bytes.to_vec()) {
-                    let mut modified_body = body_str;
-                    for rule in rules {
-                        if !rule.enabled {
-                            continue;
-                        }
-                        
-                        // 检查 Content-Type 过滤
-                        if let Some(ref content_types) = rule.content_types {
-                            if !content_types.trim().is_empty() {
-                                // 获取请求的 Content-Type
-                                let request_content_type = inbound_headers
-                                    .get(axum::http::header::CONTENT_TYPE)
-                                    .and_then(|v: &HeaderValue| v.to_str().ok())
-                                    .unwrap_or("")
-                                    .to_lowercase();
-                                
-                                // 解析 Content-Type，去除 charset 等参数
-                                let pure_content_type = request_content_type
-                                    .split(';')
-                                    .next()
-                                    .unwrap_or("")
-                                    .trim();
-                                
-                                // 检查是否匹配任一 Content-Type
-                                let content_type_list: Vec<String> = content_types
-                                    .split(',')
-                                    .map(|s| s.trim().to_lowercase())
-                                    .collect();
-                                
-                                if !content_type_list.iter().any(|ct| ct.as_str() == pure_content_type) {
-                                    continue; // 不匹配，跳过此规则
-                                }
-                            }
-                        }
-                        
-                        // 执行替换
-                        if rule.use_regex {
-                            if let Ok(re) = Regex::new(&rule.find) {
-                                modified_body = re.replace_all(&modified_body, &rule.replace).to_string();
-                            }
-                        } else {
-                            modified_body = modified_body.replace(&rule.find, &rule.replace);
-                        }
-                    }
-                    Bytes::from(modified_body.into_bytes())
-                } else {
-                    bytes
-                }
-            } else {
-                bytes
-            };
+        // 抓包：命中会话时强制缓冲请求体与响应体，以便完整记录
+        let capture = capture::matching(&matched_route_id, &ctx.client_ip, &ctx.path);
+        let stream_mode = state.stream_proxy && capture.is_none();
 
-            let len = final_bytes.len();
-            (reqwest::Body::from(final_bytes), Some(len))
+        let (client_follow, client_nofollow) = compiled
+            .clients
+            .as_ref()
+            .map(|(f, n)| (f, n))
+            .unwrap_or((&state.client_follow, &state.client_nofollow));
+        let client = if route.follow_redirects {
+            client_follow.clone()
+        } else {
+            client_nofollow.clone()
         };
 
-        // 构造最终 headers（使用预计算的 SKIP_HEADERS）
-        let mut final_headers = HeaderMap::new();
+        // 请求/响应体大小上限：路由配置优先，0 表示不限制
+        let max_body_size = route.max_body_size.unwrap_or(state.max_body_size);
+        let max_response_body_size = route.max_response_body_size.unwrap_or(state.max_response_body_size);
 
-        for (k, v) in inbound_headers.iter() {
-            if SKIP_HEADERS.contains(k) || is_hop_header_fast(k.as_str()) {
-                continue;
-            }
-            final_headers.append(k.clone(), v.clone());
-        }
+        let declared_len = req
+            .headers()
+            .get(axum::http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<usize>().ok());
+        if max_body_size > 0 && declared_len.is_some_and(|n| n > max_body_size) {
+            let status = StatusCode::PAYLOAD_TOO_LARGE;
+            push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+            log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&upstream_url), &fault_log);
 
-        // Host header
-        if let Some(h) = inbound_headers.get(axum::http::header::HOST) {
-            final_headers.insert(axum::http::header::HOST, h.clone());
+            return error_response(
+                &state,
+                Some(compiled),
+                &ctx,
+                status,
+                format!("request body too large (limit={max_body_size} bytes)"),
+            );
         }
 
-        // 转发头
-        {
-            let remote_ip = remote.ip().to_string();
-            if let Ok(v) = HeaderValue::from_str(&remote_ip) {
-                final_headers.insert(HeaderName::from_static("x-real-ip"), v);
-            }
+        let (req_parts, req_body_axum) = req.into_parts();
+        let inbound_headers = req_parts.headers.clone();
+        let method_up = req_parts.method.clone();
+        let http_version = format!("{:?}", req_parts.version);
+
+        // 读取请求体（非流式时保留一份用于流量镜像与抓包，Bytes clone 为引用计数）
+        let (reqwest_body, req_body_size, mirror_body) = if stream_mode {
+            // 流式转发时只镜像无请求体的请求
+            let no_body = !inbound_headers.contains_key(axum::http::header::CONTENT_LENGTH)
+                && !inbound_headers.contains_key(axum::http::header::TRANSFER_ENCODING);
+            // 流式转发同样限制请求体大小：超出时中断上游请求并返回 413
+            (limited_body(req_body_axum, max_body_size), None, no_body.then(Bytes::new))
+        } else {
+            let limit = if max_body_size > 0 { max_body_size } else { usize::MAX };
+            let bytes = match axum::body::to_bytes(req_body_axum, limit).await {
+                Ok(b) => b,
+                Err(e) => {
+                    let status = if is_length_limit_error(&e) {
+                        StatusCode::PAYLOAD_TOO_LARGE
+                    } else {
+                        StatusCode::BAD_REQUEST
+                    };
+                    push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-            let prior = inbound_headers
-                .get("x-forwarded-for")
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.trim())
-                .filter(|s| !s.is_empty());
+                    log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&upstream_url), &fault_log);
 
-            let combined = match prior {
-                Some(p) => format!("{}, {}", p, remote_ip),
-                None => remote_ip,
+                    return error_response(
+                        &state,
+                        Some(compiled),
+                        &ctx,
+                        status,
+                        format!("read request body failed: {e}"),
+                    );
+                }
             };
 
-            if let Ok(v) = HeaderValue::from_str(&combined) {
-                final_headers.insert(HeaderName::from_static("x-forwarded-for"), v);
-            }
-        }
-
-        final_headers.insert(
-            HeaderName::from_static("x-forwarded-proto"),
-            HeaderValue::from_static(if state.rule.ssl_enable { "https" } else { "http" }),
-        );
+            // 3.2 请求体修改（如果配置了替换规则）
+            let final_bytes = route_table::apply_body_replace(
+                &compiled.request_body_replace,
+                bytes,
+                inbound_headers.get(axum::http::header::CONTENT_TYPE),
+            );
 
-        final_headers.insert(axum::http::header::ACCEPT_ENCODING, HeaderValue::from_static(""));
+            let len = final_bytes.len();
+            (reqwest::Body::from(final_bytes.clone()), Some(len), Some(final_bytes))
+        };
 
-        if !final_headers.contains_key(axum::http::header::CONTENT_TYPE) {
-            if let Some(ct) = inbound_headers.get(axum::http::header::CONTENT_TYPE) {
-                final_headers.insert(axum::http::header::CONTENT_TYPE, ct.clone());
+        let final_headers = build_outbound_headers(&state.rule, &matched, &ctx, &inbound_headers, remote);
+
+        // 响应缓存：仅 GET/HEAD；会向上游转发 Authorization 的请求不走缓存
+        let mut cache_key = None;
+        let mut cache_status = None;
+        let mut stale_hit = None;
+        let mut _cache_lock = None;
+        if let Some(cache) = compiled.cache.as_ref() {
+            let cacheable = (ctx.method == Method::GET || ctx.method == Method::HEAD)
+                && !final_headers.contains_key(axum::http::header::AUTHORIZATION)
+                && !cache::request_no_store(&inbound_headers);
+            if !cacheable {
+                cache_status = Some("BYPASS");
+            } else {
+                let key = vars::expand(cache.key_template(), |name| key_var(&ctx, &inbound_headers, name));
+                if !cache::request_no_cache(&inbound_headers) {
+                    let mut lookup = cache.lookup(&key, &inbound_headers).await;
+                    if matches!(lookup, cache::Lookup::Miss) {
+                        // 同一 key 的并发未命中只放行一个回源请求，其余等待其写入后直接读缓存
+                        _cache_lock = cache.lock(&key).await;
+                        lookup = cache.lookup(&key, &inbound_headers).await;
+                    }
+                    match lookup {
+                        cache::Lookup::Fresh(hit) => {
+                            return cached_response(&state, compiled, &ctx, &matched_route_id, hit, "HIT");
+                        }
+                        cache::Lookup::Stale(hit) if hit.can_revalidate() => {
+                            spawn_cache_revalidate(
+                                cache.clone(),
+                                key,
+                                client.clone(),
+                                target.clone(),
+                                final_headers,
+                                inbound_headers,
+                                max_response_body_size,
+                            );
+                            return cached_response(&state, compiled, &ctx, &matched_route_id, hit, "STALE");
+                        }
+                        cache::Lookup::Stale(hit) => stale_hit = Some(hit),
+                        cache::Lookup::Miss => {}
+                    }
+                }
+                cache_key = Some(key);
+                cache_status = Some("MISS");
             }
         }
 
-        // set_headers
-        if let Some(map) = route.set_headers.as_ref() {
-            for (k, v) in map {
-                let key = k.trim();
-                if key.is_empty() || is_hop_header_fast(key) {
-                    continue;
+        // 请求合并：相同 key 的并发请求只回源一次，其余等待并共享首个请求的上游响应
+        let mut coalesce_leader = None;
+        if let Some(coalescer) = compiled.coalesce.as_ref() {
+            // 带 Authorization、Cookie 或认证注入的身份请求头时响应可能因用户而异，不合并
+            if (ctx.method == Method::GET || ctx.method == Method::HEAD)
+                && !final_headers.contains_key(axum::http::header::AUTHORIZATION)
+                && coalescer.shareable(&inbound_headers, compiled.identity_headers())
+            {
+                let key = vars::expand(coalescer.key_template(), |name| key_var(&ctx, &inbound_headers, name));
+                match coalescer.join(key) {
+                    coalesce::Role::Leader(guard) => coalesce_leader = Some(guard),
+                    coalesce::Role::Follower(rx) => {
+                        // 超时或首个请求失败时继续向下自行回源
+                        if let Some(shared) = coalescer.wait(rx).await {
+                            return buffered_response(&state, compiled, &ctx, &matched_route_id, &shared);
+                        }
+                    }
                 }
+            }
+        }
 
-                let expanded =
-                    expand_proxy_header_value(v, &remote, &inbound_headers, state.rule.ssl_enable);
-
-                let name = match HeaderName::from_bytes(key.as_bytes()) {
-                    Ok(n) => n,
-                    Err(_) => continue,
-                };
-
-                if expanded.is_empty() {
-                    final_headers.insert(name, HeaderValue::from_static(""));
-                    continue;
+        // 熔断器：上游熔断期间直接返回 503（有可用的 stale-if-error 缓存时返回缓存），不再等待超时
+        let breaker_cfg = route
+            .circuit_breaker
+            .as_ref()
+            .or(state.rule.circuit_breaker.as_ref())
+            .filter(|c| c.enabled);
+        let mut breaker_permit = None;
+        if let Some(cfg) = breaker_cfg {
+            breaker_permit = circuit_breaker::acquire(&state.app, &upstream_url, cfg);
+            if breaker_permit.is_none() {
+                if let Some(hit) = stale_hit.filter(|h| h.usable_on_error()) {
+                    return cached_response(&state, compiled, &ctx, &matched_route_id, hit, "STALE");
                 }
 
-                let value = match HeaderValue::from_str(&expanded) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                };
+                let status = StatusCode::SERVICE_UNAVAILABLE;
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
 
-                final_headers.insert(name, value);
-            }
-        }
+                log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&upstream_url), &fault_log);
 
-        // 移除 Authorization（如需要）
-        if state.rule.basic_auth_enable && !state.rule.basic_auth_forward_header {
-            final_headers.remove(axum::http::header::AUTHORIZATION);
+                return error_response(&state, Some(compiled), &ctx, status, "upstream circuit open");
+            }
         }
 
-        // 3.3 移除指定的请求头
-        if let Some(headers_to_remove) = route.remove_headers.as_ref() {
-            for header_name in headers_to_remove {
-                let trimmed = header_name.trim();
-                if trimmed.is_empty() {
+        // 流量镜像：按比例异步复制请求，主请求结束后回传状态码用于比对
+        let mut mirror_status_txs = Vec::new();
+        if let Some(body) = mirror_body.as_ref() {
+            for m in compiled.mirrors.iter() {
+                if !m.accepts_body(body.len()) || !m.sampled() {
                     continue;
                 }
-                if let Ok(name) = HeaderName::from_bytes(trimmed.as_bytes()) {
-                    final_headers.remove(name);
+                let mirror_target = match build_upstream_url(&m.url, route_path, proxy_pass_path.as_deref(), &final_uri) {
+                    Ok(u) => u,
+                    Err(_) => continue,
+                };
+                let req = mirror::MirrorRequest {
+                    route_id: matched_route_id.clone(),
+                    method: method_up.clone(),
+                    target: mirror_target,
+                    headers: final_headers.clone(),
+                    body: body.clone(),
+                };
+                if let Some(tx) = m.spawn(state.client_nofollow.clone(), req) {
+                    mirror_status_txs.push(tx);
                 }
             }
         }
@@ -1596,15 +1782,20 @@ async fn proxy_handler(
         // 构造上游请求
         let mut builder = client.request(method_up, target.clone());
         builder = builder.body(reqwest_body);
+        if let Some(ms) = route.total_timeout_ms {
+            builder = builder.timeout(Duration::from_millis(ms));
+        }
 
         let mut upstream_req = match builder.build() {
             Ok(r) => r,
             Err(e) => {
-                return (
+                return error_response(
+                    &state,
+                    Some(compiled),
+                    &ctx,
                     StatusCode::BAD_GATEWAY,
                     format!("build upstream request failed: {e}"),
-                )
-                    .into_response();
+                );
             }
         };
 
@@ -1617,40 +1808,97 @@ async fn proxy_handler(
         let resp = match client.execute(upstream_req).await {
             Ok(r) => r,
             Err(e) => {
-                return (
-                    StatusCode::BAD_GATEWAY,
-                    format!("upstream request failed: {e}"),
-                )
-                    .into_response();
+                // 流式请求体超出上限：不是上游故障
+                if is_length_limit_error(&e) {
+                    let status = StatusCode::PAYLOAD_TOO_LARGE;
+                    push_log_lazy(&state.app, || format_access_log(node, &ctx, status));
+
+                    log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&target), &fault_log);
+
+                    return error_response(
+                        &state,
+                        Some(compiled),
+                        &ctx,
+                        status,
+                        format!("request body too large (limit={max_body_size} bytes)"),
+                    );
+                }
+                if let (Some(permit), Some(cfg)) = (breaker_permit, breaker_cfg) {
+                    permit.failure(&state.app, cfg, &e.to_string());
+                }
+                // stale-if-error：上游不可用时返回过期缓存
+                if let Some(hit) = stale_hit.filter(|h| h.usable_on_error()) {
+                    return cached_response(&state, compiled, &ctx, &matched_route_id, hit, "STALE");
+                }
+                let message = format!("upstream request failed: {e}");
+                if let Some(session) = capture.as_ref().filter(|s| s.accepts_status(502)) {
+                    session.record(capture::CaptureEntry {
+                        route_id: &matched_route_id,
+                        client_ip: &ctx.client_ip,
+                        method: ctx.method.as_str(),
+                        url: ctx.full_url(),
+                        http_version: &http_version,
+                        request_headers: &inbound_headers,
+                        request_body: mirror_body.clone().unwrap_or_default(),
+                        status: 502,
+                        upstream: &target,
+                        latency_ms: ctx.elapsed_ms(),
+                        response_headers: &HeaderMap::new(),
+                        response_body: Bytes::from(message.clone()),
+                    });
+                }
+                return error_response(&state, Some(compiled), &ctx, StatusCode::BAD_GATEWAY, message);
             }
         };
 
         let status = resp.status();
+        if let (Some(permit), Some(cfg)) = (breaker_permit, breaker_cfg) {
+            if status.is_server_error() {
+                permit.failure(&state.app, cfg, &format!("status {}", status.as_u16()));
+            } else {
+                permit.success(&state.app);
+            }
+        }
+        for tx in mirror_status_txs {
+            let _ = tx.send(status.as_u16());
+        }
+        if status.is_server_error() {
+            if let Some(hit) = stale_hit.filter(|h| h.usable_on_error()) {
+                return cached_response(&state, compiled, &ctx, &matched_route_id, hit, "STALE");
+            }
+        }
         let response_headers = resp.headers().clone(); // 提前 clone headers
-        
-        push_log_lazy(&state.app, || {
-            format_access_log(
-                node,
-                &ctx,
-                StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
-            )
-        });
+        let upstream_addr = resp
+            .remote_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|| upstream_url.clone());
+
+        // 响应不可缓存时立即释放回源锁，等待同一 key 的请求不必等到响应体传输完毕
+        if let Some(cache) = compiled.cache.as_ref() {
+            let code = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+            if cache_key.is_some() && (ctx.method != Method::GET || !cache.cacheable(code, &response_headers)) {
+                cache_key = None;
+                _cache_lock = None;
+            }
+        }
 
-        metrics::try_enqueue_request_log(metrics::RequestLogInsert {
-            timestamp: chrono::Utc::now().timestamp(),
-            listen_addr: node.to_string(),
-            client_ip: ctx.client_ip.clone(),
-            remote_ip: remote.ip().to_string(),
-            method: ctx.method.as_str().to_string(),
-            request_path: ctx.path.clone(),
-            request_host: ctx.host_header.clone(),
-            status_code: status.as_u16() as i32,
-            upstream: target.clone(),
-            latency_ms: ctx.elapsed_ms(),
-            user_agent: ctx.user_agent_header.clone(),
-            referer: ctx.referer_header.clone(),
-            matched_route_id: matched_route_id.clone(),
-        });
+        // proxy_intercept_errors：上游 4xx/5xx 且配置了对应错误页时，丢弃上游响应体改用错误页
+        let intercept = compiled
+            .route
+            .proxy_intercept_errors
+            .unwrap_or(state.proxy_intercept_errors);
+        if intercept && (status.is_client_error() || status.is_server_error()) {
+            let code = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+            if compiled.error_pages.contains(code) || state.error_pages.contains(code) {
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, code));
+
+                log_request(&state, &ctx, &matched_route_id, code, &upstream_log(&target), &fault_log);
+
+                let mut resp = error_response(&state, Some(compiled), &ctx, code, "");
+                finish_response_headers(compiled, &ctx, &matched_route_id, &upstream_addr, resp.headers_mut());
+                return resp;
+            }
+        }
 
         let mut out = Response::new(Body::empty());
         *out.status_mut() = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
@@ -1675,94 +1923,116 @@ async fn proxy_handler(
             }
         }
 
-        // 响应体处理
-        if state.stream_proxy {
-            let stream = resp.bytes_stream();
-            *out.body_mut() = Body::from_stream(stream);
+        // 3.5 响应头修改（remove -> set -> add）
+        finish_response_headers(compiled, &ctx, &matched_route_id, &upstream_addr, out.headers_mut());
+
+        if let Some(result) = cache_status {
+            metrics::record_cache_result(&matched_route_id, result);
+            out.headers_mut()
+                .insert(HeaderName::from_static("x-cache"), HeaderValue::from_static(result));
+        }
+
+        // 响应体处理（需写入缓存或分发给合并请求时，即使开启流式转发也先完整读取）
+        let mut captured_response = None;
+        if stream_mode && cache_key.is_none() && coalesce_leader.is_none() {
+            // 全局 max_response_body_size 只约束需要缓冲的响应；流式转发仅在路由显式配置时限制
+            match route.max_response_body_size.filter(|n| *n > 0) {
+                Some(limit) => {
+                    if resp.content_length().is_some_and(|n| n as usize > limit) {
+                        let code = StatusCode::BAD_GATEWAY;
+                        push_log_lazy(&state.app, || format_access_log(node, &ctx, code));
+
+                        log_request(&state, &ctx, &matched_route_id, code, &upstream_log(&target), &fault_log);
+
+                        return error_response(
+                            &state,
+                            Some(compiled),
+                            &ctx,
+                            code,
+                            format!("upstream body too large (limit={limit} bytes)"),
+                        );
+                    }
+                    *out.body_mut() = Body::from_stream(limit_stream(resp.bytes_stream(), limit));
+                }
+                None => {
+                    let stream = resp.bytes_stream();
+                    *out.body_mut() = Body::from_stream(stream);
+                }
+            }
         } else {
             let bytes = match resp.bytes().await {
                 Ok(b) => b,
                 Err(e) => {
-                    return (
-                        StatusCode::BAD_GATEWAY,
+                    let code = StatusCode::BAD_GATEWAY;
+                    push_log_lazy(&state.app, || format_access_log(node, &ctx, code));
+
+                    log_request(&state, &ctx, &matched_route_id, code, &upstream_log(&target), &fault_log);
+
+                    return error_response(
+                        &state,
+                        Some(compiled),
+                        &ctx,
+                        code,
                         format!("read upstream body failed: {e}"),
-                    )
-                        .into_response();
+                    );
                 }
             };
 
-            if state.max_response_body_size > 0 && bytes.len() > state.max_response_body_size {
-                return (
-                    StatusCode::BAD_GATEWAY,
+            if max_response_body_size > 0 && bytes.len() > max_response_body_size {
+                let code = StatusCode::BAD_GATEWAY;
+                push_log_lazy(&state.app, || format_access_log(node, &ctx, code));
+
+                log_request(&state, &ctx, &matched_route_id, code, &upstream_log(&target), &fault_log);
+
+                return error_response(
+                    &state,
+                    Some(compiled),
+                    &ctx,
+                    code,
                     format!(
                         "upstream body too large (limit={} bytes)",
-                        state.max_response_body_size
+                        max_response_body_size
                     ),
-                )
-                    .into_response();
+                );
             }
 
-            // 3.5 响应体修改（如果配置了替换规则）
-            let final_bytes = if let Some(rules) = route.response_body_replace.as_ref() {
-                if let Ok(body_str) = String::from_utf8(bytes.to_vec()) {
-                    let mut modified_body = body_str;
-                    for rule in rules {
-                        if !rule.enabled {
-                            continue;
-                        }
-                        
-                        // 检查 Content-Type 过滤
-                        if let Some(ref content_types) = rule.content_types {
-                            if !content_types.trim().is_empty() {
-                                // 获取响应的 Content-Type（使用提前 clone 的 headers）
-                                let response_content_type = response_headers
-                                    .get(axum::http::header::CONTENT_TYPE)
-                                    .and_then(|v: &HeaderValue| v.to_str().ok())
-                                    .unwrap_or("")
-                                    .to_lowercase();
-
-                                // 解析 Content-Type，去除 charset 等参数
-                                let pure_content_type = response_content_type
-                                    .split(';')
-                                    .next()
-                                    .unwrap_or("")
-                                    .trim();
-
-                                // 检查是否匹配任一 Content-Type
-                                let content_type_list: Vec<String> = content_types
-                                    .split(',')
-                                    .map(|s| s.trim().to_lowercase())
-                                    .collect();
-
-                                if !content_type_list
-                                    .iter()
-                                    .any(|ct| ct.as_str() == pure_content_type)
-                                {
-                                    continue; // 不匹配，跳过此规则
-                                }
-                            }
-                        }
-                        
-                        // 执行替换
-                        if rule.use_regex {
-                            if let Ok(re) = Regex::new(&rule.find) {
-                                modified_body = re.replace_all(&modified_body, &rule.replace).to_string();
-                            }
-                        } else {
-                            modified_body = modified_body.replace(&rule.find, &rule.replace);
-                        }
-                    }
-                    Bytes::from(modified_body.into_bytes())
-                } else {
-                    bytes
+            // 私有响应（Set-Cookie / private / no-store）不分发，等待者各自回源
+            if let Some(leader) = coalesce_leader.take() {
+                leader.complete(coalesce::SharedResponse {
+                    status: out.status(),
+                    headers: response_headers.clone(),
+                    body: bytes.clone(),
+                    upstream_addr: upstream_addr.clone(),
+                });
+            }
+
+            // 缓存保存上游原始响应，命中时再执行响应体替换与响应头规则
+            if let (Some(cache), Some(key)) = (compiled.cache.as_ref(), cache_key.as_deref()) {
+                if ctx.method == Method::GET {
+                    cache
+                        .store(key, &inbound_headers, out.status(), &response_headers, bytes.clone())
+                        .await;
                 }
-            } else {
-                bytes
-            };
+            }
 
+            // 3.6 响应体修改（如果配置了替换规则）
+            let final_bytes = route_table::apply_body_replace(
+                &compiled.response_body_replace,
+                bytes,
+                response_headers.get(axum::http::header::CONTENT_TYPE),
+            );
+
+            if capture.is_some() {
+                captured_response = Some(final_bytes.clone());
+            }
             *out.body_mut() = Body::from(final_bytes);
         }
 
+        // 响应体超限等提前返回已各自记录日志，这里记录正常转发的结果
+        push_log_lazy(&state.app, || format_access_log(node, &ctx, out.status()));
+
+        log_request(&state, &ctx, &matched_route_id, status, &upstream_log(&target), &fault_log);
+
         // 仅错误时记录详细日志
         if !status.is_success() {
             let inbound_headers_line = inbound_headers
@@ -1799,14 +2069,292 @@ async fn proxy_handler(
             ));
         }
 
+        if let Some(session) = capture.as_ref().filter(|s| s.accepts_status(status.as_u16())) {
+            session.record(capture::CaptureEntry {
+                route_id: &matched_route_id,
+                client_ip: &ctx.client_ip,
+                method: ctx.method.as_str(),
+                url: ctx.full_url(),
+                http_version: &http_version,
+                request_headers: &inbound_headers,
+                request_body: mirror_body.clone().unwrap_or_default(),
+                status: status.as_u16(),
+                upstream: &target,
+                latency_ms: ctx.elapsed_ms(),
+                response_headers: out.headers(),
+                response_body: captured_response.unwrap_or_default(),
+            });
+        }
+
+        // 故障注入：响应体限速
+        if let Some(bps) = fault_plan.as_ref().and_then(|p| p.bytes_per_sec) {
+            out = out.map(|body| fault::throttle(body, bps));
+        }
+
         return out;
     }
 
-    (
+    error_response(
+        &state,
+        Some(compiled),
+        &ctx,
         StatusCode::NOT_FOUND,
         "No static directory or upstream configured",
     )
-        .into_response()
+}
+
+/// 路由匹配诊断结果
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RouteExplain {
+    pub listen_addr: String,
+    pub candidates: Vec<route_table::RouteCandidate>,
+    pub selected_route_id: Option<String>,
+    /// 命中路由的处理方式：return / mock / static / proxy；未命中任何路由时为 none
+    pub action: String,
+    pub split: Option<String>,
+    /// 执行 url_rewrite_rules 后的请求 URI
+    pub rewritten_uri: Option<String>,
+    /// 每个候选上游对应的最终请求地址
+    pub upstream_urls: Vec<String>,
+    /// 执行 set_headers / remove_headers 后发往上游的请求头
+    pub outbound_headers: Vec<(String, String)>,
+}
+
+/// 模拟一次请求的路由匹配，不发出任何请求
+///
+/// 监听器运行中时使用其路由表；否则按配置编译一份无副作用的诊断用路由表
+#[allow(clippy::too_many_arguments)]
+pub fn explain_request(
+    rule: &config::ListenRule,
+    listen_addr: &str,
+    host: &str,
+    uri: &str,
+    method: &str,
+    headers: &HashMap<String, String>,
+    body: Option<&[u8]>,
+    client_ip: Option<&str>,
+) -> Result<RouteExplain> {
+    let uri: Uri = uri.trim().parse().map_err(|e| anyhow!("请求路径无效: {e}"))?;
+    let method = Method::from_bytes(method.trim().to_ascii_uppercase().as_bytes())
+        .map_err(|_| anyhow!("请求方法无效: {method}"))?;
+
+    let mut inbound = HeaderMap::new();
+    for (k, v) in headers {
+        let name = HeaderName::from_bytes(k.trim().as_bytes()).map_err(|_| anyhow!("请求头名无效: {k}"))?;
+        let value = HeaderValue::from_str(v).map_err(|_| anyhow!("请求头 {k} 的值无效"))?;
+        inbound.insert(name, value);
+    }
+    if !host.trim().is_empty() {
+        inbound.insert(
+            axum::http::header::HOST,
+            HeaderValue::from_str(host.trim()).map_err(|_| anyhow!("Host 无效: {host}"))?,
+        );
+    }
+
+    let remote_ip = client_ip
+        .and_then(|ip| ip.trim().parse::<std::net::IpAddr>().ok())
+        .unwrap_or(std::net::IpAddr::from([127, 0, 0, 1]));
+    let remote = SocketAddr::new(remote_ip, 0);
+    let ctx = RequestContext::new(remote, &inbound, method.clone(), uri, rule.ssl_enable);
+
+    let table = match LIVE_ROUTES.get(listen_addr.trim()) {
+        Some(t) => t.clone(),
+        None => Arc::new(route_table::RouteTable::compile_preview(rule)?),
+    };
+    let candidates = table.explain(&ctx.host_header, &ctx.path, &method, &inbound);
+    let (matched, matched_route_id) = table.match_route(&ctx.host_header, &ctx.path, &method, &inbound);
+
+    let mut out = RouteExplain {
+        listen_addr: listen_addr.to_string(),
+        candidates,
+        selected_route_id: None,
+        action: "none".to_string(),
+        split: None,
+        rewritten_uri: None,
+        upstream_urls: Vec::new(),
+        outbound_headers: Vec::new(),
+    };
+    let Some(matched) = matched else {
+        return Ok(out);
+    };
+    let compiled = matched.route;
+    let route = &compiled.route;
+    out.selected_route_id = Some(matched_route_id.clone());
+
+    if compiled.return_directive.is_some() {
+        out.action = "return".to_string();
+        return Ok(out);
+    }
+    if compiled.mock.as_ref().is_some_and(|m| {
+        // 与实际处理一致：仅在有规则匹配请求体时才解析 JSON
+        let json = body
+            .filter(|_| m.needs_body())
+            .and_then(|b| serde_json::from_slice::<serde_json::Value>(b).ok());
+        m.find(&method, &ctx.path, ctx.uri.query(), &inbound, json.as_ref()).is_some()
+    }) {
+        out.action = "mock".to_string();
+        return Ok(out);
+    }
+    if route.static_dir.is_some() {
+        out.action = "static".to_string();
+        return Ok(out);
+    }
+    out.action = "proxy".to_string();
+
+    let split = compiled.splits.iter().find(|s| {
+        s.matches(&inbound, ctx.uri.query(), &ctx.client_ip, |name| key_var(&ctx, &inbound, name))
+    });
+    out.split = split.map(|s| s.name.clone());
+    let upstreams = split.map_or(&route.upstreams, |s| &s.upstreams);
+
+    let final_uri = compiled.rewrite_uri(&ctx.uri);
+    out.rewritten_uri = Some(final_uri.to_string());
+    let proxy_pass_path = route
+        .proxy_pass_path
+        .as_deref()
+        .map(|pp| vars::expand(pp, |name| matched.capture(name)));
+    let route_path = if matched.is_regex() {
+        Some(final_uri.path())
+    } else {
+        route.path.as_deref()
+    };
+    let server_port = parse_listen_addr(listen_addr)
+        .map(|(addr, _)| addr.port().to_string())
+        .unwrap_or_default();
+    for up in upstreams {
+        let base = up.url.replace("$server_port", &server_port);
+        out.upstream_urls.push(
+            build_upstream_url(&base, route_path, proxy_pass_path.as_deref(), &final_uri)
+                .unwrap_or_else(|e| format!("bad upstream url: {e}")),
+        );
+    }
+
+    let outbound = build_outbound_headers(rule, &matched, &ctx, &inbound, remote);
+    out.outbound_headers = outbound
+        .iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+        .collect();
+    Ok(out)
+}
+
+/// 构造发往上游的请求头：过滤逐跳头，补充转发头，执行 set_headers / remove_headers
+fn build_outbound_headers(
+    rule: &config::ListenRule,
+    matched: &route_table::RouteMatch<'_>,
+    ctx: &RequestContext,
+    inbound: &HeaderMap,
+    remote: SocketAddr,
+) -> HeaderMap {
+    let route = &matched.route.route;
+
+    // 使用预计算的 SKIP_HEADERS
+    let mut final_headers = HeaderMap::new();
+
+    for (k, v) in inbound.iter() {
+        if SKIP_HEADERS.contains(k) || is_hop_header_fast(k.as_str()) {
+            continue;
+        }
+        final_headers.append(k.clone(), v.clone());
+    }
+
+    // Host header
+    if let Some(h) = inbound.get(axum::http::header::HOST) {
+        final_headers.insert(axum::http::header::HOST, h.clone());
+    }
+
+    // 转发头
+    {
+        let remote_ip = remote.ip().to_string();
+        if let Ok(v) = HeaderValue::from_str(&remote_ip) {
+            final_headers.insert(HeaderName::from_static("x-real-ip"), v);
+        }
+
+        let prior = inbound
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty());
+
+        let combined = match prior {
+            Some(p) => format!("{}, {}", p, remote_ip),
+            None => remote_ip,
+        };
+
+        if let Ok(v) = HeaderValue::from_str(&combined) {
+            final_headers.insert(HeaderName::from_static("x-forwarded-for"), v);
+        }
+    }
+
+    final_headers.insert(
+        HeaderName::from_static("x-forwarded-proto"),
+        HeaderValue::from_static(if rule.ssl_enable { "https" } else { "http" }),
+    );
+
+    final_headers.insert(axum::http::header::ACCEPT_ENCODING, HeaderValue::from_static(""));
+
+    if !final_headers.contains_key(axum::http::header::CONTENT_TYPE) {
+        if let Some(ct) = inbound.get(axum::http::header::CONTENT_TYPE) {
+            final_headers.insert(axum::http::header::CONTENT_TYPE, ct.clone());
+        }
+    }
+
+    // set_headers
+    if let Some(map) = route.set_headers.as_ref() {
+        for (k, v) in map {
+            let key = k.trim();
+            if key.is_empty() || is_hop_header_fast(key) {
+                continue;
+            }
+
+            // 正则路由的捕获组（$1 / 命名分组）优先于请求级变量
+            let expanded =
+                vars::expand(v, |name| matched.capture(name).or_else(|| ctx.var(name)));
+
+            let name = match HeaderName::from_bytes(key.as_bytes()) {
+                Ok(n) => n,
+                Err(_) => continue,
+            };
+
+            if expanded.is_empty() {
+                final_headers.insert(name, HeaderValue::from_static(""));
+                continue;
+            }
+
+            let value = match HeaderValue::from_str(&expanded) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            final_headers.insert(name, value);
+        }
+    }
+
+    // 透传请求 ID，便于与上游日志关联
+    if !final_headers.contains_key("x-request-id") {
+        if let Ok(v) = HeaderValue::from_str(&ctx.request_id) {
+            final_headers.insert(HeaderName::from_static("x-request-id"), v);
+        }
+    }
+
+    // 移除 Authorization（如需要）
+    if basic_auth::strips_authorization(rule, Some(route)) {
+        final_headers.remove(axum::http::header::AUTHORIZATION);
+    }
+
+    // 3.3 移除指定的请求头
+    if let Some(headers_to_remove) = route.remove_headers.as_ref() {
+        for header_name in headers_to_remove {
+            let trimmed = header_name.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if let Ok(name) = HeaderName::from_bytes(trimmed.as_bytes()) {
+                final_headers.remove(name);
+            }
+        }
+    }
+
+    final_headers
 }
 
 fn build_upstream_url(
@@ -1867,88 +2415,235 @@ fn is_asset_path(path: &str) -> bool {
     path.contains('.') || path.starts_with("/assets/") || path.starts_with("/static/")
 }
 
-// 使用预计算的 HashSet，性能更好
-#[inline]
-fn is_hop_header_fast(name: &str) -> bool {
-    // 0 分配：HTTP header 名大小写不敏感，直接用 eq_ignore_ascii_case
-    // 覆盖常见 hop-by-hop headers
-    name.eq_ignore_ascii_case("connection")
-        || name.eq_ignore_ascii_case("keep-alive")
-        || name.eq_ignore_ascii_case("proxy-authenticate")
-        || name.eq_ignore_ascii_case("proxy-authorization")
-        || name.eq_ignore_ascii_case("te")
-        || name.eq_ignore_ascii_case("trailer")
-        || name.eq_ignore_ascii_case("transfer-encoding")
-        || name.eq_ignore_ascii_case("upgrade")
+/// 生成错误响应：依次尝试路由级、监听器级自定义错误页，均未配置时回退为纯文本
+fn error_response(
+    state: &AppState,
+    route: Option<&route_table::CompiledRoute>,
+    ctx: &RequestContext,
+    status: StatusCode,
+    fallback: impl Into<String>,
+) -> Response {
+    let lookup = |name: &str| ctx.var(name);
+    let rendered = route
+        .and_then(|r| r.error_pages.render(status, &ctx.accept_header, lookup))
+        .or_else(|| state.error_pages.render(status, &ctx.accept_header, lookup));
+
+    let mut resp = rendered.unwrap_or_else(|| (status, fallback.into()).into_response());
+    if let Ok(v) = HeaderValue::from_str(&ctx.request_id) {
+        resp.headers_mut().insert(HeaderName::from_static("x-request-id"), v);
+    }
+    resp
 }
 
-fn expand_proxy_header_value(raw: &str, remote: &SocketAddr, inbound_headers: &HeaderMap, is_tls: bool) -> String {
-    // 仅在真的包含变量时才分配
-    if !(raw.contains('$')) {
-        return raw.to_string();
+/// 由已缓冲的上游原始响应（缓存命中或合并请求共享）生成响应：
+/// 与回源响应一样执行响应体替换与响应头规则，并记录访问日志
+fn buffered_response(
+    state: &AppState,
+    compiled: &route_table::CompiledRoute,
+    ctx: &RequestContext,
+    route_id: &str,
+    resp: &coalesce::SharedResponse,
+) -> Response {
+    let node = &*state.listen_addr;
+    let status = resp.status;
+    let headers = &resp.headers;
+    let upstream = resp.upstream_addr.as_str();
+    let mut out = Response::new(Body::empty());
+    *out.status_mut() = status;
+
+    for (k, v) in headers.iter() {
+        if is_hop_header_fast(k.as_str()) {
+            continue;
+        }
+        out.headers_mut().append(k.clone(), v.clone());
     }
 
-    let remote_ip = remote.ip().to_string();
-    let scheme = if is_tls { "https" } else { "http" };
-    let host = inbound_headers
-        .get("host")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("")
-        .to_string();
+    if ctx.method != Method::HEAD {
+        let body = route_table::apply_body_replace(
+            &compiled.response_body_replace,
+            resp.body.clone(),
+            headers.get(axum::http::header::CONTENT_TYPE),
+        );
+        *out.body_mut() = Body::from(body);
+    }
 
-    // 仅在需要时计算 $proxy_add_x_forwarded_for
-    let proxy_add_xff = if raw.contains("$proxy_add_x_forwarded_for") {
-        let prior = inbound_headers
-            .get("x-forwarded-for")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty());
+    if let Some(headers_to_remove) = compiled.route.remove_headers.as_ref() {
+        for header_name in headers_to_remove {
+            if let Ok(name) = HeaderName::from_bytes(header_name.trim().as_bytes()) {
+                out.headers_mut().remove(name);
+            }
+        }
+    }
+    finish_response_headers(compiled, ctx, route_id, upstream, out.headers_mut());
 
-        Some(match prior {
-            Some(p) => format!("{}, {}", p, remote_ip),
-            None => remote_ip.clone(),
-        })
+    push_log_lazy(&state.app, || format_access_log(node, ctx, status));
+    log_request(state, ctx, route_id, status, upstream, "");
+    out
+}
+
+/// 由缓存内容生成响应，并附带 X-Cache 头
+fn cached_response(
+    state: &AppState,
+    compiled: &route_table::CompiledRoute,
+    ctx: &RequestContext,
+    route_id: &str,
+    hit: cache::CachedResponse,
+    result: &'static str,
+) -> Response {
+    let resp = coalesce::SharedResponse {
+        status: hit.status,
+        headers: hit.response_headers(),
+        body: hit.body,
+        upstream_addr: "cache".to_string(),
+    };
+    let mut out = buffered_response(state, compiled, ctx, route_id, &resp);
+    out.headers_mut()
+        .insert(HeaderName::from_static("x-cache"), HeaderValue::from_static(result));
+    metrics::record_cache_result(route_id, result);
+    out
+}
+
+/// 错误链中是否包含 http_body_util::Limited 产生的超限错误
+pub(crate) fn is_length_limit_error(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut cur = Some(e);
+    while let Some(err) = cur {
+        if err.is::<http_body_util::LengthLimitError>() {
+            return true;
+        }
+        cur = err.source();
+    }
+    false
+}
+
+/// 流式请求体：超过上限（0 表示不限制）时以 LengthLimitError 中断上游请求
+pub(crate) fn limited_body(body: Body, limit: usize) -> reqwest::Body {
+    let stream = if limit > 0 {
+        Body::new(http_body_util::Limited::new(body, limit)).into_data_stream()
     } else {
-        None
+        body.into_data_stream()
     };
+    reqwest::Body::wrap_stream(stream)
+}
 
-    // 预估容量，尽量一次分配
-    let mut out = String::with_capacity(raw.len() + 32);
-    let bytes = raw.as_bytes();
-    let mut i = 0usize;
-    while i < bytes.len() {
-        if bytes[i] == b'$' {
-            let rest = &raw[i..];
-            if rest.starts_with("$remote_addr") {
-                out.push_str(&remote_ip);
-                i += "$remote_addr".len();
-                continue;
-            }
-            if rest.starts_with("$scheme") {
-                out.push_str(scheme);
-                i += "$scheme".len();
-                continue;
-            }
-            if rest.starts_with("$host") {
-                out.push_str(&host);
-                i += "$host".len();
-                continue;
-            }
-            if rest.starts_with("$proxy_add_x_forwarded_for") {
-                if let Some(v) = proxy_add_xff.as_ref() {
-                    out.push_str(v);
-                }
-                i += "$proxy_add_x_forwarded_for".len();
-                continue;
+/// 流式响应体超过上限时中断传输
+pub(crate) fn limit_stream<S>(
+    stream: S,
+    limit: usize,
+) -> impl futures_util::Stream<Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>>
+where
+    S: futures_util::Stream<Item = reqwest::Result<Bytes>>,
+{
+    use futures_util::StreamExt;
+
+    let mut seen = 0usize;
+    stream.map(move |chunk| {
+        let chunk = chunk?;
+        seen += chunk.len();
+        if seen > limit {
+            return Err(format!("upstream body too large (limit={limit} bytes)").into());
+        }
+        Ok(chunk)
+    })
+}
+
+/// 缓存键 / 合并键变量：在请求级变量基础上支持 `$http_<name>` 与 `$cookie_<name>`
+fn key_var(ctx: &RequestContext, headers: &HeaderMap, name: &str) -> Option<String> {
+    if let Some(h) = name.strip_prefix("http_") {
+        let h = h.replace('_', "-");
+        let v = headers.get(h.as_str()).and_then(|v| v.to_str().ok()).unwrap_or("");
+        return Some(v.to_string());
+    }
+    if let Some(c) = name.strip_prefix("cookie_") {
+        let v = headers
+            .get_all(axum::http::header::COOKIE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(';'))
+            .find_map(|kv| {
+                let (k, v) = kv.trim().split_once('=')?;
+                (k == c).then(|| v.to_string())
+            });
+        return Some(v.unwrap_or_default());
+    }
+    ctx.var(name)
+}
+
+/// stale-while-revalidate：后台回源刷新缓存，同一 key 同时只有一个刷新任务
+fn spawn_cache_revalidate(
+    cache: Arc<cache::RouteCache>,
+    key: String,
+    client: reqwest::Client,
+    target: String,
+    headers: HeaderMap,
+    inbound_headers: HeaderMap,
+    max_body_size: usize,
+) {
+    tokio::spawn(async move {
+        let Some(_lock) = cache.lock(&key).await else {
+            return;
+        };
+        if matches!(cache.lookup(&key, &inbound_headers).await, cache::Lookup::Fresh(_)) {
+            return;
+        }
+        let resp = match client.get(&target).headers(headers).send().await {
+            Ok(r) => r,
+            Err(e) => {
+                send_log(format!("缓存后台刷新失败: {} -> {}: {}", key, target, e));
+                return;
             }
+        };
+        let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+        let resp_headers = resp.headers().clone();
+        let Ok(bytes) = resp.bytes().await else {
+            return;
+        };
+        if max_body_size > 0 && bytes.len() > max_body_size {
+            return;
         }
+        cache.store(&key, &inbound_headers, status, &resp_headers, bytes).await;
+    });
+}
 
-        // 退化为逐字节拷贝（utf8 安全：这里拷的是原始 bytes，对应原始字符串片段）
-        // 为避免 utf8 边界问题，用 char 级推进
-        let ch = raw[i..].chars().next().unwrap();
-        out.push(ch);
-        i += ch.len_utf8();
+/// 路由级响应头处理：先执行 remove/set/add_response_headers，再补充 CORS 头
+pub(crate) fn finish_response_headers(
+    compiled: &route_table::CompiledRoute,
+    ctx: &RequestContext,
+    route_id: &str,
+    upstream_addr: &str,
+    headers: &mut HeaderMap,
+) {
+    compiled.apply_response_headers(headers, |name| response_var(ctx, route_id, upstream_addr, name));
+    if let Some(policy) = compiled.cors.as_ref() {
+        policy.apply(ctx.origin.as_ref(), headers);
     }
+}
 
-    out
+/// 响应头变量：在请求级变量基础上补充 $upstream_addr / $request_time / $route_id
+fn response_var(
+    ctx: &RequestContext,
+    route_id: &str,
+    upstream_addr: &str,
+    name: &str,
+) -> Option<String> {
+    match name {
+        "upstream_addr" => Some(upstream_addr.to_string()),
+        "request_time" => Some(format!("{:.3}", ctx.elapsed_s())),
+        "route_id" => Some(route_id.to_string()),
+        _ => ctx.var(name),
+    }
+}
+
+// 使用预计算的 HashSet，性能更好
+#[inline]
+pub(crate) fn is_hop_header_fast(name: &str) -> bool {
+    // 0 分配：HTTP header 名大小写不敏感，直接用 eq_ignore_ascii_case
+    // 覆盖常见 hop-by-hop headers
+    name.eq_ignore_ascii_case("connection")
+        || name.eq_ignore_ascii_case("keep-alive")
+        || name.eq_ignore_ascii_case("proxy-authenticate")
+        || name.eq_ignore_ascii_case("proxy-authorization")
+        || name.eq_ignore_ascii_case("te")
+        || name.eq_ignore_ascii_case("trailer")
+        || name.eq_ignore_ascii_case("transfer-encoding")
+        || name.eq_ignore_ascii_case("upgrade")
 }
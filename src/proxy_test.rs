@@ -0,0 +1,215 @@
+// 代理转发（proxy）模块的单元测试
+
+#[cfg(test)]
+mod proxy_tests {
+    use crate::config;
+    use crate::proxy::{
+        explain_request, finish_response_headers, is_length_limit_error, limit_stream, limited_body, RequestContext,
+    };
+    use crate::route_table::CompiledRoute;
+    use axum::body::{Body, Bytes};
+    use axum::http::{HeaderMap, Method, Request, Uri};
+    use futures_util::StreamExt;
+    use tower::util::ServiceExt;
+
+    fn chunks(sizes: &[usize]) -> impl futures_util::Stream<Item = reqwest::Result<Bytes>> {
+        let items: Vec<_> = sizes.iter().map(|&n| Ok(Bytes::from(vec![b'x'; n]))).collect();
+        futures_util::stream::iter(items)
+    }
+
+    #[tokio::test]
+    async fn test_limit_stream() {
+        // 累计超过上限的那一块返回错误，之前的块照常输出
+        let out: Vec<_> = limit_stream(chunks(&[3, 3, 3]), 5).collect().await;
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].as_ref().unwrap().len(), 3);
+        let err = out[1].as_ref().unwrap_err().to_string();
+        assert!(err.contains("limit=5"), "{err}");
+
+        // 恰好等于上限时不中断
+        let out: Vec<_> = limit_stream(chunks(&[3, 3]), 6).collect().await;
+        assert!(out.iter().all(|c| c.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_is_length_limit_error() {
+        let err = axum::body::to_bytes(Body::from("hello"), 3).await.unwrap_err();
+        assert!(is_length_limit_error(&err));
+
+        let other = std::io::Error::other("connection reset");
+        assert!(!is_length_limit_error(&other));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_body_limit() {
+        // 上游回显收到的请求体长度
+        let app = axum::Router::new().route(
+            "/upload",
+            axum::routing::post(|body: Bytes| async move { body.len().to_string() }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let client = reqwest::Client::new();
+        let url = format!("http://{addr}/upload");
+        let body = |sizes: &[usize]| {
+            let items: Vec<Result<Bytes, std::io::Error>> =
+                sizes.iter().map(|&n| Ok(Bytes::from(vec![b'x'; n]))).collect();
+            Body::from_stream(futures_util::stream::iter(items))
+        };
+
+        // 未超限：完整转发
+        let resp = client.post(&url).body(limited_body(body(&[40, 40]), 100)).send().await.unwrap();
+        assert_eq!(resp.text().await.unwrap(), "80");
+
+        // 0 表示不限制
+        let resp = client.post(&url).body(limited_body(body(&[400, 400]), 0)).send().await.unwrap();
+        assert_eq!(resp.text().await.unwrap(), "800");
+
+        // 超限：上游请求中断，错误链可识别为超限（代理据此返回 413 而非 502）
+        let err = client
+            .post(&url)
+            .body(limited_body(body(&[60, 60]), 100))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(is_length_limit_error(&err), "{err:?}");
+    }
+
+    fn header_route(static_dir: &str) -> CompiledRoute {
+        let route: config::Route = toml::from_str(&format!(
+            r#"id = "site"
+               path = "/"
+               static_dir = "{static_dir}"
+               remove_response_headers = ["X-Powered-By", "Last-Modified"]
+               set_response_headers = {{ cache-control = "no-store", x-served-by = "$route_id@$host_name", x-host = "$host" }}
+               add_response_headers = {{ vary = "Origin", x-upstream = "$upstream_addr" }}"#
+        ))
+        .expect("invalid route toml");
+        CompiledRoute::compile(&route, false).unwrap()
+    }
+
+    fn context(uri: &str) -> RequestContext {
+        let mut headers = HeaderMap::new();
+        headers.insert("host", "a.com:8080".parse().unwrap());
+        let uri: Uri = uri.parse().unwrap();
+        RequestContext::new("10.0.0.1:5000".parse().unwrap(), &headers, Method::GET, uri, false)
+    }
+
+    #[tokio::test]
+    async fn test_response_headers_on_static_response() {
+        let dir = std::env::temp_dir().join(format!("static-headers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "hello").unwrap();
+        let compiled = header_route(&dir.display().to_string().replace('\\', "/"));
+
+        let static_dir = compiled.route.static_dir.as_deref().unwrap();
+        let req = Request::builder().uri("/a.txt").body(Body::empty()).unwrap();
+        let mut resp = tower_http::services::ServeDir::new(static_dir).oneshot(req).await.unwrap();
+        assert!(resp.headers().contains_key("last-modified"));
+
+        finish_response_headers(&compiled, &context("/a.txt"), "site", "", resp.headers_mut());
+        let h = resp.headers();
+        assert!(!h.contains_key("last-modified"));
+        assert_eq!(h["content-type"], "text/plain");
+        assert_eq!(h["cache-control"], "no-store");
+        assert_eq!(h["x-served-by"], "site@a.com");
+        assert_eq!(h["x-host"], "a.com:8080");
+        assert_eq!(h["vary"], "Origin");
+        assert_eq!(h["x-upstream"], "");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_response_headers_on_proxied_response() {
+        let app = axum::Router::new().route(
+            "/api",
+            axum::routing::get(|| async {
+                (
+                    [
+                        ("x-powered-by", "php"),
+                        ("cache-control", "public, max-age=60"),
+                        ("vary", "Accept"),
+                    ],
+                    "ok",
+                )
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let compiled = header_route("unused");
+        let resp = reqwest::get(format!("http://{addr}/api")).await.unwrap();
+        let upstream_addr = resp.remote_addr().unwrap().to_string();
+        let mut headers = resp.headers().clone();
+
+        finish_response_headers(&compiled, &context("/api"), "site", &upstream_addr, &mut headers);
+        assert!(!headers.contains_key("x-powered-by"));
+        // set 覆盖上游值，add 在上游值之后追加
+        assert_eq!(headers.get_all("cache-control").iter().collect::<Vec<_>>(), ["no-store"]);
+        assert_eq!(headers.get_all("vary").iter().collect::<Vec<_>>(), ["Accept", "Origin"]);
+        assert_eq!(headers["x-upstream"], addr.to_string().as_str());
+        assert_eq!(headers["x-served-by"], "site@a.com");
+    }
+
+    #[test]
+    fn test_explain_request_preview() {
+        let id = uuid::Uuid::new_v4();
+        let mock_file = std::env::temp_dir().join(format!("explain-mock-{id}.toml"));
+        std::fs::write(
+            &mock_file,
+            r#"
+            [[mocks]]
+            method = "POST"
+            path = "/api/login"
+            body = [{ path = "$.user", value = "admin" }]
+            [mocks.response]
+            status = 200
+            "#,
+        )
+        .unwrap();
+        let cache_dir = std::env::temp_dir().join(format!("explain-cache-{id}"));
+        let rule: config::ListenRule = toml::from_str(&format!(
+            r#"listen_addr = ":18083"
+               ssl_enable = false
+               cert_file = ""
+               key_file = ""
+               basic_auth_enable = false
+               basic_auth_username = ""
+               basic_auth_password = ""
+               basic_auth_forward_header = false
+
+               [[routes]]
+               id = "api"
+               path = "/api/"
+               basic_auth_enable = true
+               basic_auth_htpasswd_file = "{htpasswd}"
+               upstreams = [{{ url = "http://127.0.0.1:9", weight = 1 }}]
+               mock = {{ file = "{mock}" }}
+               cache = {{ storage = "disk", disk_path = "{cache}" }}"#,
+            htpasswd = std::env::temp_dir().join(format!("missing-htpasswd-{id}")).display(),
+            mock = mock_file.display(),
+            cache = cache_dir.display(),
+        ))
+        .unwrap();
+
+        let explain = |body: Option<&str>| {
+            let headers = std::collections::HashMap::new();
+            let out = explain_request(&rule, ":18083", "a.com", "/api/login", "POST", &headers, body.map(str::as_bytes), None)
+                .expect("explain without compiling caches or auth files");
+            out.action
+        };
+        // 请求体匹配的 mock 需要带上请求体才能命中
+        assert_eq!(explain(Some(r#"{"user": "admin"}"#)), "mock");
+        assert_eq!(explain(Some(r#"{"user": "guest"}"#)), "proxy");
+        assert_eq!(explain(None), "proxy");
+
+        // 诊断不创建缓存目录，也不读取 htpasswd 文件
+        assert!(!cache_dir.exists());
+        assert!(crate::route_table::RouteTable::compile(&rule).is_err());
+        let _ = std::fs::remove_file(&mock_file);
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+}
@@ -28,7 +28,7 @@ impl Default for RateLimitConfig {
 }
 
 /// 令牌桶结构
-struct TokenBucket {
+pub(crate) struct TokenBucket {
     /// 当前令牌数
     tokens: f64,
     /// 令牌桶容量
@@ -40,7 +40,7 @@ struct TokenBucket {
 }
 
 impl TokenBucket {
-    fn new(capacity: f64, refill_rate: f64) -> Self {
+    pub(crate) fn new(capacity: f64, refill_rate: f64) -> Self {
         Self {
             tokens: capacity,
             capacity,
@@ -50,7 +50,7 @@ impl TokenBucket {
     }
 
     /// 尝试消费一个令牌
-    fn try_consume(&mut self) -> bool {
+    pub(crate) fn try_consume(&mut self) -> bool {
         let now = Instant::now();
         let elapsed = now.duration_since(self.last_update);
         
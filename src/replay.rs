@@ -0,0 +1,462 @@
+use anyhow::{anyhow, Context, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+use sqlx::QueryBuilder;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::sync::Semaphore;
+
+use crate::{metrics, proxy};
+
+/// 回放数据来源：captures 带完整请求头与请求体；request_logs 只有方法、路径与少量请求头
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaySource {
+    #[default]
+    Captures,
+    RequestLogs,
+}
+
+/// 回放条件：过滤字段与 query_request_logs 一致（captures 没有 listen_addr，忽略该条件）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayRequest {
+    #[serde(default)]
+    pub source: ReplaySource,
+    pub session_id: Option<String>,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub listen_addr: Option<String>,
+    pub upstream: Option<String>,
+    pub request_path: Option<String>,
+    pub client_ip: Option<String>,
+    pub status_code: Option<i32>,
+    pub matched_route_id: Option<String>,
+    /// 回放目标基址：上游地址或本机监听器，例如 `http://127.0.0.1:8080`
+    pub target: String,
+    /// 回放速度倍数：1 为原始节奏，2 为两倍速，0 表示不等待
+    pub speed: Option<f64>,
+    /// 最多回放的请求数，默认 1000
+    pub limit: Option<i64>,
+    /// 同时进行的请求数，默认 16
+    pub concurrency: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+/// 待回放的单个请求
+#[derive(Debug, Clone)]
+pub(crate) struct ReplayItem {
+    pub original_id: i64,
+    pub timestamp_ms: i64,
+    pub method: String,
+    pub path_and_query: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// 抓包时请求体超过上限只保存了前一部分；此类请求不回放
+    pub body_truncated: bool,
+    pub status: i64,
+    pub latency_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayResult {
+    pub original_id: i64,
+    pub method: String,
+    pub url: String,
+    pub original_status: i64,
+    /// 0 表示请求失败
+    pub replay_status: u16,
+    pub original_latency_ms: f64,
+    pub replay_latency_ms: f64,
+    pub status_changed: bool,
+    pub error: Option<String>,
+    /// 未发送（请求体在抓包时被截断或认证头已脱敏），原因见 error
+    pub skipped: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub id: String,
+    pub total: usize,
+    pub errors: usize,
+    pub skipped: usize,
+    pub status_mismatches: usize,
+    pub original_avg_latency_ms: f64,
+    pub replay_avg_latency_ms: f64,
+    pub original_p95_latency_ms: f64,
+    pub replay_p95_latency_ms: f64,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub results: Vec<ReplayResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ReplayProgress<'a> {
+    id: &'a str,
+    done: usize,
+    total: usize,
+}
+
+/// 回放时不转发的请求头：逐跳头由客户端重新生成
+fn skip_header(name: &str) -> bool {
+    const SKIP: [&str; 8] = [
+        "connection",
+        "keep-alive",
+        "proxy-connection",
+        "transfer-encoding",
+        "upgrade",
+        "te",
+        "trailer",
+        "content-length",
+    ];
+    SKIP.iter().any(|h| name.eq_ignore_ascii_case(h))
+}
+
+/// 不回放的原因：这类请求回放出去只会得到与原始请求不同的结果
+fn skip_reason(item: &ReplayItem) -> Option<&'static str> {
+    if item.body_truncated {
+        return Some("抓包时请求体被截断，已跳过");
+    }
+    // 认证头在抓包时已脱敏，不带凭据回放只会得到 401
+    if item.headers.iter().any(|(_, v)| v == "[REDACTED]") {
+        return Some("抓包记录中的认证信息已脱敏，已跳过");
+    }
+    None
+}
+
+#[derive(sqlx::FromRow)]
+struct CaptureReplayRow {
+    id: i64,
+    timestamp_ms: i64,
+    method: String,
+    url: String,
+    request_headers: String,
+    request_body: Vec<u8>,
+    request_body_size: i64,
+    status_code: i64,
+    latency_ms: f64,
+}
+
+#[derive(sqlx::FromRow)]
+struct LogReplayRow {
+    id: i64,
+    timestamp: i64,
+    method: String,
+    request_path: String,
+    request_host: String,
+    user_agent: String,
+    referer: String,
+    status_code: i64,
+    latency_ms: f64,
+}
+
+fn non_empty(v: &Option<String>) -> Option<&str> {
+    v.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+async fn load_captures(req: &ReplayRequest, limit: i64) -> Result<Vec<ReplayItem>> {
+    let pool = metrics::pool().ok_or_else(|| anyhow!("metrics 数据库未初始化"))?;
+    let mut qb = QueryBuilder::new(
+        "SELECT id, timestamp_ms, method, url, request_headers, request_body, request_body_size, status_code, latency_ms FROM captures WHERE timestamp_ms >= ",
+    );
+    qb.push_bind(req.start_time.saturating_mul(1000));
+    qb.push(" AND timestamp_ms <= ").push_bind(req.end_time.saturating_mul(1000).saturating_add(999));
+    if let Some(v) = non_empty(&req.session_id) {
+        qb.push(" AND session_id = ").push_bind(v.to_string());
+    }
+    if let Some(v) = non_empty(&req.upstream) {
+        qb.push(" AND upstream LIKE ").push_bind(format!("%{}%", v));
+    }
+    if let Some(v) = non_empty(&req.request_path) {
+        qb.push(" AND url LIKE ").push_bind(format!("%{}%", v));
+    }
+    if let Some(v) = non_empty(&req.client_ip) {
+        qb.push(" AND client_ip LIKE ").push_bind(format!("%{}%", v));
+    }
+    if let Some(v) = req.status_code.filter(|c| *c > 0) {
+        qb.push(" AND status_code = ").push_bind(v);
+    }
+    if let Some(v) = non_empty(&req.matched_route_id) {
+        qb.push(" AND route_id = ").push_bind(v.to_string());
+    }
+    qb.push(" ORDER BY timestamp_ms ASC, id ASC LIMIT ").push_bind(limit);
+
+    let rows: Vec<CaptureReplayRow> = qb
+        .build_query_as()
+        .fetch_all(&*pool)
+        .await
+        .context("查询抓包记录失败")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let path_and_query = url::Url::parse(&r.url)
+                .map(|u| match u.query() {
+                    Some(q) => format!("{}?{}", u.path(), q),
+                    None => u.path().to_string(),
+                })
+                .unwrap_or(r.url);
+            ReplayItem {
+                original_id: r.id,
+                timestamp_ms: r.timestamp_ms,
+                method: r.method,
+                path_and_query,
+                headers: serde_json::from_str(&r.request_headers).unwrap_or_default(),
+                body_truncated: r.request_body_size > r.request_body.len() as i64,
+                body: r.request_body,
+                status: r.status_code,
+                latency_ms: r.latency_ms,
+            }
+        })
+        .collect())
+}
+
+async fn load_request_logs(req: &ReplayRequest, limit: i64) -> Result<Vec<ReplayItem>> {
+    let pool = metrics::pool().ok_or_else(|| anyhow!("metrics 数据库未初始化"))?;
+    let mut qb = QueryBuilder::new(
+        "SELECT id, timestamp, method, request_path, request_host, user_agent, referer, status_code, latency_ms FROM request_logs WHERE timestamp >= ",
+    );
+    qb.push_bind(req.start_time);
+    qb.push(" AND timestamp <= ").push_bind(req.end_time);
+    if let Some(v) = non_empty(&req.listen_addr) {
+        qb.push(" AND listen_addr = ").push_bind(v.to_string());
+    }
+    if let Some(v) = non_empty(&req.upstream) {
+        qb.push(" AND upstream LIKE ").push_bind(format!("%{}%", v));
+    }
+    if let Some(v) = non_empty(&req.request_path) {
+        qb.push(" AND request_path LIKE ").push_bind(format!("%{}%", v));
+    }
+    if let Some(v) = non_empty(&req.client_ip) {
+        qb.push(" AND client_ip LIKE ").push_bind(format!("%{}%", v));
+    }
+    if let Some(v) = req.status_code.filter(|c| *c > 0) {
+        qb.push(" AND status_code = ").push_bind(v);
+    }
+    if let Some(v) = non_empty(&req.matched_route_id) {
+        qb.push(" AND matched_route_id = ").push_bind(v.to_string());
+    }
+    qb.push(" ORDER BY timestamp ASC, id ASC LIMIT ").push_bind(limit);
+
+    let rows: Vec<LogReplayRow> = qb
+        .build_query_as()
+        .fetch_all(&*pool)
+        .await
+        .context("查询请求日志失败")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let headers = [("host", r.request_host), ("user-agent", r.user_agent), ("referer", r.referer)]
+                .into_iter()
+                .filter(|(_, v)| !v.is_empty())
+                .map(|(k, v)| (k.to_string(), v))
+                .collect();
+            ReplayItem {
+                original_id: r.id,
+                timestamp_ms: r.timestamp * 1000,
+                method: r.method,
+                path_and_query: r.request_path,
+                headers,
+                body: Vec::new(),
+                body_truncated: false,
+                status: r.status_code,
+                latency_ms: r.latency_ms,
+            }
+        })
+        .collect())
+}
+
+fn p95(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let idx = ((values.len() as f64 * 0.95).ceil() as usize).clamp(1, values.len()) - 1;
+    values[idx]
+}
+
+fn avg(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+pub(crate) fn summarize(id: String, started_at: i64, results: Vec<ReplayResult>) -> ReplayReport {
+    let ok: Vec<&ReplayResult> = results.iter().filter(|r| r.error.is_none()).collect();
+    let skipped = results.iter().filter(|r| r.skipped).count();
+    let mut original: Vec<f64> = ok.iter().map(|r| r.original_latency_ms).collect();
+    let mut replay: Vec<f64> = ok.iter().map(|r| r.replay_latency_ms).collect();
+    ReplayReport {
+        id,
+        total: results.len(),
+        errors: results.len() - ok.len() - skipped,
+        skipped,
+        status_mismatches: results.iter().filter(|r| r.status_changed).count(),
+        original_avg_latency_ms: avg(&original),
+        replay_avg_latency_ms: avg(&replay),
+        original_p95_latency_ms: p95(&mut original),
+        replay_p95_latency_ms: p95(&mut replay),
+        started_at,
+        finished_at: chrono::Utc::now().timestamp(),
+        results,
+    }
+}
+
+/// 按原始时间间隔（除以 speed）依次发出请求；on_progress 在每个请求完成后调用
+pub(crate) async fn replay_items<F>(
+    client: reqwest::Client,
+    target: &str,
+    items: Vec<ReplayItem>,
+    speed: f64,
+    concurrency: usize,
+    on_progress: F,
+) -> Vec<ReplayResult>
+where
+    F: Fn(usize, usize) + Send + Sync + 'static,
+{
+    let total = items.len();
+    let base = target.trim_end_matches('/').to_string();
+    let first_ts = items.first().map(|i| i.timestamp_ms).unwrap_or(0);
+    let started = tokio::time::Instant::now();
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let done = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let on_progress = Arc::new(on_progress);
+
+    let mut tasks = Vec::with_capacity(total);
+    for item in items {
+        if speed > 0.0 {
+            let offset_ms = ((item.timestamp_ms - first_ts).max(0) as f64 / speed) as u64;
+            tokio::time::sleep_until(started + Duration::from_millis(offset_ms)).await;
+        }
+        let Ok(permit) = semaphore.clone().acquire_owned().await else {
+            break;
+        };
+        let client = client.clone();
+        let url = format!("{}{}", base, item.path_and_query);
+        let done = done.clone();
+        let on_progress = on_progress.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = permit;
+            if let Some(reason) = skip_reason(&item) {
+                on_progress(done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1, total);
+                return ReplayResult {
+                    original_id: item.original_id,
+                    method: item.method,
+                    url,
+                    original_status: item.status,
+                    replay_status: 0,
+                    original_latency_ms: item.latency_ms,
+                    replay_latency_ms: 0.0,
+                    status_changed: false,
+                    error: Some(reason.to_string()),
+                    skipped: true,
+                };
+            }
+            let method = Method::from_bytes(item.method.as_bytes()).unwrap_or(Method::GET);
+            let mut builder = client.request(method, &url);
+            for (k, v) in item.headers.iter().filter(|(k, _)| !skip_header(k)) {
+                if let (Ok(k), Ok(v)) = (HeaderName::from_bytes(k.as_bytes()), HeaderValue::from_str(v)) {
+                    builder = builder.header(k, v);
+                }
+            }
+            if !item.body.is_empty() {
+                builder = builder.body(item.body);
+            }
+
+            // 与代理记录的原始延迟口径一致，计时到响应体读完为止；响应体本身不比较
+            let t = std::time::Instant::now();
+            let (replay_status, error) = match builder.send().await {
+                Ok(resp) => {
+                    let status = resp.status().as_u16();
+                    match resp.bytes().await {
+                        Ok(_) => (status, None),
+                        Err(e) => (status, Some(e.to_string())),
+                    }
+                }
+                Err(e) => (0, Some(e.to_string())),
+            };
+            let replay_latency_ms = t.elapsed().as_secs_f64() * 1000.0;
+
+            on_progress(done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1, total);
+            ReplayResult {
+                original_id: item.original_id,
+                method: item.method,
+                url,
+                original_status: item.status,
+                replay_status,
+                original_latency_ms: item.latency_ms,
+                replay_latency_ms,
+                status_changed: replay_status as i64 != item.status,
+                error,
+                skipped: false,
+            }
+        }));
+    }
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for t in tasks {
+        if let Ok(r) = t.await {
+            results.push(r);
+        }
+    }
+    results
+}
+
+pub async fn run(app: tauri::AppHandle, req: ReplayRequest) -> Result<ReplayReport> {
+    let target = req.target.trim().to_string();
+    if !(target.starts_with("http://") || target.starts_with("https://")) {
+        return Err(anyhow!("回放目标地址无效: {target}"));
+    }
+    let speed = req.speed.unwrap_or(1.0);
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(anyhow!("回放速度无效: {speed}"));
+    }
+    let limit = req.limit.unwrap_or(1000).clamp(1, 10_000);
+
+    let items = match req.source {
+        ReplaySource::Captures => load_captures(&req, limit).await?,
+        ReplaySource::RequestLogs => load_request_logs(&req, limit).await?,
+    };
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(req.timeout_ms.unwrap_or(30_000)))
+        .build()
+        .context("创建回放 HTTP client 失败")?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let started_at = chrono::Utc::now().timestamp();
+    proxy::send_log_with_app(&app, format!("开始回放 {} 个请求 -> {}（速度 {speed}x）", items.len(), target));
+
+    let progress_app = app.clone();
+    let progress_id = id.clone();
+    let results = replay_items(client, &target, items, speed, req.concurrency.unwrap_or(16), move |done, total| {
+        let _ = progress_app.emit(
+            "replay-progress",
+            ReplayProgress {
+                id: &progress_id,
+                done,
+                total,
+            },
+        );
+    })
+    .await;
+
+    let report = summarize(id, started_at, results);
+    proxy::send_log_with_app(
+        &app,
+        format!(
+            "回放完成: 共 {} 个，失败 {}，状态码变化 {}，平均延迟 {:.1}ms -> {:.1}ms",
+            report.total,
+            report.errors,
+            report.status_mismatches,
+            report.original_avg_latency_ms,
+            report.replay_avg_latency_ms
+        ),
+    );
+    Ok(report)
+}
@@ -0,0 +1,74 @@
+// 流量回放（replay）模块的单元测试
+
+#[cfg(test)]
+mod replay_tests {
+    use crate::replay::{replay_items, summarize, ReplayItem};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::any;
+
+    #[tokio::test]
+    async fn test_replay_against_local_server() {
+        // 本地上游：/ok 返回 200，其他路径返回 404；抓包记录中的 Content-Length 不应被转发
+        let app = axum::Router::new().route(
+            "/{*path}",
+            any(|uri: axum::http::Uri, headers: HeaderMap| async move {
+                let status = if uri.path() == "/ok" { StatusCode::OK } else { StatusCode::NOT_FOUND };
+                assert_ne!(headers["content-length"], "999", "captured content-length forwarded");
+                status
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let item = |id: i64, ts: i64, path: &str, status: i64, body_truncated: bool| ReplayItem {
+            original_id: id,
+            timestamp_ms: ts,
+            method: "POST".into(),
+            path_and_query: path.into(),
+            headers: vec![("content-length".into(), "999".into())],
+            body: b"{}".to_vec(),
+            body_truncated,
+            status,
+            latency_ms: 10.0,
+        };
+        let items = vec![
+            item(1, 1_000, "/ok?x=1", 200, false),
+            item(2, 1_200, "/gone", 200, false),
+            // 抓包时请求体被截断：不发送，单独计数
+            item(3, 1_200, "/ok", 200, true),
+            // 认证头已脱敏：不带凭据回放只会得到 401，同样跳过
+            ReplayItem {
+                headers: vec![("authorization".into(), "[REDACTED]".into())],
+                ..item(4, 1_200, "/ok", 200, false)
+            },
+        ];
+
+        // 10 倍速：两个请求间隔 200ms 缩短为 20ms
+        let started = std::time::Instant::now();
+        let progress = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let p = progress.clone();
+        let results = replay_items(reqwest::Client::new(), &format!("http://{addr}/"), items, 10.0, 4, move |done, _| {
+            p.store(done, std::sync::atomic::Ordering::Relaxed);
+        })
+        .await;
+        assert!(started.elapsed() >= std::time::Duration::from_millis(20));
+        assert_eq!(progress.load(std::sync::atomic::Ordering::Relaxed), 4);
+
+        let report = summarize("r".into(), 0, results);
+        assert_eq!(report.total, 4);
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.skipped, 2);
+        let reason = |id: i64| {
+            let r = report.results.iter().find(|r| r.original_id == id).unwrap();
+            assert!(r.skipped && r.replay_status == 0);
+            r.error.clone().unwrap()
+        };
+        assert!(reason(3).contains("截断"));
+        assert!(reason(4).contains("脱敏"));
+        assert_eq!(report.status_mismatches, 1);
+        let changed = report.results.iter().find(|r| r.status_changed).unwrap();
+        assert_eq!((changed.original_id, changed.replay_status), (2, 404));
+        assert!(report.results[0].url.ends_with("/ok?x=1"));
+    }
+}
@@ -0,0 +1,949 @@
+use anyhow::{anyhow, Result};
+use axum::body::{Body, Bytes};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri};
+use axum::response::Response;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{api_keys, basic_auth, cache, coalesce, config, cors, error_pages, fault, forward_auth, jwt_auth, mirror, mock, oidc, traffic_split, vars};
+
+/// 预编译的 URL 重写规则
+pub struct CompiledRewrite {
+    re: Regex,
+    replacement: String,
+}
+
+/// 请求/响应体替换的匹配方式
+enum BodyMatcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+/// 预编译的请求/响应体替换规则
+pub struct CompiledBodyReplace {
+    matcher: BodyMatcher,
+    replace: String,
+    /// 已转小写的 Content-Type 列表；为空表示不过滤
+    content_types: Vec<String>,
+}
+
+/// 路由 headers 条件的匹配方式
+enum HeaderMatcher {
+    /// 精确匹配（不区分大小写）
+    Exact(String),
+    /// 含 `*` 的通配匹配，`*` 展开为 `.*`
+    Wildcard(Regex),
+}
+
+/// 路由 path 的匹配方式（对应 Nginx location 修饰符）
+enum PathMatcher {
+    /// `=`：完全相等
+    Exact(String),
+    /// 前缀匹配；`no_regex` 对应 `^~`，命中最长前缀后不再尝试正则路由
+    Prefix { prefix: String, no_regex: bool },
+    /// `~` / `~*`：正则匹配，捕获组可在 proxy_pass_path / set_headers 中引用
+    Regex(Regex),
+}
+
+impl PathMatcher {
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            PathMatcher::Exact(p) => path == p,
+            PathMatcher::Prefix { prefix, .. } => path.starts_with(prefix.as_str()),
+            PathMatcher::Regex(re) => re.is_match(path),
+        }
+    }
+}
+
+/// 单条路由的路径命中结果
+enum PathHit {
+    Exact,
+    Prefix { len: usize, no_regex: bool },
+    Regex(Vec<(String, String)>),
+}
+
+/// 路由未命中的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMiss {
+    Disabled,
+    Path,
+    Host,
+    Method,
+    Headers,
+}
+
+/// 路由匹配结果：命中的路由以及正则捕获组（`$1`.. 与命名分组）
+pub struct RouteMatch<'a> {
+    pub route: &'a CompiledRoute,
+    captures: Vec<(String, String)>,
+}
+
+impl RouteMatch<'_> {
+    /// 按名称查找捕获组（数字或命名分组）
+    pub fn capture(&self, name: &str) -> Option<String> {
+        self.captures
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    /// 是否为正则路由命中（此时 proxy_pass_path 作为完整的上游路径）
+    pub fn is_regex(&self) -> bool {
+        matches!(self.route.path_matcher, Some(PathMatcher::Regex(_)))
+    }
+}
+
+/// 预校验的 return 指令
+pub struct CompiledReturn {
+    pub status: StatusCode,
+    location: Option<String>,
+    body: Option<String>,
+    content_type: HeaderValue,
+}
+
+impl CompiledReturn {
+    fn compile(ret: &config::ReturnDirective, label: &str) -> Result<Self> {
+        let status = StatusCode::from_u16(ret.status)
+            .map_err(|_| anyhow!("路由 {label} 的 return.status 无效: {}", ret.status))?;
+        let location = ret
+            .location
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        if status.is_redirection() && status != StatusCode::NOT_MODIFIED && location.is_none() {
+            return Err(anyhow!("路由 {label} 的 return 为重定向 {}，但未配置 location", ret.status));
+        }
+        let content_type = match ret.content_type.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            Some(ct) => HeaderValue::from_str(ct)
+                .map_err(|_| anyhow!("路由 {label} 的 return.content_type 无效: {ct}"))?,
+            None => HeaderValue::from_static("text/plain; charset=utf-8"),
+        };
+        Ok(Self {
+            status,
+            location,
+            body: ret.body.clone(),
+            content_type,
+        })
+    }
+
+    /// 构造响应；location / body 中的变量由 lookup 展开
+    pub fn response<F>(&self, lookup: F) -> Response
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let body = self
+            .body
+            .as_deref()
+            .map(|b| vars::expand(b, &lookup))
+            .unwrap_or_default();
+        let has_body = !body.is_empty();
+
+        let mut resp = Response::new(Body::from(body));
+        *resp.status_mut() = self.status;
+        if has_body {
+            resp.headers_mut().insert(header::CONTENT_TYPE, self.content_type.clone());
+        }
+        if let Some(loc) = self.location.as_deref() {
+            if let Ok(v) = HeaderValue::from_str(&vars::expand(loc, &lookup)) {
+                resp.headers_mut().insert(header::LOCATION, v);
+            }
+        }
+        resp
+    }
+}
+
+/// 预解析的响应头修改规则（Header 名在编译期校验）
+struct ResponseHeaderRules {
+    remove: Vec<HeaderName>,
+    set: Vec<(HeaderName, String)>,
+    add: Vec<(HeaderName, String)>,
+}
+
+/// 预编译后的路由：持有原始配置以及所有已编译的正则
+pub struct CompiledRoute {
+    pub route: config::Route,
+    /// 未配置 path 时为 None，此类路由永远不会命中
+    path_matcher: Option<PathMatcher>,
+    url_rewrite_rules: Vec<CompiledRewrite>,
+    pub request_body_replace: Vec<CompiledBodyReplace>,
+    pub response_body_replace: Vec<CompiledBodyReplace>,
+    header_matchers: Vec<(String, HeaderMatcher)>,
+    response_headers: ResponseHeaderRules,
+    pub cors: Option<cors::CompiledCors>,
+    pub return_directive: Option<CompiledReturn>,
+    pub error_pages: error_pages::ErrorPages,
+    pub cache: Option<Arc<cache::RouteCache>>,
+    pub coalesce: Option<coalesce::Coalescer>,
+    pub mirrors: Vec<Arc<mirror::CompiledMirror>>,
+    pub splits: Vec<traffic_split::CompiledSplit>,
+    pub fault: Option<fault::CompiledFault>,
+    pub mock: Option<mock::MockSet>,
+    pub jwt_auth: Option<jwt_auth::CompiledJwt>,
+    pub forward_auth: Option<forward_auth::CompiledForwardAuth>,
+    pub oidc: Option<oidc::CompiledOidc>,
+    pub api_key: Option<api_keys::CompiledApiKeyAuth>,
+    // 路由自己的 Basic Auth（basic_auth_enable = true 时）
+    pub basic_auth: Option<basic_auth::BasicAuth>,
+    /// 覆盖了连接/读取超时的路由使用的独立上游 client (follow, nofollow)，由监听器启动时填充
+    pub clients: Option<(reqwest::Client, reqwest::Client)>,
+}
+
+/// 路由匹配诊断：单条路由的检查结果
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteCandidate {
+    /// 在配置中的下标
+    pub index: usize,
+    pub id: String,
+    pub label: String,
+    /// 所在分组（priority 降序、限定 Host 优先），越小越先匹配
+    pub group: usize,
+    /// 路径命中方式：exact / prefix / regex；未通过检查时为 None
+    pub hit: Option<&'static str>,
+    pub rejected: Option<MatchMiss>,
+    pub selected: bool,
+    pub reason: String,
+}
+
+fn hit_kind(hit: &PathHit) -> &'static str {
+    match hit {
+        PathHit::Exact => "exact",
+        PathHit::Prefix { no_regex: true, .. } => "prefix_no_regex",
+        PathHit::Prefix { .. } => "prefix",
+        PathHit::Regex(_) => "regex",
+    }
+}
+
+/// 单个监听规则的路由表：在监听器启动（或配置重载）时一次性编译，
+/// 请求热路径上不再调用 Regex::new
+pub struct RouteTable {
+    routes: Vec<CompiledRoute>,
+    /// 按 (priority 降序, 是否限定 Host) 分组的路由下标，组内保持配置顺序
+    groups: Vec<Vec<usize>>,
+}
+
+fn route_label(route: &config::Route) -> String {
+    match route.id.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(id) => format!("{} ({})", route.path.as_deref().unwrap_or("/"), id),
+        None => route.path.as_deref().unwrap_or("/").to_string(),
+    }
+}
+
+fn compile_body_replace(
+    rules: Option<&Vec<config::BodyReplaceRule>>,
+    label: &str,
+    field: &str,
+) -> Result<Vec<CompiledBodyReplace>> {
+    let mut out = Vec::new();
+    for (i, rule) in rules.map(|v| v.as_slice()).unwrap_or_default().iter().enumerate() {
+        // 禁用的规则同样校验，避免启用时才发现正则无效
+        let matcher = if rule.use_regex {
+            let re = Regex::new(&rule.find)
+                .map_err(|e| anyhow!("路由 {label} 的 {field}[{i}] 正则无效: {e}"))?;
+            BodyMatcher::Regex(re)
+        } else {
+            BodyMatcher::Literal(rule.find.clone())
+        };
+
+        if !rule.enabled {
+            continue;
+        }
+
+        let content_types = rule
+            .content_types
+            .as_deref()
+            .unwrap_or("")
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        out.push(CompiledBodyReplace {
+            matcher,
+            replace: rule.replace.clone(),
+            content_types,
+        });
+    }
+    Ok(out)
+}
+
+fn parse_header_name(raw: &str, label: &str, field: &str) -> Result<Option<HeaderName>> {
+    let key = raw.trim();
+    if key.is_empty() {
+        return Ok(None);
+    }
+    HeaderName::from_bytes(key.as_bytes())
+        .map(Some)
+        .map_err(|_| anyhow!("路由 {label} 的 {field} 包含无效的 Header 名: {key}"))
+}
+
+fn compile_header_values(
+    map: Option<&HashMap<String, String>>,
+    label: &str,
+    field: &str,
+) -> Result<Vec<(HeaderName, String)>> {
+    let mut out = Vec::new();
+    for (k, v) in map.into_iter().flatten() {
+        if let Some(name) = parse_header_name(k, label, field)? {
+            out.push((name, v.clone()));
+        }
+    }
+    // HashMap 无序：排序后保证多次启动行为一致
+    out.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+    Ok(out)
+}
+
+impl CompiledRoute {
+    /// preview 为 true 时只编译路由诊断用到的部分：跳过缓存、错误页与各类认证，
+    /// 避免创建缓存目录、注册全局缓存或读取密钥 / 密码文件
+    pub fn compile(route: &config::Route, preview: bool) -> Result<Self> {
+        let label = route_label(route);
+
+        let path_matcher = match route.path.as_deref() {
+            None => None,
+            Some(p) => Some(match route.match_type.unwrap_or_default() {
+                config::RouteMatchType::Exact => PathMatcher::Exact(p.to_string()),
+                config::RouteMatchType::Prefix => PathMatcher::Prefix {
+                    prefix: p.to_string(),
+                    no_regex: false,
+                },
+                config::RouteMatchType::PrefixNoRegex => PathMatcher::Prefix {
+                    prefix: p.to_string(),
+                    no_regex: true,
+                },
+                kind @ (config::RouteMatchType::Regex | config::RouteMatchType::RegexCaseInsensitive) => {
+                    let re = RegexBuilder::new(p)
+                        .case_insensitive(kind == config::RouteMatchType::RegexCaseInsensitive)
+                        .build()
+                        .map_err(|e| anyhow!("路由 {label} 的 path 正则无效: {e}"))?;
+                    PathMatcher::Regex(re)
+                }
+            }),
+        };
+
+        let mut url_rewrite_rules = Vec::new();
+        for (i, rule) in route.url_rewrite_rules.iter().flatten().enumerate() {
+            let re = Regex::new(&rule.pattern)
+                .map_err(|e| anyhow!("路由 {label} 的 url_rewrite_rules[{i}] 正则无效: {e}"))?;
+            if rule.enabled {
+                url_rewrite_rules.push(CompiledRewrite {
+                    re,
+                    replacement: rule.replacement.clone(),
+                });
+            }
+        }
+
+        let request_body_replace =
+            compile_body_replace(route.request_body_replace.as_ref(), &label, "request_body_replace")?;
+        let response_body_replace =
+            compile_body_replace(route.response_body_replace.as_ref(), &label, "response_body_replace")?;
+
+        let mut header_matchers = Vec::new();
+        for (key, expected) in route.headers.iter().flatten() {
+            let matcher = if expected.contains('*') {
+                let pattern = expected.replace('*', ".*");
+                let re = Regex::new(&pattern)
+                    .map_err(|e| anyhow!("路由 {label} 的 headers.{key} 通配模式无效: {e}"))?;
+                HeaderMatcher::Wildcard(re)
+            } else {
+                HeaderMatcher::Exact(expected.trim().to_string())
+            };
+            header_matchers.push((key.clone(), matcher));
+        }
+
+        let mut remove = Vec::new();
+        for k in route.remove_response_headers.iter().flatten() {
+            if let Some(name) = parse_header_name(k, &label, "remove_response_headers")? {
+                remove.push(name);
+            }
+        }
+        let response_headers = ResponseHeaderRules {
+            remove,
+            set: compile_header_values(route.set_response_headers.as_ref(), &label, "set_response_headers")?,
+            add: compile_header_values(route.add_response_headers.as_ref(), &label, "add_response_headers")?,
+        };
+
+        let cors = match route.cors.as_ref() {
+            Some(policy) => {
+                let compiled = cors::CompiledCors::compile(policy, &label)?;
+                policy.enabled.then_some(compiled)
+            }
+            None => None,
+        };
+
+        let return_directive = route
+            .return_directive
+            .as_ref()
+            .map(|ret| CompiledReturn::compile(ret, &label))
+            .transpose()?;
+
+        let error_pages = error_pages::ErrorPages::compile(
+            route.error_pages.as_ref().filter(|_| !preview),
+            &format!("路由 {label}"),
+        )?;
+
+        let cache = match route.cache.as_ref().filter(|c| c.enabled && !preview) {
+            Some(c) => {
+                let id = route.id.as_deref().unwrap_or("").to_string();
+                Some(cache::RouteCache::new(id, c, &label)?)
+            }
+            None => None,
+        };
+
+        let coalesce = route
+            .coalesce
+            .as_ref()
+            .filter(|c| c.enabled)
+            .map(coalesce::Coalescer::new);
+
+        let mirrors = route
+            .mirrors
+            .iter()
+            .flatten()
+            .map(|m| mirror::CompiledMirror::compile(m, &label).map(Arc::new))
+            .collect::<Result<Vec<_>>>()?;
+
+        let splits = route
+            .splits
+            .iter()
+            .flatten()
+            .map(|s| traffic_split::CompiledSplit::compile(s, &label))
+            .collect::<Result<Vec<_>>>()?;
+
+        let fault = route
+            .fault
+            .as_ref()
+            .filter(|f| f.enabled)
+            .map(|f| fault::CompiledFault::compile(f, &label))
+            .transpose()?;
+
+        let mock = route
+            .mock
+            .as_ref()
+            .filter(|m| m.enabled)
+            .map(|m| mock::MockSet::load(m, &label))
+            .transpose()?;
+
+        let jwt_auth = route
+            .jwt_auth
+            .as_ref()
+            .filter(|j| j.enabled && !preview)
+            .map(|j| jwt_auth::CompiledJwt::compile(j, &label))
+            .transpose()?;
+
+        let forward_auth = route
+            .forward_auth
+            .as_ref()
+            .filter(|f| f.enabled && !preview)
+            .map(|f| forward_auth::CompiledForwardAuth::compile(f, &label))
+            .transpose()?;
+
+        // OIDC 回调 / 登出路径默认位于路由前缀下，且必须能匹配到本路由，否则身份提供方的回调到不了这里
+        let oidc = match route.oidc.as_ref().filter(|o| o.enabled && !preview) {
+            Some(o) => {
+                let base = match path_matcher.as_ref() {
+                    Some(PathMatcher::Prefix { prefix, .. }) => prefix.as_str(),
+                    _ => "",
+                };
+                let compiled = oidc::CompiledOidc::compile(o, base, &label)?;
+                for p in compiled.endpoint_paths() {
+                    if !path_matcher.as_ref().is_some_and(|m| m.is_match(p)) {
+                        return Err(anyhow!("路由 {label} 的 oidc 回调 / 登出路径 {p} 不在该路由的 path 范围内"));
+                    }
+                }
+                Some(compiled)
+            }
+            None => None,
+        };
+
+        let api_key = route
+            .api_key
+            .as_ref()
+            .filter(|a| a.enabled && !preview)
+            .map(|a| api_keys::CompiledApiKeyAuth::compile(a, &label))
+            .transpose()?;
+
+        let basic_auth = if preview {
+            None
+        } else {
+            basic_auth::BasicAuth::for_route(route, &label)?
+        };
+
+        Ok(Self {
+            route: route.clone(),
+            path_matcher,
+            url_rewrite_rules,
+            request_body_replace,
+            response_body_replace,
+            header_matchers,
+            response_headers,
+            cors,
+            return_directive,
+            error_pages,
+            cache,
+            coalesce,
+            mirrors,
+            splits,
+            fault,
+            mock,
+            jwt_auth,
+            forward_auth,
+            oidc,
+            api_key,
+            basic_auth,
+            clients: None,
+        })
+    }
+
+    #[inline]
+    fn has_host(&self) -> bool {
+        self.route
+            .host
+            .as_deref()
+            .is_some_and(|h| !h.trim().is_empty())
+    }
+
+    /// 检查该路由能否处理请求：先匹配路径，再依次检查 Host / Method / Header 条件
+    fn check(
+        &self,
+        host: &str,
+        path: &str,
+        method: &Method,
+        headers: &HeaderMap,
+    ) -> std::result::Result<PathHit, MatchMiss> {
+        let r = &self.route;
+        if !r.enabled {
+            return Err(MatchMiss::Disabled);
+        }
+
+        // 1. Path 匹配
+        let hit = match self.path_matcher.as_ref() {
+            Some(PathMatcher::Exact(p)) if path == p => PathHit::Exact,
+            Some(PathMatcher::Prefix { prefix, no_regex }) if path.starts_with(prefix.as_str()) => {
+                PathHit::Prefix {
+                    len: prefix.len(),
+                    no_regex: *no_regex,
+                }
+            }
+            Some(PathMatcher::Regex(re)) => {
+                let Some(caps) = re.captures(path) else {
+                    return Err(MatchMiss::Path);
+                };
+                let mut captures = Vec::new();
+                for (i, name) in re.capture_names().enumerate().skip(1) {
+                    if let Some(m) = caps.get(i) {
+                        captures.push((i.to_string(), m.as_str().to_string()));
+                        if let Some(name) = name {
+                            captures.push((name.to_string(), m.as_str().to_string()));
+                        }
+                    }
+                }
+                PathHit::Regex(captures)
+            }
+            _ => return Err(MatchMiss::Path),
+        };
+
+        // 2. Host 匹配（未配置 Host 的路由匹配所有请求）
+        if let Some(h) = r.host.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            if !host_matches(h, host) {
+                return Err(MatchMiss::Host);
+            }
+        }
+
+        // 3. Method 匹配
+        if let Some(ref methods) = r.methods {
+            if !methods.iter().any(|m| m.eq_ignore_ascii_case(method.as_str())) {
+                return Err(MatchMiss::Method);
+            }
+        }
+
+        // 4. Header 匹配（通配模式已预编译）
+        if !self.headers_match(headers) {
+            return Err(MatchMiss::Headers);
+        }
+
+        Ok(hit)
+    }
+
+    #[inline]
+    fn headers_match(&self, headers: &HeaderMap) -> bool {
+        self.header_matchers.iter().all(|(key, matcher)| {
+            let actual = headers
+                .get(key.as_str())
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            match matcher {
+                HeaderMatcher::Exact(expected) => actual.eq_ignore_ascii_case(expected),
+                HeaderMatcher::Wildcard(re) => re.is_match(actual),
+            }
+        })
+    }
+
+    /// 认证模块（JWT / 外部认证 / OIDC / API Key）写入上游请求的身份请求头
+    pub fn identity_headers(&self) -> Vec<&HeaderName> {
+        let mut names: Vec<&HeaderName> = Vec::new();
+        if let Some(jwt) = self.jwt_auth.as_ref() {
+            names.extend(jwt.identity_headers());
+        }
+        if let Some(fa) = self.forward_auth.as_ref() {
+            names.extend(fa.identity_headers());
+        }
+        if let Some(oidc) = self.oidc.as_ref() {
+            names.extend(oidc.identity_headers());
+        }
+        if let Some(ak) = self.api_key.as_ref() {
+            names.extend(ak.identity_headers());
+        }
+        names
+    }
+
+    /// 依次应用 url_rewrite_rules，返回重写后的 URI
+    pub fn rewrite_uri(&self, uri: &Uri) -> Uri {
+        let mut final_uri = uri.clone();
+        for rule in &self.url_rewrite_rules {
+            let original = final_uri.to_string();
+            let rewritten = rule.re.replace_all(&original, rule.replacement.as_str());
+            if rewritten != original {
+                if let Ok(new_uri) = rewritten.parse::<Uri>() {
+                    final_uri = new_uri;
+                }
+            }
+        }
+        final_uri
+    }
+
+    /// 应用 remove/set/add_response_headers，值中的变量由 lookup 展开
+    pub fn apply_response_headers<F>(&self, headers: &mut HeaderMap, lookup: F)
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let rules = &self.response_headers;
+        for name in &rules.remove {
+            headers.remove(name);
+        }
+        for (name, raw) in &rules.set {
+            if let Ok(v) = HeaderValue::from_str(&vars::expand(raw, &lookup)) {
+                headers.insert(name.clone(), v);
+            }
+        }
+        for (name, raw) in &rules.add {
+            if let Ok(v) = HeaderValue::from_str(&vars::expand(raw, &lookup)) {
+                headers.append(name.clone(), v);
+            }
+        }
+    }
+}
+
+/// 对请求/响应体执行替换规则；body 非 UTF-8 时原样返回
+pub fn apply_body_replace(
+    rules: &[CompiledBodyReplace],
+    bytes: Bytes,
+    content_type: Option<&HeaderValue>,
+) -> Bytes {
+    if rules.is_empty() {
+        return bytes;
+    }
+
+    let Ok(mut body) = String::from_utf8(bytes.to_vec()) else {
+        return bytes;
+    };
+
+    // 解析 Content-Type，去除 charset 等参数
+    let pure_content_type = content_type
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_lowercase();
+
+    for rule in rules {
+        if !rule.content_types.is_empty()
+            && !rule.content_types.contains(&pure_content_type)
+        {
+            continue;
+        }
+
+        body = match &rule.matcher {
+            BodyMatcher::Regex(re) => re.replace_all(&body, rule.replace.as_str()).into_owned(),
+            BodyMatcher::Literal(find) => body.replace(find.as_str(), &rule.replace),
+        };
+    }
+
+    Bytes::from(body.into_bytes())
+}
+
+impl RouteTable {
+    pub fn compile(rule: &config::ListenRule) -> Result<Self> {
+        Self::from_routes(&rule.routes)
+    }
+
+    /// 供路由诊断使用的路由表，编译时没有副作用（见 CompiledRoute::compile）
+    pub fn compile_preview(rule: &config::ListenRule) -> Result<Self> {
+        Self::build(&rule.routes, true)
+    }
+
+    pub fn routes_mut(&mut self) -> impl Iterator<Item = &mut CompiledRoute> {
+        self.routes.iter_mut()
+    }
+
+    pub fn from_routes(routes: &[config::Route]) -> Result<Self> {
+        Self::build(routes, false)
+    }
+
+    fn build(routes: &[config::Route], preview: bool) -> Result<Self> {
+        let routes = routes
+            .iter()
+            .map(|r| CompiledRoute::compile(r, preview))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut keys: Vec<(i32, bool)> = routes
+            .iter()
+            .map(|cr| (cr.route.priority.unwrap_or(0), cr.has_host()))
+            .collect();
+        keys.sort_unstable_by(|a, b| b.cmp(a));
+        keys.dedup();
+
+        let groups = keys
+            .into_iter()
+            .map(|key| {
+                (0..routes.len())
+                    .filter(|&i| (routes[i].route.priority.unwrap_or(0), routes[i].has_host()) == key)
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self { routes, groups })
+    }
+
+    /// 按 Nginx location 规则选择路由：
+    /// 1. priority 高的分组优先；同优先级下限定 Host 的路由优先于未限定的
+    /// 2. 组内：`exact` 命中立即返回；否则取最长前缀，若其为 `prefix_no_regex` 则直接采用；
+    ///    否则按配置顺序尝试正则路由，首个命中者胜出；都未命中时回退到最长前缀
+    pub fn match_route(
+        &self,
+        request_host: &str,
+        path: &str,
+        method: &Method,
+        headers: &HeaderMap,
+    ) -> (Option<RouteMatch<'_>>, String) {
+        let host = normalize_host(request_host);
+
+        for group in &self.groups {
+            if let Some(m) = self.match_group(group, host, path, method, headers) {
+                let id = m.route.route.id.as_deref().unwrap_or("").to_string();
+                return (Some(m), id);
+            }
+        }
+        (None, String::new())
+    }
+
+    /// 匹配诊断：按匹配顺序列出每条路由的检查结果、是否最终命中以及原因
+    pub fn explain(
+        &self,
+        request_host: &str,
+        path: &str,
+        method: &Method,
+        headers: &HeaderMap,
+    ) -> Vec<RouteCandidate> {
+        let host = normalize_host(request_host);
+        let chosen = self
+            .match_route(request_host, path, method, headers)
+            .0
+            .and_then(|m| self.routes.iter().position(|r| std::ptr::eq(r, m.route)));
+        let chosen_group = chosen.and_then(|c| self.groups.iter().position(|g| g.contains(&c)));
+        let chosen_kind = chosen.and_then(|c| {
+            self.routes[c]
+                .check(host, path, method, headers)
+                .ok()
+                .map(|h| hit_kind(&h))
+        });
+
+        let mut out = Vec::with_capacity(self.routes.len());
+        for (gi, group) in self.groups.iter().enumerate() {
+            for &i in group {
+                let cr = &self.routes[i];
+                let r = &cr.route;
+                let result = cr.check(host, path, method, headers);
+                let selected = chosen == Some(i);
+                let winner = chosen.map(|c| route_label(&self.routes[c].route)).unwrap_or_default();
+
+                let reason = match &result {
+                    _ if selected => "最终命中".to_string(),
+                    Err(MatchMiss::Disabled) => "路由已禁用".to_string(),
+                    Err(MatchMiss::Path) => match r.path.as_deref() {
+                        Some(p) => format!(
+                            "path 不匹配（{:?} {p}）",
+                            r.match_type.unwrap_or_default()
+                        ),
+                        None => "未配置 path".to_string(),
+                    },
+                    Err(MatchMiss::Host) => format!(
+                        "Host 不匹配（配置 {}，请求 {host}）",
+                        r.host.as_deref().unwrap_or("")
+                    ),
+                    Err(MatchMiss::Method) => format!(
+                        "方法不匹配（允许 {}）",
+                        r.methods.as_deref().unwrap_or_default().join(",")
+                    ),
+                    Err(MatchMiss::Headers) => "请求头条件不满足".to_string(),
+                    Ok(_) if chosen_group.is_some_and(|cg| cg < gi) => {
+                        format!("更高优先级分组中的 {winner} 已命中")
+                    }
+                    Ok(hit) => match (hit_kind(hit), chosen_kind) {
+                        (_, Some("exact")) => format!("精确匹配 {winner} 优先"),
+                        ("regex", Some("prefix_no_regex")) => format!("^~ 前缀 {winner} 命中后不再尝试正则"),
+                        ("regex", _) => format!("配置在前的正则路由 {winner} 已命中"),
+                        (_, Some("regex")) => format!("正则路由 {winner} 优先于普通前缀"),
+                        _ => format!("最长前缀 {winner} 优先"),
+                    },
+                };
+
+                out.push(RouteCandidate {
+                    index: i,
+                    id: r.id.clone().unwrap_or_default(),
+                    label: route_label(r),
+                    group: gi,
+                    hit: result.as_ref().ok().map(hit_kind),
+                    rejected: result.err(),
+                    selected,
+                    reason,
+                });
+            }
+        }
+        out
+    }
+
+    fn match_group(
+        &self,
+        group: &[usize],
+        host: &str,
+        path: &str,
+        method: &Method,
+        headers: &HeaderMap,
+    ) -> Option<RouteMatch<'_>> {
+        // 第一轮：exact 与前缀路由
+        let mut longest: Option<(&CompiledRoute, usize, bool)> = None;
+        for &i in group {
+            let cr = &self.routes[i];
+            if matches!(cr.path_matcher, Some(PathMatcher::Regex(_))) {
+                continue;
+            }
+            match cr.check(host, path, method, headers) {
+                Ok(PathHit::Exact) => {
+                    return Some(RouteMatch {
+                        route: cr,
+                        captures: Vec::new(),
+                    });
+                }
+                // 同长度时保留先出现的路由
+                Ok(PathHit::Prefix { len, no_regex })
+                    if longest.is_none_or(|(_, best_len, _)| len > best_len) =>
+                {
+                    longest = Some((cr, len, no_regex));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((cr, _, true)) = longest {
+            return Some(RouteMatch {
+                route: cr,
+                captures: Vec::new(),
+            });
+        }
+
+        // 第二轮：按配置顺序尝试正则路由
+        for &i in group {
+            let cr = &self.routes[i];
+            if !matches!(cr.path_matcher, Some(PathMatcher::Regex(_))) {
+                continue;
+            }
+            if let Ok(PathHit::Regex(captures)) = cr.check(host, path, method, headers) {
+                return Some(RouteMatch { route: cr, captures });
+            }
+        }
+
+        longest.map(|(cr, _, _)| RouteMatch {
+            route: cr,
+            captures: Vec::new(),
+        })
+    }
+}
+
+/// 校验整份配置中所有路由的正则/通配模式，供保存配置时提前报错
+pub fn validate_config(cfg: &config::Config) -> Result<()> {
+    for rule in &cfg.rules {
+        RouteTable::compile(rule).map_err(|e| anyhow!("监听规则 {}: {e}", rule.listen_addr))?;
+        crate::error_pages::ErrorPages::compile(rule.error_pages.as_ref(), "监听规则")
+            .map_err(|e| anyhow!("监听规则 {}: {e}", rule.listen_addr))?;
+        if crate::https_redirect::redirect_listen_addr(rule).is_some() {
+            crate::https_redirect::https_redirect_status(rule)
+                .map_err(|e| anyhow!("监听规则 {}: {e}", rule.listen_addr))?;
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn normalize_host(host: &str) -> &str {
+    // 去除端口号，只保留主机名部分
+    let host = host.trim();
+    // IPv6 字面量保留方括号：[::1]:8443 -> [::1]
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |i| &host[..=i]);
+    }
+    match host.split_once(':') {
+        // 不带方括号的 IPv6 地址（多个冒号）没有端口可去
+        Some((name, port)) if !port.contains(':') => name,
+        _ => host,
+    }
+}
+
+#[inline]
+fn strip_brackets(host: &str) -> &str {
+    host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host)
+}
+
+/// 检查请求的 Host 是否匹配路由配置的 Host
+/// 支持：
+/// 1. 精确匹配（不区分大小写）
+/// 2. 通配符匹配：*.example.com 匹配 example.com, www.example.com, api.example.com 等
+pub fn host_matches(route_host: &str, request_host: &str) -> bool {
+    // 比较时去掉 IPv6 的方括号，路由配置写 ::1 或 [::1] 均可
+    let route_host = strip_brackets(normalize_host(route_host));
+    let request_host = strip_brackets(normalize_host(request_host));
+
+    // 如果请求 Host 为空，只匹配路由 Host 也为空的情况
+    if request_host.is_empty() {
+        return route_host.is_empty();
+    }
+
+    // 精确匹配（不区分大小写）
+    if route_host.eq_ignore_ascii_case(request_host) {
+        return true;
+    }
+
+    // 通配符匹配：*.example.com
+    if let Some(suffix) = route_host.strip_prefix("*.") {
+        if !suffix.is_empty() && request_host.ends_with(suffix) {
+            // 确保匹配的是完整的域名部分，而不是部分匹配
+            // 例如 *.example.com 应该匹配 www.example.com，但不匹配 evil-example.com
+            let prefix_len = request_host.len() - suffix.len();
+            if prefix_len > 0 {
+                // 检查是否有正确的分隔符（点）
+                let prefix = &request_host[..prefix_len];
+                // 前缀不能包含点（确保是子域名），且不能为空
+                if !prefix.contains('.') && !prefix.is_empty() {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
@@ -0,0 +1,290 @@
+// 路由匹配（route_table）的单元测试
+// 以表驱动方式覆盖 Nginx 风格的 location 优先级规则
+
+#[cfg(test)]
+mod route_table_tests {
+    use crate::config;
+    use crate::route_table::RouteTable;
+    use axum::http::{HeaderMap, Method, StatusCode};
+
+    /// 用 TOML 片段构造路由，字段与配置文件中的 [[rules.routes]] 一致
+    fn route(toml_src: &str) -> config::Route {
+        toml::from_str(toml_src).expect("invalid route toml")
+    }
+
+    fn table(routes: &[&str]) -> RouteTable {
+        let routes: Vec<_> = routes.iter().map(|s| route(s)).collect();
+        RouteTable::from_routes(&routes).expect("compile route table")
+    }
+
+    fn matched_id(t: &RouteTable, host: &str, path: &str) -> String {
+        t.match_route(host, path, &Method::GET, &HeaderMap::new()).1
+    }
+
+    #[test]
+    fn test_nginx_precedence_table() {
+        let t = table(&[
+            r#"id = "root"
+               path = "/""#,
+            r#"id = "exact-login"
+               path = "/login"
+               match_type = "exact""#,
+            r#"id = "static"
+               path = "/static/"
+               match_type = "prefix_no_regex""#,
+            r#"id = "images"
+               path = "/images/"
+               match_type = "prefix""#,
+            r#"id = "img-regex"
+               path = '\.(png|jpg)$'
+               match_type = "regex""#,
+            r#"id = "api-ci"
+               path = '^/API/'
+               match_type = "regex_case_insensitive""#,
+            r#"id = "api-v1"
+               path = "/api/v1/""#,
+        ]);
+
+        // (请求路径, 期望命中的路由 id)
+        let cases = [
+            ("/login", "exact-login"),          // exact 优先于一切
+            ("/login/x", "root"),               // exact 不做前缀匹配
+            ("/static/a.png", "static"),        // ^~ 命中后跳过正则
+            ("/images/a.png", "img-regex"),     // 普通前缀让位于正则
+            ("/images/a.txt", "images"),        // 正则未命中时回退到最长前缀
+            ("/api/v1/users", "api-ci"),        // 正则优先于更长的普通前缀
+            ("/Api/v2", "api-ci"),              // ~* 不区分大小写
+            ("/other", "root"),
+        ];
+        for (path, want) in cases {
+            assert_eq!(matched_id(&t, "example.com", path), want, "path={path}");
+        }
+    }
+
+    #[test]
+    fn test_regex_routes_first_match_wins() {
+        let t = table(&[
+            r#"id = "first"
+               path = '^/a'
+               match_type = "~""#,
+            r#"id = "second"
+               path = '^/a/b'
+               match_type = "~""#,
+        ]);
+        assert_eq!(matched_id(&t, "", "/a/b/c"), "first");
+    }
+
+    #[test]
+    fn test_longest_prefix_and_tie_break() {
+        let t = table(&[
+            r#"id = "short"
+               path = "/a""#,
+            r#"id = "long"
+               path = "/a/b""#,
+            r#"id = "long-dup"
+               path = "/a/b""#,
+        ]);
+        let cases = [("/a/b/c", "long"), ("/a/x", "short"), ("/b", "")];
+        for (path, want) in cases {
+            assert_eq!(matched_id(&t, "", path), want, "path={path}");
+        }
+    }
+
+    #[test]
+    fn test_host_and_priority_groups() {
+        let t = table(&[
+            r#"id = "any-host-long"
+               path = "/app/deep""#,
+            r#"id = "host-short"
+               path = "/"
+               host = "www.example.com""#,
+            r#"id = "pinned"
+               path = "/pinned"
+               priority = 10"#,
+            r#"id = "low"
+               path = "/"
+               priority = -1"#,
+        ]);
+        let cases = [
+            ("www.example.com", "/app/deep/x", "host-short"), // 限定 Host 的分组先匹配
+            ("other.com", "/app/deep/x", "any-host-long"),
+            ("www.example.com", "/pinned/1", "pinned"),       // 显式 priority 最优先
+            ("other.com", "/zzz", "low"),                     // 低优先级兜底
+        ];
+        for (host, path, want) in cases {
+            assert_eq!(matched_id(&t, host, path), want, "host={host} path={path}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_host_and_ipv6() {
+        use crate::route_table::{host_matches, normalize_host};
+
+        let cases = [
+            ("example.com", "example.com"),
+            ("example.com:8080", "example.com"),
+            (" example.com ", "example.com"),
+            ("[::1]:8443", "[::1]"),
+            ("[2001:db8::1]", "[2001:db8::1]"),
+            ("2001:db8::1", "2001:db8::1"),
+            ("", ""),
+        ];
+        for (host, want) in cases {
+            assert_eq!(normalize_host(host), want, "host={host}");
+        }
+
+        assert!(host_matches("[::1]", "[::1]:8443"));
+        assert!(host_matches("::1", "[::1]:8443"));
+        assert!(!host_matches("::2", "[::1]:8443"));
+        assert!(host_matches("example.com", "EXAMPLE.com:8080"));
+
+        // IPv6 Host 也能按路由的 host 条件命中
+        let t = table(&[r#"id = "v6"
+               path = "/"
+               host = "[::1]""#]);
+        assert_eq!(matched_id(&t, "[::1]:8443", "/x"), "v6");
+    }
+
+    #[test]
+    fn test_method_filter() {
+        let t = table(&[
+            r#"id = "post-only"
+               path = "/submit"
+               methods = ["POST"]"#,
+            r#"id = "fallback"
+               path = "/""#,
+        ]);
+        let (_, id) = t.match_route("", "/submit", &Method::POST, &HeaderMap::new());
+        assert_eq!(id, "post-only");
+        let (_, id) = t.match_route("", "/submit", &Method::GET, &HeaderMap::new());
+        assert_eq!(id, "fallback");
+    }
+
+    #[test]
+    fn test_regex_captures() {
+        let t = table(&[r#"id = "users"
+               path = '^/users/(\d+)/(?P<tab>\w+)$'
+               match_type = "regex"
+               proxy_pass_path = "/v2/user/$1/$tab""#]);
+        let (m, _) = t.match_route("", "/users/42/posts", &Method::GET, &HeaderMap::new());
+        let m = m.expect("regex route should match");
+        assert!(m.is_regex());
+        assert_eq!(m.capture("1").as_deref(), Some("42"));
+        assert_eq!(m.capture("2").as_deref(), Some("posts"));
+        assert_eq!(m.capture("tab").as_deref(), Some("posts"));
+        assert_eq!(m.capture("3"), None);
+    }
+
+    #[test]
+    fn test_invalid_path_regex_rejected() {
+        let routes = vec![route(
+            r#"path = "/(unclosed"
+               match_type = "regex""#,
+        )];
+        assert!(RouteTable::from_routes(&routes).is_err());
+    }
+
+    #[test]
+    fn test_return_directive() {
+        let t = table(&[r#"id = "to-https"
+               path = "/"
+               [return]
+               status = 301
+               location = "https://$host_name$request_uri""#]);
+        let (m, _) = t.match_route("a.com", "/x", &Method::GET, &HeaderMap::new());
+        let ret = m.unwrap().route.return_directive.as_ref().expect("return compiled");
+        let resp = ret.response(|name| match name {
+            "host_name" => Some("a.com".to_string()),
+            "request_uri" => Some("/x?y=1".to_string()),
+            _ => None,
+        });
+        assert_eq!(resp.status(), 301);
+        assert_eq!(resp.headers()["location"], "https://a.com/x?y=1");
+
+        // 重定向缺少 location 时拒绝编译
+        let routes = vec![route(
+            r#"path = "/"
+               [return]
+               status = 302"#,
+        )];
+        assert!(RouteTable::from_routes(&routes).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_route_error_pages_negotiation() {
+        let t = table(&[r#"id = "api"
+               path = "/"
+               [[error_pages]]
+               status = [502, 504]
+               html = "<p>$status $request_uri</p>""#]);
+        let (m, _) = t.match_route("", "/", &Method::GET, &HeaderMap::new());
+        let pages = &m.unwrap().route.error_pages;
+        let lookup = |name: &str| match name {
+            "request_uri" => Some("/<x>".to_string()),
+            "request_id" => Some("rid".to_string()),
+            _ => None,
+        };
+
+        // HTML 模板中的变量做实体转义
+        let resp = pages.render(StatusCode::BAD_GATEWAY, "text/html", lookup).unwrap();
+        assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"<p>502 /&lt;x&gt;</p>");
+
+        // JSON 客户端在未配置 json 模板时得到内置 JSON
+        let resp = pages.render(StatusCode::GATEWAY_TIMEOUT, "application/json", lookup).unwrap();
+        assert_eq!(resp.headers()["content-type"], "application/json; charset=utf-8");
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], 504);
+        assert_eq!(json["request_id"], "rid");
+
+        assert!(pages.render(StatusCode::NOT_FOUND, "text/html", lookup).is_none());
+    }
+
+    #[test]
+    fn test_route_explain() {
+        use crate::route_table::MatchMiss;
+
+        let t = table(&[
+            r#"id = "root"
+               path = "/""#,
+            r#"id = "admin"
+               path = "/"
+               host = "admin.example.com""#,
+            r#"id = "api-post"
+               path = "/api/"
+               methods = ["POST"]"#,
+            r#"id = "img-regex"
+               path = '\.png$'
+               match_type = "regex""#,
+            r#"id = "images"
+               path = "/images/""#,
+        ]);
+
+        let c = t.explain("example.com", "/images/a.png", &Method::GET, &HeaderMap::new());
+        assert_eq!(c.len(), 5);
+        assert_eq!(c.iter().filter(|x| x.selected).count(), 1);
+        let by_id = |id: &str| c.iter().find(|x| x.id == id).unwrap();
+
+        assert!(by_id("img-regex").selected);
+        assert_eq!(by_id("img-regex").hit, Some("regex"));
+        assert_eq!(by_id("images").hit, Some("prefix"));
+        assert!(by_id("images").reason.contains("正则路由"), "{}", by_id("images").reason);
+        assert_eq!(by_id("root").hit, Some("prefix"));
+        assert_eq!(by_id("admin").rejected, Some(MatchMiss::Host));
+        assert_eq!(by_id("api-post").rejected, Some(MatchMiss::Path));
+
+        let c = t.explain("example.com", "/api/x", &Method::GET, &HeaderMap::new());
+        let api = c.iter().find(|x| x.id == "api-post").unwrap();
+        assert_eq!(api.rejected, Some(MatchMiss::Method));
+        assert!(api.reason.contains("POST"));
+        assert!(c.iter().find(|x| x.id == "root").unwrap().selected);
+
+        // 未命中任何路由时所有候选都不被选中
+        let t = table(&[r#"id = "only"
+               path = "/only""#]);
+        let c = t.explain("example.com", "/x", &Method::GET, &HeaderMap::new());
+        assert!(c.iter().all(|x| !x.selected && x.rejected == Some(MatchMiss::Path)));
+    }
+}
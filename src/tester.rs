@@ -0,0 +1,369 @@
+use anyhow::{anyhow, Context, Result};
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, Request};
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+use crate::{config, proxy};
+
+/// 响应体最多保留的字节数，超出部分只计入 body_size
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// 测试请求：按配置的监听规则发送一次真实请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestRequest {
+    pub listen_addr: String,
+    pub host: String,
+    /// 请求路径，可带查询串，例如 `/api/users?id=1`
+    pub uri: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body: Option<String>,
+    pub client_ip: Option<String>,
+    /// true：经本机监听器发送，完整执行访问控制、认证、限流等；
+    /// false（默认）：按路由匹配结果直连上游，跳过这些检查
+    #[serde(default)]
+    pub through_listener: bool,
+    /// 直连时使用的上游下标（对应 upstream_urls），默认第一个
+    pub upstream_index: Option<usize>,
+    pub timeout_ms: Option<u64>,
+}
+
+/// 各阶段耗时（毫秒）；目标为 IP 时 dns_ms 为 0，非 HTTPS 时 tls_ms 为 None
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TestTimings {
+    pub dns_ms: f64,
+    pub connect_ms: f64,
+    pub tls_ms: Option<f64>,
+    /// 从开始到收到响应头
+    pub ttfb_ms: f64,
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestResult {
+    /// 实际请求的地址（上游或本机监听器）
+    pub target_url: String,
+    pub through_listener: bool,
+    /// 直连时选中的上游地址
+    pub upstream: Option<String>,
+    pub remote_addr: String,
+    /// 路由匹配、分流与重写结果
+    pub explain: proxy::RouteExplain,
+    pub status: u16,
+    pub http_version: String,
+    pub response_headers: Vec<(String, String)>,
+    /// UTF-8 文本原样返回，否则为 base64（见 body_base64）
+    pub body: String,
+    pub body_base64: bool,
+    pub body_size: usize,
+    pub body_truncated: bool,
+    pub timings: TestTimings,
+}
+
+pub async fn run(rule: &config::ListenRule, req: TestRequest) -> Result<TestResult> {
+    let explain = proxy::explain_request(
+        rule,
+        &req.listen_addr,
+        &req.host,
+        &req.uri,
+        &req.method,
+        &req.headers,
+        req.body.as_deref().map(str::as_bytes),
+        req.client_ip.as_deref(),
+    )?;
+    let method = Method::from_bytes(req.method.trim().to_ascii_uppercase().as_bytes())
+        .map_err(|_| anyhow!("请求方法无效: {}", req.method))?;
+
+    let (target_url, headers, upstream) = if req.through_listener {
+        let url = listener_url(rule, &req.listen_addr, &req.uri)?;
+        let mut headers = HeaderMap::new();
+        for (k, v) in &req.headers {
+            let name = HeaderName::from_bytes(k.trim().as_bytes()).map_err(|_| anyhow!("请求头名无效: {k}"))?;
+            let value = HeaderValue::from_str(v).map_err(|_| anyhow!("请求头 {k} 的值无效"))?;
+            headers.insert(name, value);
+        }
+        if !req.host.trim().is_empty() {
+            headers.insert(
+                header::HOST,
+                HeaderValue::from_str(req.host.trim()).map_err(|_| anyhow!("Host 无效: {}", req.host))?,
+            );
+        }
+        (url, headers, None)
+    } else {
+        if explain.action != "proxy" {
+            return Err(anyhow!(
+                "命中路由的处理方式为 {}，无法直连上游，请改为经监听器发送",
+                explain.action
+            ));
+        }
+        let idx = req.upstream_index.unwrap_or(0);
+        let url = explain
+            .upstream_urls
+            .get(idx)
+            .cloned()
+            .ok_or_else(|| anyhow!("上游下标 {idx} 超出范围（共 {} 个）", explain.upstream_urls.len()))?;
+        let mut headers = HeaderMap::new();
+        for (k, v) in &explain.outbound_headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(k.as_bytes()), HeaderValue::from_str(v)) {
+                headers.append(name, value);
+            }
+        }
+        (url.clone(), headers, Some(url))
+    };
+
+    let url = url::Url::parse(&target_url).with_context(|| format!("目标地址无效: {target_url}"))?;
+    // 经监听器访问 HTTPS 时按请求 Host 发送 SNI，便于命中对应证书
+    let sni = if req.through_listener {
+        sni_from_host(&req.host)
+    } else {
+        url.host_str().unwrap_or_default().to_string()
+    };
+    let body = Bytes::from(req.body.unwrap_or_default());
+    let timeout = Duration::from_millis(req.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS).max(1));
+
+    let sent = tokio::time::timeout(timeout, send(&url, &sni, method, headers, body))
+        .await
+        .map_err(|_| anyhow!("请求超时（{}ms）", timeout.as_millis()))??;
+
+    Ok(TestResult {
+        target_url,
+        through_listener: req.through_listener,
+        upstream,
+        remote_addr: sent.remote_addr,
+        explain,
+        status: sent.status,
+        http_version: sent.http_version,
+        response_headers: sent.headers,
+        body_base64: std::str::from_utf8(&sent.body).is_err(),
+        body: match String::from_utf8(sent.body) {
+            Ok(s) => s,
+            Err(e) => {
+                use base64::Engine;
+                base64::engine::general_purpose::STANDARD.encode(e.into_bytes())
+            }
+        },
+        body_size: sent.body_size,
+        body_truncated: sent.body_size > MAX_BODY_BYTES,
+        timings: sent.timings,
+    })
+}
+
+/// 本机监听器地址：未指定 IP（`:8080` / `0.0.0.0`）时使用回环地址
+fn listener_url(rule: &config::ListenRule, listen_addr: &str, uri: &str) -> Result<String> {
+    let (addr, _) = proxy::parse_listen_addr(listen_addr)?;
+    let ip = if addr.ip().is_unspecified() {
+        std::net::IpAddr::from([127, 0, 0, 1])
+    } else {
+        addr.ip()
+    };
+    let scheme = if rule.ssl_enable { "https" } else { "http" };
+    let path = if uri.trim().starts_with('/') { uri.trim() } else { "/" };
+    Ok(format!("{scheme}://{}{path}", std::net::SocketAddr::new(ip, addr.port())))
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+struct Sent {
+    remote_addr: String,
+    status: u16,
+    http_version: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    body_size: usize,
+    timings: TestTimings,
+}
+
+/// 从 Host 取 TLS 服务器名：按 Authority 解析以去掉端口，IPv6 去掉方括号；无效时返回空串（改用目标地址）
+pub(crate) fn sni_from_host(host: &str) -> String {
+    host.trim()
+        .parse::<axum::http::uri::Authority>()
+        .map(|a| a.host().trim_start_matches('[').trim_end_matches(']').to_string())
+        .unwrap_or_default()
+}
+
+/// 手动完成 DNS / TCP / TLS / HTTP 各阶段以便分别计时；与代理一致，不校验上游证书
+async fn send(url: &url::Url, sni: &str, method: Method, mut headers: HeaderMap, body: Bytes) -> Result<Sent> {
+    let host = url.host_str().ok_or_else(|| anyhow!("目标地址缺少主机: {url}"))?;
+    let port = url.port_or_known_default().ok_or_else(|| anyhow!("目标地址缺少端口: {url}"))?;
+    let https = match url.scheme() {
+        "http" => false,
+        "https" => true,
+        other => return Err(anyhow!("不支持的协议: {other}")),
+    };
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let mut timings = TestTimings::default();
+    let started = Instant::now();
+
+    let addrs: Vec<std::net::SocketAddr> = match url.host() {
+        Some(url::Host::Ipv4(ip)) => vec![(ip, port).into()],
+        Some(url::Host::Ipv6(ip)) => vec![(ip, port).into()],
+        _ => {
+            let resolved = tokio::net::lookup_host((host, port))
+                .await
+                .with_context(|| format!("DNS 解析失败: {host}"))?
+                .collect();
+            timings.dns_ms = ms(started.elapsed());
+            resolved
+        }
+    };
+
+    let connect_start = Instant::now();
+    let mut last_err = None;
+    let mut tcp = None;
+    for addr in &addrs {
+        match TcpStream::connect(addr).await {
+            Ok(s) => {
+                tcp = Some((s, *addr));
+                break;
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    let (tcp, remote) = match (tcp, last_err) {
+        (Some(t), _) => t,
+        (None, Some(e)) => return Err(anyhow!("连接 {host}:{port} 失败: {e}")),
+        (None, None) => return Err(anyhow!("DNS 未返回 {host} 的地址")),
+    };
+    let _ = tcp.set_nodelay(true);
+    timings.connect_ms = ms(connect_start.elapsed());
+
+    let io: Box<dyn Io> = if https {
+        let tls_start = Instant::now();
+        let server_name = ServerName::try_from(if sni.is_empty() { host } else { sni }.to_string())
+            .map_err(|_| anyhow!("TLS 服务器名无效: {sni}"))?;
+        let stream = tokio_rustls::TlsConnector::from(Arc::new(tls_config()?))
+            .connect(server_name, tcp)
+            .await
+            .context("TLS 握手失败")?;
+        timings.tls_ms = Some(ms(tls_start.elapsed()));
+        Box::new(stream)
+    } else {
+        Box::new(tcp)
+    };
+
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(io))
+        .await
+        .context("HTTP 握手失败")?;
+    tokio::spawn(async move {
+        let _ = conn.await;
+    });
+
+    if !headers.contains_key(header::HOST) {
+        let authority = url[url::Position::BeforeHost..url::Position::AfterPort].to_string();
+        headers.insert(header::HOST, HeaderValue::from_str(&authority)?);
+    }
+    // 长度由 hyper 按请求体重新计算
+    headers.remove(header::CONTENT_LENGTH);
+    headers.remove(header::TRANSFER_ENCODING);
+
+    let mut request = Request::builder()
+        .method(method)
+        .uri(&url[url::Position::BeforePath..])
+        .body(Full::new(body))?;
+    *request.headers_mut() = headers;
+
+    let response = sender.send_request(request).await.context("发送请求失败")?;
+    timings.ttfb_ms = ms(started.elapsed());
+
+    let status = response.status().as_u16();
+    let http_version = format!("{:?}", response.version());
+    let resp_headers = response
+        .headers()
+        .iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+        .collect();
+
+    let mut incoming = response.into_body();
+    let mut kept = Vec::new();
+    let mut body_size = 0usize;
+    while let Some(frame) = incoming.frame().await {
+        let frame = frame.context("读取响应体失败")?;
+        if let Some(data) = frame.data_ref() {
+            body_size += data.len();
+            let room = MAX_BODY_BYTES.saturating_sub(kept.len());
+            kept.extend_from_slice(&data[..room.min(data.len())]);
+        }
+    }
+    timings.total_ms = ms(started.elapsed());
+
+    Ok(Sent {
+        remote_addr: remote.to_string(),
+        status,
+        http_version,
+        headers: resp_headers,
+        body: kept,
+        body_size,
+        timings,
+    })
+}
+
+fn tls_config() -> Result<rustls::ClientConfig> {
+    let provider = CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()));
+    let mut cfg = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+        .with_no_client_auth();
+    cfg.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(cfg)
+}
+
+/// 接受任意服务端证书（仅校验握手签名），与代理的 danger_accept_invalid_certs 保持一致
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,103 @@
+// 路由测试器（tester）模块的单元测试
+
+#[cfg(test)]
+mod tester_tests {
+    use crate::config;
+    use crate::tester::{run, sni_from_host, TestRequest};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::any;
+
+    #[tokio::test]
+    async fn test_tester_direct_upstream() {
+        // 本地上游：回显收到的路径、Host 与 set_headers 注入的请求头
+        let app = axum::Router::new().route(
+            "/{*path}",
+            any(|uri: axum::http::Uri, headers: HeaderMap, body: String| async move {
+                let h = |n: &str| headers.get(n).and_then(|v| v.to_str().ok()).unwrap_or("").to_string();
+                (
+                    StatusCode::CREATED,
+                    [("x-upstream", "local")],
+                    format!("{} host={} tag={} body={body}", uri, h("host"), h("x-tag")),
+                )
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let rule: config::ListenRule = toml::from_str(&format!(
+            r#"listen_addr = ":18080"
+               ssl_enable = false
+               cert_file = ""
+               key_file = ""
+               basic_auth_enable = false
+               basic_auth_username = ""
+               basic_auth_password = ""
+               basic_auth_forward_header = false
+
+               [[routes]]
+               id = "redirect"
+               path = "/old"
+               match_type = "exact"
+               [routes.return]
+               status = 301
+               location = "/new"
+
+               [[routes]]
+               id = "api"
+               path = "/"
+               upstreams = [{{ url = "http://{addr}", weight = 1 }}]
+               set_headers = {{ x-tag = "$host" }}"#
+        ))
+        .unwrap();
+
+        let req = |uri: &str| TestRequest {
+            listen_addr: ":18080".into(),
+            host: "example.com".into(),
+            uri: uri.into(),
+            method: "POST".into(),
+            headers: Default::default(),
+            body: Some("ping".into()),
+            client_ip: None,
+            through_listener: false,
+            upstream_index: None,
+            timeout_ms: Some(5_000),
+        };
+
+        let r = run(&rule, req("/hello?x=1")).await.unwrap();
+        assert_eq!(r.status, 201);
+        assert_eq!(r.explain.selected_route_id.as_deref(), Some("api"));
+        assert_eq!(r.upstream.as_deref(), Some(format!("http://{addr}/hello?x=1").as_str()));
+        assert_eq!(r.body, "/hello?x=1 host=example.com tag=example.com body=ping");
+        assert!(!r.body_base64 && !r.body_truncated);
+        assert!(r.response_headers.iter().any(|(k, v)| k == "x-upstream" && v == "local"));
+        assert_eq!(r.timings.dns_ms, 0.0);
+        assert!(r.timings.tls_ms.is_none());
+        assert!(r.timings.ttfb_ms <= r.timings.total_ms);
+
+        // return 路由没有上游，直连模式报错
+        let err = run(&rule, req("/old")).await.unwrap_err().to_string();
+        assert!(err.contains("return"), "{err}");
+
+        let mut bad = req("/x");
+        bad.upstream_index = Some(3);
+        assert!(run(&rule, bad).await.is_err());
+    }
+
+    #[test]
+    fn test_sni_from_host() {
+        let cases = [
+            ("example.com", "example.com"),
+            ("example.com:8443", "example.com"),
+            (" api.example.com ", "api.example.com"),
+            ("[::1]:8443", "::1"),
+            ("[2001:db8::1]", "2001:db8::1"),
+            ("127.0.0.1:443", "127.0.0.1"),
+            ("", ""),
+            ("bad host", ""),
+        ];
+        for (host, want) in cases {
+            assert_eq!(sni_from_host(host), want, "host={host}");
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+use axum::http::{header, HeaderMap, HeaderName};
+use std::net::IpAddr;
+
+use crate::{access_control, config, vars};
+
+pub const DEFAULT_HASH_KEY: &str = "$client_ip";
+
+/// 预编译的分流规则：所有已配置的条件都满足时命中
+pub struct CompiledSplit {
+    pub name: String,
+    pub upstreams: Vec<config::Upstream>,
+    header: Option<(HeaderName, Option<String>)>,
+    cookie: Option<(String, Option<String>)>,
+    query: Option<(String, Option<String>)>,
+    cidrs: Vec<(IpAddr, u8)>,
+    /// 按哈希分桶的比例（万分比）与哈希键模板
+    bucket: Option<(u64, String)>,
+}
+
+/// 稳定哈希：同一个键始终落在同一个桶，用户不会在金丝雀与主版本之间来回切换
+#[inline]
+fn bucket_of(key: &str) -> u64 {
+    const FNV_OFFSET: u64 = 14695981039346656037;
+    const FNV_PRIME: u64 = 1099511628211;
+
+    let mut h = FNV_OFFSET;
+    for &b in key.as_bytes() {
+        h ^= b as u64;
+        h = h.wrapping_mul(FNV_PRIME);
+    }
+    h % 10_000
+}
+
+fn value_ok(expected: &Option<String>, actual: Option<&str>) -> bool {
+    match (expected, actual) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        (Some(e), Some(a)) => e == a,
+    }
+}
+
+fn compile_match(m: Option<&config::SplitMatch>) -> Option<(String, Option<String>)> {
+    m.map(|m| (m.name.trim().to_string(), m.value.clone()))
+}
+
+impl CompiledSplit {
+    pub fn compile(cfg: &config::TrafficSplit, label: &str) -> Result<Self> {
+        let name = cfg.name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("路由 {label} 的 splits 缺少 name"));
+        }
+        if cfg.upstreams.is_empty() {
+            return Err(anyhow!("路由 {label} 的分流 {name} 未配置 upstreams"));
+        }
+
+        let header = match cfg.header.as_ref() {
+            Some(h) => {
+                let n = HeaderName::from_bytes(h.name.trim().as_bytes())
+                    .map_err(|_| anyhow!("路由 {label} 的分流 {name} 请求头名无效: {}", h.name))?;
+                Some((n, h.value.clone()))
+            }
+            None => None,
+        };
+
+        let mut cidrs = Vec::new();
+        for c in cfg.client_cidrs.iter().flatten() {
+            let parsed = access_control::parse_cidr(c)
+                .ok_or_else(|| anyhow!("路由 {label} 的分流 {name} CIDR 无效: {c}"))?;
+            cidrs.push(parsed);
+        }
+
+        let bucket = match cfg.percent {
+            Some(p) if !(0.0..=100.0).contains(&p) => {
+                return Err(anyhow!("路由 {label} 的分流 {name} percent 需在 0-100 之间: {p}"));
+            }
+            Some(p) => {
+                let key = cfg
+                    .hash_key
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or(DEFAULT_HASH_KEY);
+                Some(((p * 100.0).round() as u64, key.to_string()))
+            }
+            None => None,
+        };
+
+        let split = Self {
+            name: name.to_string(),
+            upstreams: cfg.upstreams.clone(),
+            header,
+            cookie: compile_match(cfg.cookie.as_ref()),
+            query: compile_match(cfg.query.as_ref()),
+            cidrs,
+            bucket,
+        };
+        if split.header.is_none()
+            && split.cookie.is_none()
+            && split.query.is_none()
+            && split.cidrs.is_empty()
+            && split.bucket.is_none()
+        {
+            return Err(anyhow!("路由 {label} 的分流 {name} 未配置任何条件"));
+        }
+        Ok(split)
+    }
+
+    pub fn matches<F>(&self, headers: &HeaderMap, query: Option<&str>, client_ip: &str, lookup: F) -> bool
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some((name, expected)) = self.header.as_ref() {
+            let actual = headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+            if !value_ok(expected, actual) {
+                return false;
+            }
+        }
+
+        if let Some((name, expected)) = self.cookie.as_ref() {
+            let actual = headers
+                .get_all(header::COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .find_map(|kv| {
+                    let (k, v) = kv.trim().split_once('=')?;
+                    (k == name).then_some(v)
+                });
+            if !value_ok(expected, actual) {
+                return false;
+            }
+        }
+
+        if let Some((name, expected)) = self.query.as_ref() {
+            let actual = query.and_then(|q| {
+                q.split('&').find_map(|kv| {
+                    let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+                    (k == name).then_some(v)
+                })
+            });
+            if !value_ok(expected, actual) {
+                return false;
+            }
+        }
+
+        if !self.cidrs.is_empty() {
+            let Ok(ip) = client_ip.trim().parse::<IpAddr>() else {
+                return false;
+            };
+            if !self.cidrs.iter().any(|c| access_control::ip_in_cidr(&ip, c)) {
+                return false;
+            }
+        }
+
+        if let Some((basis_points, key)) = self.bucket.as_ref() {
+            if bucket_of(&vars::expand(key, lookup)) >= *basis_points {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,73 @@
+// 流量分流（traffic_split）模块的单元测试
+
+#[cfg(test)]
+mod traffic_split_tests {
+    use crate::config;
+    use crate::traffic_split::CompiledSplit;
+    use axum::http::{HeaderMap, HeaderValue};
+
+    fn split(toml_src: &str) -> anyhow::Result<CompiledSplit> {
+        let cfg: config::TrafficSplit = toml::from_str(toml_src).expect("invalid split toml");
+        CompiledSplit::compile(&cfg, "app")
+    }
+
+    #[test]
+    fn test_traffic_split_conditions() {
+        let splits = [
+            split(
+                r#"name = "beta-header"
+                   header = { name = "X-Beta", value = "1" }
+                   upstreams = [{ url = "http://beta", weight = 1 }]"#,
+            )
+            .unwrap(),
+            split(
+                r#"name = "office"
+                   client_cidrs = ["10.0.0.0/8"]
+                   cookie = { name = "staff" }
+                   upstreams = [{ url = "http://office", weight = 1 }]"#,
+            )
+            .unwrap(),
+            split(
+                r#"name = "canary"
+                   percent = 30
+                   hash_key = "$cookie_uid"
+                   upstreams = [{ url = "http://canary", weight = 1 }]"#,
+            )
+            .unwrap(),
+        ];
+        let pick = |headers: &HeaderMap, ip: &str, uid: &str| {
+            splits
+                .iter()
+                .find(|s| s.matches(headers, None, ip, |n| (n == "cookie_uid").then(|| uid.to_string())))
+                .map(|s| s.name.clone())
+        };
+
+        let mut beta = HeaderMap::new();
+        beta.insert("x-beta", HeaderValue::from_static("1"));
+        let mut staff = HeaderMap::new();
+        staff.insert("cookie", HeaderValue::from_static("a=b; staff=yes"));
+        let empty = HeaderMap::new();
+
+        assert_eq!(pick(&beta, "1.2.3.4", "").as_deref(), Some("beta-header"));
+        assert_eq!(pick(&staff, "10.9.9.9", "").as_deref(), Some("office"));
+        // 条件需全部满足：Cookie 存在但不在网段内
+        assert_ne!(pick(&staff, "1.2.3.4", "").as_deref(), Some("office"));
+
+        // 同一用户始终落在同一侧，整体比例接近配置值
+        let canary = (0..2000)
+            .filter(|i| pick(&empty, "1.2.3.4", &format!("user-{i}")).is_some())
+            .count();
+        assert!((500..700).contains(&canary), "canary={canary}");
+        for i in 0..50 {
+            let uid = format!("user-{i}");
+            assert_eq!(pick(&empty, "1.2.3.4", &uid), pick(&empty, "5.6.7.8", &uid));
+        }
+
+        // 未配置任何条件的分流规则拒绝编译
+        assert!(split(
+            r#"name = "x"
+               upstreams = [{ url = "http://x", weight = 1 }]"#
+        )
+        .is_err());
+    }
+}
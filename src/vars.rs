@@ -0,0 +1,65 @@
+/// 展开字符串中的 Nginx 风格变量：`$name` 或 `${name}`
+///
+/// - 变量名由字母、数字、下划线组成（因此 `$1` 这类捕获组引用也按变量处理）
+/// - `lookup` 返回 None 的变量原样保留，便于排查拼写错误
+pub fn expand<F>(raw: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    // 仅在真的包含变量时才分配
+    if !raw.contains('$') {
+        return raw.to_string();
+    }
+
+    let mut out = String::with_capacity(raw.len() + 32);
+    let mut rest = raw;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        // ${name} 形式：允许变量后紧跟字母数字
+        if let Some(braced) = after.strip_prefix('{') {
+            if let Some(end) = braced.find('}') {
+                let name = &braced[..end];
+                match lookup(name) {
+                    Some(v) => out.push_str(&v),
+                    None => out.push_str(&rest[pos..pos + end + 3]),
+                }
+                rest = &braced[end + 1..];
+                continue;
+            }
+        }
+
+        let name_len = after
+            .bytes()
+            .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+            .count();
+        if name_len == 0 {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+
+        let name = &after[..name_len];
+        match lookup(name) {
+            Some(v) => out.push_str(&v),
+            None => {
+                out.push('$');
+                out.push_str(name);
+            }
+        }
+        rest = &after[name_len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 列出字符串中引用的变量名（`$name` 与 `${name}` 两种形式）
+pub fn names(raw: &str) -> Vec<String> {
+    let names = std::cell::RefCell::new(Vec::new());
+    expand(raw, |name| {
+        names.borrow_mut().push(name.to_string());
+        None
+    });
+    names.into_inner()
+}
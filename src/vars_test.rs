@@ -0,0 +1,47 @@
+// 变量展开（vars）模块的单元测试
+
+#[cfg(test)]
+mod vars_tests {
+    use crate::vars::{expand, names};
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "host" => Some("a.com".to_string()),
+            "request_uri" => Some("/x?y=1".to_string()),
+            "1" => Some("42".to_string()),
+            "empty" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        // (模板, 期望结果)
+        let cases = [
+            ("no vars", "no vars"),
+            ("https://$host$request_uri", "https://a.com/x?y=1"),
+            ("${host}x", "a.comx"),                // 花括号允许变量后紧跟字母数字
+            ("$hostx", "$hostx"),                  // 不加花括号时变量名取到最长
+            ("/user/$1/posts", "/user/42/posts"),  // 捕获组引用
+            ("[$empty]", "[]"),
+            ("$unknown and ${unknown}", "$unknown and ${unknown}"), // 未知变量原样保留
+            ("price: 5$", "price: 5$"),
+            ("$$host", "$a.com"),
+            ("${host", "${host"),                  // 未闭合的花括号按普通文本处理
+        ];
+        for (raw, want) in cases {
+            assert_eq!(expand(raw, lookup), want, "raw={raw}");
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(names("$scheme://${host}$request_uri $cookie_sid"), [
+            "scheme",
+            "host",
+            "request_uri",
+            "cookie_sid"
+        ]);
+        assert!(names("plain text $ 5").is_empty());
+    }
+}